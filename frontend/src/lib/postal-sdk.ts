@@ -1,4 +1,17 @@
-export type SearchMode = "exact" | "prefix" | "partial";
+export type SearchMode = "exact" | "prefix" | "partial" | "fuzzy";
+
+export type PostalCodeKind = "residential" | "business" | "po_box";
+
+export type UpdateStatus = "unchanged" | "changed" | "abolished";
+
+export type UpdateReason =
+  | "none"
+  | "municipal_reorganization"
+  | "address_display"
+  | "land_readjustment"
+  | "postal_district_adjustment"
+  | "correction"
+  | "abolished";
 
 export type PostalCodeRecord = {
   zip_code: string;
@@ -7,6 +20,63 @@ export type PostalCodeRecord = {
   prefecture: string;
   city: string;
   town: string;
+  prefecture_kana: string;
+  city_kana: string;
+  town_kana: string;
+  town_detail: string;
+  chome_range: string;
+  floor: string;
+  kind: PostalCodeKind;
+  business_name: string;
+  business_name_kana: string;
+  street_address: string;
+  town_has_multiple_zip_codes: boolean;
+  numbered_per_koaza: boolean;
+  has_chome: boolean;
+  covers_multiple_towns: boolean;
+  update_status: UpdateStatus;
+  update_reason: UpdateReason;
+  prefecture_roma: string;
+  city_roma: string;
+  town_roma: string;
+};
+
+export type Lang = "ja" | "en";
+
+export type ScoredPostalCodeRecord = PostalCodeRecord & {
+  score: number;
+  edit_distance?: number;
+};
+
+export type BatchLookupStatus = "found" | "not_found" | "invalid";
+
+export type BatchLookupResult = {
+  input: string;
+  zip_code: string | null;
+  status: BatchLookupStatus;
+  records: PostalCodeRecord[];
+};
+
+export type ValidationReason = "bad_format" | "unknown" | "retired";
+
+export type ValidateResult = {
+  input: string;
+  normalized: string | null;
+  valid: boolean;
+  reason: ValidationReason | null;
+  message: string | null;
+  last_seen_data_version: string | null;
+  records: PostalCodeRecord[];
+};
+
+export type ResolveResult = {
+  prefecture: string;
+  city: string;
+  town: string;
+  remainder: string;
+  confidence: number;
+  best_match: PostalCodeRecord | null;
+  candidates: PostalCodeRecord[];
 };
 
 export type PrefectureRecord = {
@@ -17,11 +87,99 @@ export type PrefectureRecord = {
 export type CityRecord = {
   city_id: string;
   city: string;
+  /** Retired city_id this municipality replaced; set by resolveCity only. */
+  successor_of?: string;
 };
 
+export type SearchField = "all" | "city" | "town";
+
 export type PostalSearchOptions = {
   mode?: SearchMode;
   limit?: number;
+  field?: SearchField;
+  prefectureId?: number;
+  cityId?: string;
+  townDetail?: string;
+  floor?: string;
+  lang?: Lang;
+  /** data_version, YYYY-MM-DD or RFC 3339; not combinable with mode "fuzzy". */
+  asOf?: string;
+};
+
+export type SearchPage = {
+  items: PostalCodeRecord[];
+  next_cursor: string | null;
+  total: number | null;
+};
+
+export type PostalSearchPageOptions = Omit<PostalSearchOptions, "mode" | "asOf"> & {
+  mode?: Exclude<SearchMode, "fuzzy">;
+  cursor?: string;
+  includeTotal?: boolean;
+};
+
+export type SuggestionKind = "postal_code" | "city" | "town";
+
+export type Suggestion = {
+  kind: SuggestionKind;
+  label: string;
+  zip_code: string | null;
+  prefecture_id: number;
+  city_id: string;
+};
+
+export type DataVersionStatus = "success" | "failed" | "rollback" | "unchanged";
+
+export type DataVersion = {
+  data_version: string;
+  status: DataVersionStatus;
+  run_finished_at: string;
+  total_count: number;
+  inserted_count: number;
+  updated_count: number;
+  deleted_count: number;
+  diff_month: string | null;
+  source_sha256: string | null;
+  snapshot_version: string | null;
+};
+
+export type ModifiedRow = {
+  before: PostalCodeRecord;
+  after: PostalCodeRecord;
+  changed_fields: string[];
+};
+
+export type DataVersionDiff = {
+  from: string;
+  to: string;
+  added: PostalCodeRecord[];
+  removed: PostalCodeRecord[];
+  modified: ModifiedRow[];
+};
+
+export type HistoryAddress = {
+  prefecture_id: number;
+  city_id: string;
+  prefecture: string;
+  city: string;
+  town: string;
+  business_name: string;
+};
+
+export type HistoryEvent = {
+  data_version: string;
+  effective_at: string;
+  kind: "added" | "changed" | "retired";
+  before: HistoryAddress[];
+  after: HistoryAddress[];
+  update_reasons: UpdateReason[];
+};
+
+export type PostalCodeHistory = {
+  zip_code: string;
+  status: "active" | "retired";
+  tracked_since: string;
+  events: HistoryEvent[];
 };
 
 export type PostalSdkOptions = {
@@ -42,6 +200,30 @@ function normalizeZip(zipInput: string): string {
   return zipInput.replace(/\D/g, "").slice(0, 7);
 }
 
+function applySearchFilters(query: URLSearchParams, options: PostalSearchOptions): void {
+  if (options.field) {
+    query.set("field", options.field);
+  }
+  if (options.prefectureId) {
+    query.set("prefecture_id", String(options.prefectureId));
+  }
+  if (options.cityId) {
+    query.set("city_id", options.cityId);
+  }
+  if (options.townDetail) {
+    query.set("town_detail", options.townDetail);
+  }
+  if (options.floor) {
+    query.set("floor", options.floor);
+  }
+  if (options.lang) {
+    query.set("lang", options.lang);
+  }
+  if (options.asOf) {
+    query.set("as_of", options.asOf);
+  }
+}
+
 export class PostalSdk {
   private readonly baseUrl: string;
   private readonly fetcher: typeof fetch;
@@ -52,18 +234,50 @@ export class PostalSdk {
     this.fetcher = fetcher === globalThis.fetch ? globalThis.fetch.bind(globalThis) : fetcher;
   }
 
-  async lookupZip(zipInput: string): Promise<PostalCodeRecord[]> {
+  async lookupZip(zipInput: string, lang?: Lang, asOf?: string): Promise<PostalCodeRecord[]> {
     const zip = normalizeZip(zipInput);
     if (zip.length !== 7) {
       return [];
     }
-    return this.request<PostalCodeRecord[]>(`/postal_codes/${zip}`);
+    const query = new URLSearchParams();
+    if (lang) {
+      query.set("lang", lang);
+    }
+    if (asOf) {
+      query.set("as_of", asOf);
+    }
+    const suffix = query.toString() ? `?${query.toString()}` : "";
+    return this.request<PostalCodeRecord[]>(`/postal_codes/${zip}${suffix}`);
+  }
+
+  async lookupZipHistory(zipInput: string): Promise<PostalCodeHistory | null> {
+    const zip = normalizeZip(zipInput);
+    if (zip.length !== 7) {
+      return null;
+    }
+    return this.request<PostalCodeHistory>(`/postal_codes/${zip}/history`);
+  }
+
+  async lookupZipBatch(zipInputs: string[], lang?: Lang): Promise<BatchLookupResult[]> {
+    if (zipInputs.length === 0) {
+      return [];
+    }
+    const query = lang ? `?lang=${lang}` : "";
+    const response = await this.request<{ results: BatchLookupResult[] }>(
+      `/postal_codes/batch${query}`,
+      {
+        method: "POST",
+        headers: { "Content-Type": "application/json" },
+        body: JSON.stringify(zipInputs),
+      },
+    );
+    return response.results;
   }
 
   async searchAddress(
     address: string,
     options: PostalSearchOptions = {},
-  ): Promise<PostalCodeRecord[]> {
+  ): Promise<ScoredPostalCodeRecord[]> {
     const keyword = address.trim();
     if (!keyword) {
       return [];
@@ -76,7 +290,54 @@ export class PostalSdk {
       mode,
       limit: String(limit),
     });
-    return this.request<PostalCodeRecord[]>(`/postal_codes/search?${query.toString()}`);
+    applySearchFilters(query, options);
+    return this.request<ScoredPostalCodeRecord[]>(`/postal_codes/search?${query.toString()}`);
+  }
+
+  async searchAddressPage(
+    address: string,
+    options: PostalSearchPageOptions = {},
+  ): Promise<SearchPage> {
+    const keyword = address.trim();
+    if (!keyword) {
+      return { items: [], next_cursor: null, total: options.includeTotal ? 0 : null };
+    }
+
+    const query = new URLSearchParams({
+      address: keyword,
+      mode: options.mode ?? "partial",
+      limit: String(clampLimit(options.limit)),
+    });
+    applySearchFilters(query, options);
+    if (options.cursor) {
+      query.set("cursor", options.cursor);
+    }
+    if (options.includeTotal) {
+      query.set("include_total", "true");
+    }
+    return this.request<SearchPage>(`/v2/postal_codes/search?${query.toString()}`);
+  }
+
+  async validateZip(zipInput: string): Promise<ValidateResult> {
+    const query = new URLSearchParams({ zip_code: zipInput });
+    return this.request<ValidateResult>(`/postal_codes/validate?${query.toString()}`);
+  }
+
+  async resolveAddress(address: string): Promise<ResolveResult> {
+    const query = new URLSearchParams({ address: address.trim() });
+    return this.request<ResolveResult>(`/postal_codes/resolve?${query.toString()}`);
+  }
+
+  async suggest(input: string, limit?: number): Promise<Suggestion[]> {
+    const q = input.trim();
+    if (!q) {
+      return [];
+    }
+    const query = new URLSearchParams({ q });
+    if (limit) {
+      query.set("limit", String(Math.max(1, Math.min(50, Math.floor(limit)))));
+    }
+    return this.request<Suggestion[]>(`/postal_codes/suggest?${query.toString()}`);
   }
 
   async listPrefectures(): Promise<PrefectureRecord[]> {
@@ -88,7 +349,29 @@ export class PostalSdk {
     return this.request<CityRecord[]>(`/postal_codes/cities?${query.toString()}`);
   }
 
-  private async request<T>(path: string): Promise<T> {
+  /** Current municipality for a city_id; a code retired by a merger yields its successors. */
+  async resolveCity(cityId: string): Promise<CityRecord[]> {
+    const query = new URLSearchParams({ city_id: cityId });
+    return this.request<CityRecord[]>(`/postal_codes/cities?${query.toString()}`);
+  }
+
+  async listDataVersions(limit?: number): Promise<DataVersion[]> {
+    const query = new URLSearchParams();
+    if (limit) {
+      query.set("limit", String(Math.max(1, Math.min(1000, Math.floor(limit)))));
+    }
+    const suffix = query.toString() ? `?${query.toString()}` : "";
+    return this.request<DataVersion[]>(`/data_versions${suffix}`);
+  }
+
+  async diffDataVersions(from: string, to: string): Promise<DataVersionDiff> {
+    return this.request<DataVersionDiff>(
+      `/data_versions/${encodeURIComponent(from)}/diff/${encodeURIComponent(to)}`,
+    );
+  }
+
+  async diffDataVersionsCsv(from: string, to: string): Promise<string> {
+    const path = `/data_versions/${encodeURIComponent(from)}/diff/${encodeURIComponent(to)}?format=csv`;
     const response = await this.fetcher(`${this.baseUrl}${path}`);
     if (!response.ok) {
       const errorBody = await response.text();
@@ -96,6 +379,17 @@ export class PostalSdk {
         `Postal API request failed (${response.status}): ${errorBody || response.statusText}`,
       );
     }
+    return response.text();
+  }
+
+  private async request<T>(path: string, init?: RequestInit): Promise<T> {
+    const response = await this.fetcher(`${this.baseUrl}${path}`, init);
+    if (!response.ok) {
+      const errorBody = await response.text();
+      throw new Error(
+        `Postal API request failed (${response.status}): ${errorBody || response.statusText}`,
+      );
+    }
     return (await response.json()) as T;
   }
 }
@@ -4,9 +4,10 @@ set -euo pipefail
 ROOT_DIR="$(cd "$(dirname "${BASH_SOURCE[0]}")/.." && pwd)"
 OUT_DB="${1:-$ROOT_DIR/storage/sqlite/postal_codes.sqlite3}"
 TMP_CSV="$(mktemp -t postal_codes_sqlite.XXXXXX.csv)"
+TMP_CHANGES_CSV="$(mktemp -t municipality_changes_sqlite.XXXXXX.csv)"
 
 cleanup() {
-  rm -f "$TMP_CSV"
+  rm -f "$TMP_CSV" "$TMP_CHANGES_CSV"
 }
 trap cleanup EXIT
 
@@ -24,7 +25,7 @@ fi
 echo "[1/4] Checking PostgreSQL container readiness..."
 docker exec postgres_container pg_isready -U postgres -d zip_code_db >/dev/null
 
-echo "[2/4] Exporting postal_codes from PostgreSQL..."
+echo "[2/4] Exporting postal_codes and municipality_changes from PostgreSQL..."
 docker exec postgres_container psql -U postgres -d zip_code_db -c "\copy (
   SELECT
     zip_code,
@@ -32,9 +33,43 @@ docker exec postgres_container psql -U postgres -d zip_code_db -c "\copy (
     city_id,
     prefecture,
     city,
-    COALESCE(town, '')
+    COALESCE(town, ''),
+    prefecture_kana,
+    city_kana,
+    town_kana,
+    town_detail,
+    chome_range,
+    floor,
+    kind,
+    business_name,
+    business_name_kana,
+    street_address,
+    town_has_multiple_zip_codes::int,
+    numbered_per_koaza::int,
+    has_chome::int,
+    covers_multiple_towns::int,
+    update_status,
+    update_reason,
+    prefecture_roma,
+    city_roma,
+    town_roma,
+    prefecture_normalized,
+    city_normalized,
+    town_normalized
   FROM postal_codes
 ) TO STDOUT WITH CSV" >"$TMP_CSV"
+docker exec postgres_container psql -U postgres -d zip_code_db -c "\copy (
+  SELECT
+    old_city_id,
+    new_city_id,
+    old_city,
+    new_city,
+    effective_date::text,
+    data_version,
+    source,
+    shared_zip_codes
+  FROM municipality_changes
+) TO STDOUT WITH CSV" >"$TMP_CHANGES_CSV"
 
 mkdir -p "$(dirname "$OUT_DB")"
 rm -f "$OUT_DB"
@@ -50,14 +85,58 @@ CREATE TABLE postal_codes (
   prefecture TEXT NOT NULL,
   city TEXT NOT NULL,
   town TEXT NOT NULL,
+  prefecture_kana TEXT NOT NULL DEFAULT '',
+  city_kana TEXT NOT NULL DEFAULT '',
+  town_kana TEXT NOT NULL DEFAULT '',
+  town_detail TEXT NOT NULL DEFAULT '',
+  chome_range TEXT NOT NULL DEFAULT '',
+  floor TEXT NOT NULL DEFAULT '',
+  kind TEXT NOT NULL DEFAULT 'residential',
+  business_name TEXT NOT NULL DEFAULT '',
+  business_name_kana TEXT NOT NULL DEFAULT '',
+  street_address TEXT NOT NULL DEFAULT '',
+  town_has_multiple_zip_codes INTEGER NOT NULL DEFAULT 0,
+  numbered_per_koaza INTEGER NOT NULL DEFAULT 0,
+  has_chome INTEGER NOT NULL DEFAULT 0,
+  covers_multiple_towns INTEGER NOT NULL DEFAULT 0,
+  update_status TEXT NOT NULL DEFAULT 'unchanged',
+  update_reason TEXT NOT NULL DEFAULT 'none',
+  prefecture_roma TEXT NOT NULL DEFAULT '',
+  city_roma TEXT NOT NULL DEFAULT '',
+  town_roma TEXT NOT NULL DEFAULT '',
+  prefecture_normalized TEXT NOT NULL DEFAULT '',
+  city_normalized TEXT NOT NULL DEFAULT '',
+  town_normalized TEXT NOT NULL DEFAULT '',
   PRIMARY KEY (zip_code, prefecture_id, city, town)
 );
+CREATE TABLE municipality_changes (
+  old_city_id TEXT NOT NULL,
+  new_city_id TEXT NOT NULL,
+  old_city TEXT NOT NULL DEFAULT '',
+  new_city TEXT NOT NULL DEFAULT '',
+  effective_date TEXT,
+  data_version TEXT,
+  source TEXT NOT NULL,
+  shared_zip_codes INTEGER NOT NULL DEFAULT 0,
+  PRIMARY KEY (old_city_id, new_city_id)
+);
 "
 
 echo "[4/4] Importing CSV into SQLite..."
 sqlite3 "$OUT_DB" ".mode csv" ".import '$TMP_CSV' postal_codes"
+sqlite3 "$OUT_DB" ".mode csv" ".import '$TMP_CHANGES_CSV' municipality_changes"
+sqlite3 "$OUT_DB" "
+UPDATE municipality_changes SET effective_date = NULL WHERE effective_date = '';
+UPDATE municipality_changes SET data_version = NULL WHERE data_version = '';
+"
 sqlite3 "$OUT_DB" "
 CREATE INDEX idx_postal_codes_zip_code ON postal_codes (zip_code, town);
+CREATE VIRTUAL TABLE postal_codes_fts USING fts5(
+  prefecture_normalized, city_normalized, town_normalized,
+  content='postal_codes',
+  tokenize='trigram'
+);
+INSERT INTO postal_codes_fts(postal_codes_fts) VALUES('rebuild');
 ANALYZE;
 VACUUM;
 "
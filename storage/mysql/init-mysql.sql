@@ -10,6 +10,28 @@ CREATE TABLE IF NOT EXISTS postal_codes (
     prefecture VARCHAR(32) NOT NULL COMMENT '都道府県',
     city VARCHAR(50) NOT NULL COMMENT '市区町村',
     town VARCHAR(500) COMMENT '町名',
+    prefecture_kana VARCHAR(64) NOT NULL DEFAULT '' COMMENT '都道府県カナ',
+    city_kana VARCHAR(100) NOT NULL DEFAULT '' COMMENT '市区町村カナ',
+    town_kana VARCHAR(1000) NOT NULL DEFAULT '' COMMENT '町名カナ',
+    town_detail VARCHAR(500) NOT NULL DEFAULT '' COMMENT '町域名の括弧内（丁目・階層等）',
+    chome_range VARCHAR(100) NOT NULL DEFAULT '' COMMENT '丁目の範囲',
+    floor VARCHAR(32) NOT NULL DEFAULT '' COMMENT '高層ビルの階層',
+    kind VARCHAR(16) NOT NULL DEFAULT 'residential' COMMENT 'residential | business | po_box',
+    business_name VARCHAR(200) NOT NULL DEFAULT '' COMMENT '大口事業所名',
+    business_name_kana VARCHAR(400) NOT NULL DEFAULT '' COMMENT '大口事業所名カナ',
+    street_address VARCHAR(200) NOT NULL DEFAULT '' COMMENT '小字名、丁目、番地等',
+    town_has_multiple_zip_codes BOOLEAN NOT NULL DEFAULT FALSE COMMENT '一町域が二以上の郵便番号で表される',
+    numbered_per_koaza BOOLEAN NOT NULL DEFAULT FALSE COMMENT '小字毎に番地が起番されている',
+    has_chome BOOLEAN NOT NULL DEFAULT FALSE COMMENT '丁目を有する町域',
+    covers_multiple_towns BOOLEAN NOT NULL DEFAULT FALSE COMMENT '一つの郵便番号で二以上の町域を表す',
+    update_status VARCHAR(16) NOT NULL DEFAULT 'unchanged' COMMENT '更新の表示',
+    update_reason VARCHAR(32) NOT NULL DEFAULT 'none' COMMENT '変更理由',
+    prefecture_roma VARCHAR(64) NOT NULL DEFAULT '' COMMENT '都道府県ローマ字',
+    city_roma VARCHAR(100) NOT NULL DEFAULT '' COMMENT '市区町村ローマ字',
+    town_roma VARCHAR(500) NOT NULL DEFAULT '' COMMENT '町名ローマ字',
+    prefecture_normalized VARCHAR(64) NOT NULL DEFAULT '' COMMENT '都道府県（検索用に正規化）',
+    city_normalized VARCHAR(100) NOT NULL DEFAULT '' COMMENT '市区町村（検索用に正規化）',
+    town_normalized VARCHAR(500) NOT NULL DEFAULT '' COMMENT '町名（検索用に正規化）',
     created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP COMMENT '作成日時',
     updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP ON UPDATE CURRENT_TIMESTAMP COMMENT '更新日時',
     PRIMARY KEY (zip_code, prefecture_id, city, town)
@@ -34,6 +56,9 @@ CREATE TABLE IF NOT EXISTS data_update_audits (
     total_count BIGINT NOT NULL,
     status VARCHAR(16) NOT NULL,
     error_message TEXT,
+    diff_month CHAR(4),
+    source_sha256 CHAR(64),
+    dataset_fingerprint CHAR(64),
     created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
     INDEX idx_data_update_audits_created_at (created_at)
 );
@@ -47,9 +72,45 @@ CREATE TABLE IF NOT EXISTS postal_codes_snapshots (
     prefecture VARCHAR(32) NOT NULL,
     city VARCHAR(50) NOT NULL,
     town VARCHAR(500),
+    prefecture_kana VARCHAR(64) NOT NULL DEFAULT '',
+    city_kana VARCHAR(100) NOT NULL DEFAULT '',
+    town_kana VARCHAR(1000) NOT NULL DEFAULT '',
+    town_detail VARCHAR(500) NOT NULL DEFAULT '',
+    chome_range VARCHAR(100) NOT NULL DEFAULT '',
+    floor VARCHAR(32) NOT NULL DEFAULT '',
+    kind VARCHAR(16) NOT NULL DEFAULT 'residential',
+    business_name VARCHAR(200) NOT NULL DEFAULT '',
+    business_name_kana VARCHAR(400) NOT NULL DEFAULT '',
+    street_address VARCHAR(200) NOT NULL DEFAULT '',
+    town_has_multiple_zip_codes BOOLEAN NOT NULL DEFAULT FALSE,
+    numbered_per_koaza BOOLEAN NOT NULL DEFAULT FALSE,
+    has_chome BOOLEAN NOT NULL DEFAULT FALSE,
+    covers_multiple_towns BOOLEAN NOT NULL DEFAULT FALSE,
+    update_status VARCHAR(16) NOT NULL DEFAULT 'unchanged',
+    update_reason VARCHAR(32) NOT NULL DEFAULT 'none',
+    prefecture_roma VARCHAR(64) NOT NULL DEFAULT '',
+    city_roma VARCHAR(100) NOT NULL DEFAULT '',
+    town_roma VARCHAR(500) NOT NULL DEFAULT '',
+    prefecture_normalized VARCHAR(64) NOT NULL DEFAULT '',
+    city_normalized VARCHAR(100) NOT NULL DEFAULT '',
+    town_normalized VARCHAR(500) NOT NULL DEFAULT '',
     created_at TIMESTAMP NOT NULL,
     updated_at TIMESTAMP NOT NULL,
     snapshot_created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
     PRIMARY KEY (data_version, zip_code, prefecture_id, city, town),
     INDEX idx_postal_codes_snapshots_version (data_version)
 );
+
+-- Create table of municipality code changes (mergers, splits) between data versions
+CREATE TABLE IF NOT EXISTS municipality_changes (
+    old_city_id VARCHAR(10) NOT NULL,
+    new_city_id VARCHAR(10) NOT NULL,
+    old_city VARCHAR(50) NOT NULL DEFAULT '',
+    new_city VARCHAR(50) NOT NULL DEFAULT '',
+    effective_date DATE,
+    data_version VARCHAR(32),
+    source VARCHAR(16) NOT NULL,
+    shared_zip_codes BIGINT NOT NULL DEFAULT 0,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (old_city_id, new_city_id)
+);
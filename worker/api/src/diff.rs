@@ -0,0 +1,266 @@
+use common::models::PostalCode;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use utoipa::ToSchema;
+
+/// Columns of `postal_code_columns!` by name, in the same order. A snapshot row counts as
+/// unchanged when another snapshot has a row with equal values in all of them; the
+/// `*_normalized` columns are derived from these and left out.
+pub const SNAPSHOT_COLUMNS: &[&str] = &[
+    "zip_code",
+    "prefecture_id",
+    "city_id",
+    "prefecture",
+    "city",
+    "town",
+    "prefecture_kana",
+    "city_kana",
+    "town_kana",
+    "town_detail",
+    "chome_range",
+    "floor",
+    "kind",
+    "business_name",
+    "business_name_kana",
+    "street_address",
+    "town_has_multiple_zip_codes",
+    "numbered_per_koaza",
+    "has_chome",
+    "covers_multiple_towns",
+    "update_status",
+    "update_reason",
+    "prefecture_roma",
+    "city_roma",
+    "town_roma",
+];
+
+/// WHERE condition matching a row of `other` identical to the `postal_codes` row. MySQL
+/// compares bytes, as its collations treat some kana and width variants as equal.
+pub fn identical_row_condition(binary: bool) -> String {
+    SNAPSHOT_COLUMNS
+        .iter()
+        .map(|column| {
+            if binary {
+                format!("BINARY other.{column} = BINARY postal_codes.{column}")
+            } else {
+                format!("other.{column} = postal_codes.{column}")
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" AND ")
+}
+
+/// A row present in both versions with different values, keyed by
+/// `(zip_code, prefecture_id, city, town)`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, ToSchema)]
+pub struct ModifiedRow {
+    pub before: PostalCode,
+    pub after: PostalCode,
+    /// Names of the fields that differ, in alphabetical order.
+    pub changed_fields: Vec<String>,
+}
+
+/// Row-level changes from data version `from` to `to`, each list in key order.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, ToSchema)]
+pub struct DataVersionDiff {
+    pub from: String,
+    pub to: String,
+    pub added: Vec<PostalCode>,
+    pub removed: Vec<PostalCode>,
+    pub modified: Vec<ModifiedRow>,
+}
+
+type RowKey = (String, i16, String, String);
+
+fn row_key(record: &PostalCode) -> RowKey {
+    (
+        record.zip_code.clone(),
+        record.prefecture_id,
+        record.city.clone(),
+        record.town.clone(),
+    )
+}
+
+fn changed_fields(before: &PostalCode, after: &PostalCode) -> Vec<String> {
+    let (Ok(serde_json::Value::Object(before)), Ok(serde_json::Value::Object(after))) =
+        (serde_json::to_value(before), serde_json::to_value(after))
+    else {
+        return Vec::new();
+    };
+    before
+        .iter()
+        .filter(|(field, value)| after.get(*field) != Some(*value))
+        .map(|(field, _)| field.clone())
+        .collect()
+}
+
+/// Pairs the rows only `from` has with the rows only `to` has: rows sharing a key were
+/// modified, the rest were removed or added.
+pub fn diff_rows(
+    from: String,
+    to: String,
+    only_in_from: Vec<PostalCode>,
+    only_in_to: Vec<PostalCode>,
+) -> DataVersionDiff {
+    let mut before_by_key: HashMap<RowKey, PostalCode> = only_in_from
+        .iter()
+        .map(|record| (row_key(record), record.clone()))
+        .collect();
+    let mut added = Vec::new();
+    let mut modified = Vec::new();
+    for after in only_in_to {
+        match before_by_key.remove(&row_key(&after)) {
+            Some(before) => modified.push(ModifiedRow {
+                changed_fields: changed_fields(&before, &after),
+                before,
+                after,
+            }),
+            None => added.push(after),
+        }
+    }
+    let removed = only_in_from
+        .into_iter()
+        .filter(|record| before_by_key.contains_key(&row_key(record)))
+        .collect();
+    DataVersionDiff {
+        from,
+        to,
+        added,
+        removed,
+        modified,
+    }
+}
+
+/// One line per change: `change` (added | removed | modified), the row (the new values for
+/// modified rows) and `changed_fields` joined with ';'.
+pub fn diff_to_csv(diff: &DataVersionDiff) -> Result<String, csv::Error> {
+    // The header is written by hand: `PostalCode` fields follow SNAPSHOT_COLUMNS.
+    let mut writer = csv::WriterBuilder::new()
+        .has_headers(false)
+        .from_writer(Vec::new());
+    writer.write_record(
+        std::iter::once("change")
+            .chain(SNAPSHOT_COLUMNS.iter().copied())
+            .chain(std::iter::once("changed_fields")),
+    )?;
+    let rows = diff
+        .added
+        .iter()
+        .map(|record| ("added", record, String::new()))
+        .chain(
+            diff.removed
+                .iter()
+                .map(|record| ("removed", record, String::new())),
+        )
+        .chain(
+            diff.modified
+                .iter()
+                .map(|row| ("modified", &row.after, row.changed_fields.join(";"))),
+        );
+    for row in rows {
+        writer.serialize(row)?;
+    }
+    let bytes = writer
+        .into_inner()
+        .map_err(|e| csv::Error::from(e.into_error()))?;
+    Ok(String::from_utf8(bytes).unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{diff_rows, diff_to_csv, identical_row_condition, SNAPSHOT_COLUMNS};
+    use common::models::PostalCode;
+
+    #[test]
+    fn diff_rows_pairs_rows_by_key() {
+        let diff = diff_rows(
+            "v1".to_string(),
+            "v2".to_string(),
+            vec![
+                PostalCode::builder("1000001")
+                    .prefecture(13, "東京都")
+                    .city("13101", "千代田区")
+                    .town("千代田")
+                    .town_kana("チヨダ")
+                    .build(),
+                PostalCode::builder("1000002")
+                    .prefecture(13, "東京都")
+                    .city("13101", "千代田区")
+                    .town("皇居外苑")
+                    .town_kana("")
+                    .build(),
+            ],
+            vec![
+                PostalCode::builder("1000001")
+                    .prefecture(13, "東京都")
+                    .city("13101", "千代田区")
+                    .town("千代田")
+                    .town_kana("チヨダ2")
+                    .build(),
+                PostalCode::builder("1000003")
+                    .prefecture(13, "東京都")
+                    .city("13101", "千代田区")
+                    .town("一ツ橋")
+                    .town_kana("")
+                    .build(),
+            ],
+        );
+        assert_eq!(diff.added.len(), 1);
+        assert_eq!(diff.added[0].zip_code, "1000003");
+        assert_eq!(diff.removed.len(), 1);
+        assert_eq!(diff.removed[0].zip_code, "1000002");
+        assert_eq!(diff.modified.len(), 1);
+        assert_eq!(diff.modified[0].changed_fields, vec!["town_kana"]);
+    }
+
+    #[test]
+    fn diff_to_csv_writes_one_line_per_change() {
+        let diff = diff_rows(
+            "v1".to_string(),
+            "v2".to_string(),
+            vec![PostalCode::builder("1000001")
+                .prefecture(13, "東京都")
+                .city("13101", "千代田区")
+                .town("千代田")
+                .town_kana("チヨダ")
+                .build()],
+            vec![
+                PostalCode::builder("1000001")
+                    .prefecture(13, "東京都")
+                    .city("13101", "千代田区")
+                    .town("千代田")
+                    .town_kana("チヨダ2")
+                    .build(),
+                PostalCode::builder("1000003")
+                    .prefecture(13, "東京都")
+                    .city("13101", "千代田区")
+                    .town("一ツ橋")
+                    .town_kana("")
+                    .build(),
+            ],
+        );
+        let csv = diff_to_csv(&diff).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with("change,zip_code,prefecture_id,"));
+        assert!(lines[0].ends_with(",town_roma,changed_fields"));
+        assert!(lines[1].starts_with("added,1000003,13,13101,東京都,千代田区,一ツ橋,"));
+        assert!(lines[2].starts_with("modified,1000001,"));
+        assert!(lines[2].ends_with(",town_kana"));
+        for line in &lines {
+            assert_eq!(
+                line.split(',').count(),
+                SNAPSHOT_COLUMNS.len() + 2,
+                "{line}"
+            );
+        }
+    }
+
+    #[test]
+    fn identical_row_condition_compares_bytes_on_mysql() {
+        assert!(identical_row_condition(false)
+            .starts_with("other.zip_code = postal_codes.zip_code AND "));
+        assert!(identical_row_condition(true)
+            .ends_with("BINARY other.town_roma = BINARY postal_codes.town_roma"));
+    }
+}
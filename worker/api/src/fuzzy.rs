@@ -0,0 +1,154 @@
+use common::models::PostalCode;
+use common::normalize::search_key;
+
+/// Folds a name or keyword for fuzzy comparison: the shared `search_key` (NFKC, itaiji,
+/// hyphens and the ヶ/ケ/が family), then hiragana to katakana.
+pub fn fold_variants(input: &str) -> String {
+    search_key(input)
+        .chars()
+        .map(|c| match c {
+            'ぁ'..='ゖ' => char::from_u32(c as u32 + 0x60).unwrap_or(c),
+            _ => c,
+        })
+        .collect()
+}
+
+/// Edits tolerated for a keyword of `len` characters. Short keywords must match exactly,
+/// otherwise one edit would match almost every name.
+pub fn allowed_edits(len: usize) -> u32 {
+    match len {
+        0..=2 => 0,
+        3..=5 => 1,
+        _ => 2,
+    }
+}
+
+/// Smallest edit distance between `pattern` and any substring of `text`.
+pub fn substring_distance(pattern: &[char], text: &[char]) -> u32 {
+    if pattern.is_empty() {
+        return 0;
+    }
+    // prev[j]: distance of the pattern prefix matched so far ending at text[j - 1];
+    // row 0 is all zeros because the match may start anywhere in `text`.
+    let mut prev = vec![0u32; text.len() + 1];
+    let mut current = vec![0u32; text.len() + 1];
+    for (i, p) in pattern.iter().enumerate() {
+        current[0] = i as u32 + 1;
+        for (j, t) in text.iter().enumerate() {
+            let substitution = prev[j] + u32::from(p != t);
+            current[j + 1] = substitution.min(prev[j + 1] + 1).min(current[j] + 1);
+        }
+        std::mem::swap(&mut prev, &mut current);
+    }
+    prev.into_iter().min().unwrap_or(0)
+}
+
+/// A row of the fuzzy index with its names pre-folded.
+pub struct FuzzyEntry {
+    pub record: PostalCode,
+    /// Folded prefecture, city and town.
+    names: [Vec<char>; 3],
+    /// Lowercase romanized prefecture, city and town without separators.
+    roma: [Vec<char>; 3],
+}
+
+impl FuzzyEntry {
+    pub fn new(record: PostalCode) -> Self {
+        let fold = |s: &str| fold_variants(s).chars().collect::<Vec<_>>();
+        let fold_roma = |s: &str| {
+            s.chars()
+                .filter(|c| !matches!(c, '-' | ' '))
+                .map(|c| c.to_ascii_lowercase())
+                .collect::<Vec<_>>()
+        };
+        Self {
+            names: [
+                fold(&record.prefecture),
+                fold(&record.city),
+                fold(&record.town),
+            ],
+            roma: [
+                fold_roma(&record.prefecture_roma),
+                fold_roma(&record.city_roma),
+                fold_roma(&record.town_roma),
+            ],
+            record,
+        }
+    }
+
+    /// Best distance over the given name columns (0 = prefecture, 1 = city, 2 = town),
+    /// or `None` when it exceeds `max_edits`.
+    pub fn distance(
+        &self,
+        term: &[char],
+        columns: &[usize],
+        romaji: bool,
+        max_edits: u32,
+    ) -> Option<u32> {
+        let names = if romaji { &self.roma } else { &self.names };
+        columns
+            .iter()
+            .map(|&column| substring_distance(term, &names[column]))
+            .min()
+            .filter(|distance| *distance <= max_edits)
+    }
+
+    /// Folded name columns, for scoring exact / prefix matches.
+    pub fn names(&self, romaji: bool) -> &[Vec<char>; 3] {
+        if romaji {
+            &self.roma
+        } else {
+            &self.names
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{allowed_edits, fold_variants, substring_distance, FuzzyEntry};
+    use common::models::PostalCode;
+
+    fn chars(s: &str) -> Vec<char> {
+        s.chars().collect()
+    }
+
+    #[test]
+    fn fold_variants_unifies_common_spellings() {
+        assert_eq!(fold_variants("霞が関"), fold_variants("霞ヶ関"));
+        assert_eq!(fold_variants("霞ケ関"), "霞ケ関");
+        assert_eq!(fold_variants("三之町"), fold_variants("三ノ町"));
+        assert_eq!(fold_variants("大澤"), "大沢");
+        assert_eq!(fold_variants("龍ケ崎"), "竜ケ崎");
+        assert_eq!(fold_variants("\u{FA10}"), "塚");
+        assert_eq!(fold_variants("しんじゅく"), "シンジュク");
+    }
+
+    #[test]
+    fn substring_distance_counts_edits_inside_longer_names() {
+        assert_eq!(substring_distance(&chars("新宿"), &chars("西新宿")), 0);
+        assert_eq!(substring_distance(&chars("西新塾"), &chars("西新宿")), 1);
+        assert_eq!(substring_distance(&chars("西心塾"), &chars("西新宿")), 2);
+        assert_eq!(substring_distance(&chars("大手町"), &chars("千代田")), 3);
+        assert_eq!(substring_distance(&chars("西宿"), &chars("西新宿")), 1);
+    }
+
+    #[test]
+    fn allowed_edits_grow_with_keyword_length() {
+        assert_eq!(allowed_edits(2), 0);
+        assert_eq!(allowed_edits(3), 1);
+        assert_eq!(allowed_edits(6), 2);
+    }
+
+    #[test]
+    fn fuzzy_entry_matches_variants_and_typos() {
+        let entry = FuzzyEntry::new(PostalCode {
+            city: "千代田区".to_string(),
+            town: "霞が関".to_string(),
+            ..Default::default()
+        });
+        let term = chars(&fold_variants("霞ヶ関"));
+        assert_eq!(entry.distance(&term, &[2], false, 1), Some(0));
+        assert_eq!(entry.distance(&chars("霞ケ閣"), &[2], false, 1), Some(1));
+        assert_eq!(entry.distance(&chars("霞ケ閣"), &[1], false, 1), None);
+    }
+}
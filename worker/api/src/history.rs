@@ -0,0 +1,432 @@
+use chrono::{DateTime, Duration, FixedOffset, NaiveDate, SecondsFormat, TimeZone, Utc};
+use common::audit::{is_data_version, live_snapshot, STATUS_UNCHANGED};
+use common::models::{PostalCode, UpdateReason};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use utoipa::ToSchema;
+
+/// Dates in `as_of` are Japan Post publication dates, so they are read in JST.
+const JST_OFFSET_SECONDS: i32 = 9 * 3600;
+
+/// The dataset a request should be answered from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AsOf {
+    /// A `data_version` from `GET /data_versions`.
+    Version(String),
+    /// The version that was current at this instant.
+    At(DateTime<Utc>),
+}
+
+/// Parses `as_of`: a `data_version`, a date (`2025-01-31`, the end of that day in JST)
+/// or an RFC 3339 timestamp.
+pub fn parse_as_of(raw: &str) -> Result<AsOf, String> {
+    let raw = raw.trim();
+    if is_data_version(raw) {
+        return Ok(AsOf::Version(raw.to_string()));
+    }
+    if let Ok(date) = NaiveDate::parse_from_str(raw, "%Y-%m-%d") {
+        let jst = FixedOffset::east_opt(JST_OFFSET_SECONDS).expect("valid offset");
+        let next_day = date
+            .succ_opt()
+            .and_then(|d| d.and_hms_opt(0, 0, 0))
+            .and_then(|t| jst.from_local_datetime(&t).single())
+            .ok_or_else(|| format!("as_of is out of range: {raw}"))?;
+        return Ok(AsOf::At(
+            next_day.with_timezone(&Utc) - Duration::milliseconds(1),
+        ));
+    }
+    if let Ok(at) = DateTime::parse_from_rfc3339(raw) {
+        return Ok(AsOf::At(at.with_timezone(&Utc)));
+    }
+    Err(format!(
+        "as_of must be a data_version, a date (YYYY-MM-DD) or an RFC 3339 timestamp: {raw}"
+    ))
+}
+
+/// Table expression to read postal code rows from: the live table, or one snapshot under
+/// the same name so the usual column lists and WHERE clauses apply unchanged. A snapshot's
+/// `data_version` is bound to `placeholder`, which the caller passes only in that case.
+pub fn postal_code_source(snapshot: bool, placeholder: &str) -> String {
+    if snapshot {
+        format!(
+            "(SELECT * FROM postal_codes_snapshots WHERE data_version = {placeholder}) postal_codes"
+        )
+    } else {
+        "postal_codes".to_string()
+    }
+}
+
+/// One crawler or rollback run from `data_update_audits`, newest first.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, ToSchema)]
+pub struct DataVersion {
+    pub data_version: String,
+    /// success | failed | rollback | unchanged
+    pub status: String,
+    /// RFC 3339, UTC.
+    pub run_finished_at: String,
+    pub total_count: i64,
+    pub inserted_count: i64,
+    pub updated_count: i64,
+    pub deleted_count: i64,
+    /// Latest Japan Post monthly diff (YYMM) reflected in the data.
+    pub diff_month: Option<String>,
+    /// SHA-256 of the file downloaded from Japan Post; null for diffs and rollbacks.
+    pub source_sha256: Option<String>,
+    /// Snapshot that `as_of=<data_version>` reads; null when the run left no data behind.
+    pub snapshot_version: Option<String>,
+}
+
+impl DataVersion {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        data_version: String,
+        status: String,
+        source_url: &str,
+        run_finished_at: DateTime<Utc>,
+        total_count: i64,
+        inserted_count: i64,
+        updated_count: i64,
+        deleted_count: i64,
+        diff_month: Option<String>,
+        source_sha256: Option<String>,
+    ) -> Self {
+        let snapshot_version = live_snapshot(&data_version, &status, source_url);
+        Self {
+            data_version,
+            status,
+            run_finished_at: run_finished_at.to_rfc3339_opts(SecondsFormat::Secs, true),
+            total_count,
+            inserted_count,
+            updated_count,
+            deleted_count,
+            diff_month,
+            source_sha256,
+            snapshot_version,
+        }
+    }
+}
+
+/// Gives `unchanged` runs the snapshot of the run they matched, the next older entry.
+/// `versions` are newest first, as `GET /data_versions` lists them.
+pub fn carry_unchanged_snapshots(versions: &mut [DataVersion]) {
+    let mut older: Option<String> = None;
+    for version in versions.iter_mut().rev() {
+        if version.status == STATUS_UNCHANGED {
+            version.snapshot_version = older.clone();
+        } else {
+            older = version.snapshot_version.clone();
+        }
+    }
+}
+
+/// A dataset as it went live: a successful run, or a rollback that brought back an
+/// earlier run's snapshot.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Dataset {
+    pub data_version: String,
+    pub snapshot_version: String,
+    pub effective_at: DateTime<Utc>,
+}
+
+/// The address a postal code pointed to in one dataset.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, PartialOrd, Ord, ToSchema)]
+pub struct HistoryAddress {
+    pub prefecture_id: i16,
+    pub city_id: String,
+    pub prefecture: String,
+    pub city: String,
+    pub town: String,
+    /// Empty for residential codes.
+    pub business_name: String,
+}
+
+impl From<&PostalCode> for HistoryAddress {
+    fn from(record: &PostalCode) -> Self {
+        Self {
+            prefecture_id: record.prefecture_id,
+            city_id: record.city_id.clone(),
+            prefecture: record.prefecture.clone(),
+            city: record.city.clone(),
+            town: record.town.clone(),
+            business_name: record.business_name.clone(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum HistoryEventKind {
+    /// The code appeared, or came back after being retired.
+    Added,
+    /// The code now points to a different prefecture, city, town or business.
+    Changed,
+    /// The code is no longer in the data.
+    Retired,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, ToSchema)]
+pub struct HistoryEvent {
+    pub data_version: String,
+    /// When the dataset went live (RFC 3339, UTC).
+    pub effective_at: String,
+    pub kind: HistoryEventKind,
+    /// Addresses before the event; empty for `added`.
+    pub before: Vec<HistoryAddress>,
+    /// Addresses after the event; empty for `retired`.
+    pub after: Vec<HistoryAddress>,
+    /// KEN_ALL's reasons on the affected rows, e.g. `municipal_reorganization`.
+    pub update_reasons: Vec<UpdateReason>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum HistoryStatus {
+    Active,
+    Retired,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, ToSchema)]
+pub struct PostalCodeHistory {
+    pub zip_code: String,
+    pub status: HistoryStatus,
+    /// When the oldest recorded dataset went live; an `added` event at this time means the
+    /// code already existed then.
+    pub tracked_since: String,
+    /// Oldest first.
+    pub events: Vec<HistoryEvent>,
+}
+
+fn addresses(rows: &[PostalCode]) -> Vec<HistoryAddress> {
+    let mut addresses: Vec<HistoryAddress> = rows.iter().map(HistoryAddress::from).collect();
+    addresses.sort();
+    addresses.dedup();
+    addresses
+}
+
+fn update_reasons(rows: &[PostalCode]) -> Vec<UpdateReason> {
+    let mut reasons: Vec<UpdateReason> = Vec::new();
+    for reason in rows.iter().map(|row| row.update_reason) {
+        if reason != UpdateReason::None && !reasons.contains(&reason) {
+            reasons.push(reason);
+        }
+    }
+    reasons
+}
+
+/// Compares a postal code's rows in each dataset with the one before it. `datasets` are
+/// ordered by `effective_at`; `rows` hold the code's rows per snapshot version.
+/// Returns `None` when the code is in none of the datasets.
+pub fn build_history(
+    zip_code: &str,
+    datasets: &[Dataset],
+    rows: &HashMap<String, Vec<PostalCode>>,
+) -> Option<PostalCodeHistory> {
+    let tracked_since = datasets.first()?.effective_at;
+    let mut events: Vec<HistoryEvent> = Vec::new();
+    let mut previous: &[PostalCode] = &[];
+    for dataset in datasets {
+        let current = rows
+            .get(&dataset.snapshot_version)
+            .map_or(&[][..], Vec::as_slice);
+        let (before, after) = (addresses(previous), addresses(current));
+        if before != after {
+            let (kind, reasons) = match (before.is_empty(), after.is_empty()) {
+                (true, _) => (HistoryEventKind::Added, update_reasons(current)),
+                (_, true) => (HistoryEventKind::Retired, update_reasons(previous)),
+                _ => (HistoryEventKind::Changed, update_reasons(current)),
+            };
+            events.push(HistoryEvent {
+                data_version: dataset.data_version.clone(),
+                effective_at: dataset
+                    .effective_at
+                    .to_rfc3339_opts(SecondsFormat::Secs, true),
+                kind,
+                before,
+                after,
+                update_reasons: reasons,
+            });
+        }
+        previous = current;
+    }
+
+    let status = match events.last()?.kind {
+        HistoryEventKind::Retired => HistoryStatus::Retired,
+        _ => HistoryStatus::Active,
+    };
+    Some(PostalCodeHistory {
+        zip_code: zip_code.to_string(),
+        status,
+        tracked_since: tracked_since.to_rfc3339_opts(SecondsFormat::Secs, true),
+        events,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        build_history, carry_unchanged_snapshots, parse_as_of, postal_code_source, AsOf,
+        DataVersion, Dataset, HistoryEventKind, HistoryStatus,
+    };
+    use chrono::{TimeZone, Utc};
+    use common::models::{PostalCode, UpdateReason};
+    use std::collections::HashMap;
+
+    fn dataset(data_version: &str, snapshot_version: &str, month: u32) -> Dataset {
+        Dataset {
+            data_version: data_version.to_string(),
+            snapshot_version: snapshot_version.to_string(),
+            effective_at: Utc.with_ymd_and_hms(2025, month, 1, 0, 0, 0).unwrap(),
+        }
+    }
+
+    #[test]
+    fn parse_as_of_accepts_versions_dates_and_timestamps() {
+        assert_eq!(
+            parse_as_of("v20250101120000000"),
+            Ok(AsOf::Version("v20250101120000000".to_string()))
+        );
+        // End of 2025-01-31 in JST is 14:59:59.999 UTC.
+        assert_eq!(
+            parse_as_of("2025-01-31"),
+            Ok(AsOf::At(
+                Utc.with_ymd_and_hms(2025, 1, 31, 14, 59, 59).unwrap()
+                    + chrono::Duration::milliseconds(999)
+            ))
+        );
+        assert_eq!(
+            parse_as_of("2025-01-31T09:00:00+09:00"),
+            Ok(AsOf::At(
+                Utc.with_ymd_and_hms(2025, 1, 31, 0, 0, 0).unwrap()
+            ))
+        );
+        assert!(parse_as_of("yesterday").is_err());
+        assert!(parse_as_of("2025-02-30").is_err());
+    }
+
+    #[test]
+    fn unchanged_runs_carry_the_older_snapshot() {
+        let version = |data_version: &str, status: &str| {
+            DataVersion::new(
+                data_version.to_string(),
+                status.to_string(),
+                "https://example.com",
+                Utc.with_ymd_and_hms(2025, 3, 1, 0, 0, 0).unwrap(),
+                0,
+                0,
+                0,
+                0,
+                None,
+                None,
+            )
+        };
+        let mut versions = vec![
+            version("v4", "unchanged"),
+            version("v3", "unchanged"),
+            version("v2", "success"),
+            version("v1", "unchanged"),
+        ];
+        carry_unchanged_snapshots(&mut versions);
+        let snapshots: Vec<Option<&str>> = versions
+            .iter()
+            .map(|v| v.snapshot_version.as_deref())
+            .collect();
+        assert_eq!(snapshots, vec![Some("v2"), Some("v2"), Some("v2"), None]);
+    }
+
+    #[test]
+    fn postal_code_source_aliases_the_snapshot() {
+        assert_eq!(postal_code_source(false, "$2"), "postal_codes");
+        assert_eq!(
+            postal_code_source(true, "$2"),
+            "(SELECT * FROM postal_codes_snapshots WHERE data_version = $2) postal_codes"
+        );
+    }
+
+    #[test]
+    fn build_history_records_appearance_change_and_retirement() {
+        let datasets = vec![
+            dataset("v1", "v1", 1),
+            dataset("v2", "v2", 2),
+            dataset("v3", "v3", 3),
+            dataset("v4", "v4", 4),
+        ];
+        let rows = HashMap::from([
+            (
+                "v2".to_string(),
+                vec![PostalCode::builder("3900000")
+                    .prefecture(20, "長野県")
+                    .city("20999", "旧村")
+                    .town("本郷")
+                    .update_reason(UpdateReason::None)
+                    .build()],
+            ),
+            (
+                "v3".to_string(),
+                vec![PostalCode::builder("3900000")
+                    .prefecture(20, "長野県")
+                    .city("20202", "松本市")
+                    .town("本郷")
+                    .update_reason(UpdateReason::MunicipalReorganization)
+                    .build()],
+            ),
+        ]);
+        let history = build_history("3900000", &datasets, &rows).unwrap();
+        assert_eq!(history.status, HistoryStatus::Retired);
+        assert_eq!(history.tracked_since, "2025-01-01T00:00:00Z");
+        let kinds: Vec<(&str, HistoryEventKind)> = history
+            .events
+            .iter()
+            .map(|e| (e.data_version.as_str(), e.kind))
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![
+                ("v2", HistoryEventKind::Added),
+                ("v3", HistoryEventKind::Changed),
+                ("v4", HistoryEventKind::Retired),
+            ]
+        );
+        let changed = &history.events[1];
+        assert_eq!(changed.before[0].city, "旧村");
+        assert_eq!(changed.after[0].city, "松本市");
+        assert_eq!(
+            changed.update_reasons,
+            vec![UpdateReason::MunicipalReorganization]
+        );
+    }
+
+    #[test]
+    fn build_history_follows_rollbacks_and_ignores_unchanged_runs() {
+        let datasets = vec![
+            dataset("v1", "v1", 1),
+            dataset("v2", "v2", 2),
+            dataset("rv3", "v1", 3),
+        ];
+        let rows = HashMap::from([
+            (
+                "v1".to_string(),
+                vec![PostalCode::builder("3900000")
+                    .prefecture(20, "長野県")
+                    .city("20202", "松本市")
+                    .town("本郷")
+                    .update_reason(UpdateReason::None)
+                    .build()],
+            ),
+            (
+                "v2".to_string(),
+                vec![PostalCode::builder("3900000")
+                    .prefecture(20, "長野県")
+                    .city("20202", "松本市")
+                    .town("本郷")
+                    .update_reason(UpdateReason::None)
+                    .build()],
+            ),
+        ]);
+        let history = build_history("3900000", &datasets, &rows).unwrap();
+        assert_eq!(history.status, HistoryStatus::Active);
+        assert_eq!(history.events.len(), 1);
+        assert_eq!(history.events[0].kind, HistoryEventKind::Added);
+
+        assert!(build_history("3900000", &datasets, &HashMap::new()).is_none());
+    }
+}
@@ -1,31 +1,60 @@
 use axum::{
     extract::{connect_info::ConnectInfo, Path, Query, State},
-    http::{header::HeaderName, Request, StatusCode},
+    http::{
+        header::{self, HeaderMap, HeaderName},
+        Request, StatusCode,
+    },
     middleware::Next,
     response::{Html, IntoResponse, Response},
-    routing::get,
+    routing::{get, post},
     Json, Router,
 };
-use common::{db, models::PostalCode};
+use common::{
+    audit::{is_data_version, live_snapshot, STATUS_UNCHANGED},
+    db,
+    models::{
+        MunicipalityChange, MunicipalityChangeSource, PostalCode, PostalCodeKind, UpdateReason,
+        UpdateStatus,
+    },
+    normalize::{normalize_input, search_key},
+    romaji::{fold_romaji, is_romaji},
+    zip_code::normalize_zip_code,
+};
 use deadpool_postgres::Pool as PgPool;
 use ipnet::IpNet;
 use mysql_async::Pool as MySqlPool;
 use redis::{aio::ConnectionManager as RedisConnectionManager, AsyncCommands};
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use std::{
-    collections::HashSet,
+    collections::{HashMap, HashSet},
     net::{IpAddr, SocketAddr},
     sync::{
         atomic::{AtomicU64, Ordering},
         Arc,
     },
-    time::Instant,
+    time::{Duration, Instant},
 };
 use tokio::net::TcpListener;
+use tokio_postgres::types::ToSql;
 use tower_http::cors::CorsLayer;
-use unicode_normalization::UnicodeNormalization;
 use utoipa::{OpenApi, ToSchema};
 
+mod diff;
+mod fuzzy;
+mod history;
+mod municipality;
+mod resolve;
+mod suggest;
+
+use diff::{DataVersionDiff, ModifiedRow};
+use fuzzy::{allowed_edits, fold_variants, FuzzyEntry};
+use history::{
+    postal_code_source, AsOf, DataVersion, Dataset, HistoryAddress, HistoryEvent, HistoryEventKind,
+    HistoryStatus, PostalCodeHistory,
+};
+use resolve::{CityEntry, ResolveResponse};
+use suggest::{Suggestion, SuggestionKind};
+
 enum DbPool {
     Postgres(PgPool),
     MySql(MySqlPool),
@@ -36,6 +65,13 @@ struct AppState {
     pool: DbPool,
     cache: Option<RedisConnectionManager>,
     cache_ttl_seconds: u64,
+    /// Suggestions are requested on every keystroke, so they are cached for longer.
+    suggest_cache_ttl_seconds: u64,
+    batch_max_zip_codes: usize,
+    /// The SQLite file carries the `postal_codes_fts` trigram index.
+    sqlite_fts: bool,
+    /// All rows with folded names for `mode=fuzzy`; reloaded after `cache_ttl_seconds`.
+    fuzzy_index: tokio::sync::RwLock<Option<FuzzyIndex>>,
     ready_require_cache: bool,
     ip_allowlist: Option<IpAllowlist>,
     trust_proxy_headers: bool,
@@ -43,6 +79,11 @@ struct AppState {
     metrics: ApiMetrics,
 }
 
+struct FuzzyIndex {
+    loaded_at: Instant,
+    entries: Arc<Vec<FuzzyEntry>>,
+}
+
 struct IpAllowlist {
     networks: Vec<IpNet>,
 }
@@ -78,7 +119,7 @@ impl AuthConfig {
     }
 }
 
-#[derive(Serialize, ToSchema)]
+#[derive(Debug, Serialize, ToSchema)]
 struct ErrorResponse {
     error: String,
 }
@@ -163,6 +204,9 @@ struct PrefectureResponse {
 struct CityResponse {
     city_id: String,
     city: String,
+    /// The retired `city_id` this municipality replaced; only set for `city_id` lookups.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    successor_of: Option<String>,
 }
 
 #[derive(Deserialize)]
@@ -170,11 +214,229 @@ struct SearchParams {
     address: String,
     limit: Option<u32>,
     mode: Option<SearchMode>,
+    field: Option<SearchField>,
+    prefecture_id: Option<i16>,
+    city_id: Option<String>,
+    town_detail: Option<String>,
+    floor: Option<String>,
+    /// v2 only: `next_cursor` of the previous page.
+    cursor: Option<String>,
+    /// v2 only: also return the total number of matches.
+    include_total: Option<bool>,
+    lang: Option<Lang>,
+    /// v1 only: answer from the snapshot of a `data_version` or date.
+    as_of: Option<String>,
+}
+
+/// Position after the last returned row, in `(zip_code, prefecture_id, city, town)` order.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+struct SearchCursor {
+    zip_code: String,
+    prefecture_id: i16,
+    city: String,
+    town: String,
+}
+
+impl SearchCursor {
+    fn after(record: &PostalCode) -> Self {
+        Self {
+            zip_code: record.zip_code.clone(),
+            prefecture_id: record.prefecture_id,
+            city: record.city.clone(),
+            town: record.town.clone(),
+        }
+    }
+
+    // Hex of the JSON form: opaque to clients and safe in a query string.
+    fn encode(&self) -> String {
+        serde_json::to_string(self)
+            .unwrap_or_default()
+            .bytes()
+            .map(|b| format!("{b:02x}"))
+            .collect()
+    }
+
+    fn decode(raw: &str) -> Option<Self> {
+        if !raw.len().is_multiple_of(2) {
+            return None;
+        }
+        let bytes = (0..raw.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(raw.get(i..i + 2)?, 16).ok())
+            .collect::<Option<Vec<u8>>>()?;
+        serde_json::from_slice(&bytes).ok()
+    }
+}
+
+/// A search hit with its relevance; the record fields are inlined.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, ToSchema)]
+struct ScoredPostalCode {
+    #[serde(flatten)]
+    record: PostalCode,
+    /// 1.0 exact, 0.8 prefix, 0.5 partial match; kana-variant matches score 0.05 lower.
+    /// In fuzzy mode every edit costs 0.15.
+    score: f64,
+    /// Edits needed to match the keyword; only set in fuzzy mode.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    edit_distance: Option<u32>,
+}
+
+/// Response of `/v2/postal_codes/search`.
+#[derive(Serialize, Deserialize, ToSchema)]
+struct SearchPage {
+    items: Vec<PostalCode>,
+    /// Pass as `cursor` to fetch the next page; null on the last page.
+    next_cursor: Option<String>,
+    /// Total matches; only set when `include_total=true`.
+    total: Option<i64>,
+}
+
+impl SearchPage {
+    // The cursor was taken from the Japanese names before localizing.
+    fn localized(mut self, lang: Lang) -> Self {
+        self.items = localize_all(self.items, lang);
+        self
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "snake_case")]
+enum BatchLookupStatus {
+    Found,
+    NotFound,
+    Invalid,
+}
+
+#[derive(Serialize, ToSchema)]
+struct BatchLookupResult {
+    /// The code exactly as it was sent.
+    input: String,
+    /// Normalized 7-digit code; null when the input is malformed.
+    zip_code: Option<String>,
+    status: BatchLookupStatus,
+    records: Vec<PostalCode>,
+}
+
+#[derive(Serialize, ToSchema)]
+struct BatchLookupResponse {
+    results: Vec<BatchLookupResult>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "snake_case")]
+enum ValidationReason {
+    /// Not seven digits after normalization.
+    BadFormat,
+    /// Well-formed, but never published.
+    Unknown,
+    /// Published in an earlier data version but no longer current.
+    Retired,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+struct ValidateResponse {
+    input: String,
+    /// Normalized 7-digit code; null when the input is malformed.
+    normalized: Option<String>,
+    valid: bool,
+    reason: Option<ValidationReason>,
+    /// Human readable detail for `bad_format`.
+    message: Option<String>,
+    /// Latest snapshot that still contained a retired code.
+    last_seen_data_version: Option<String>,
+    records: Vec<PostalCode>,
+}
+
+/// Language of the prefecture / city / town names in responses.
+#[derive(Debug, Clone, Copy, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+enum Lang {
+    #[default]
+    Ja,
+    /// Hepburn romanization; rows without a reading keep their Japanese names.
+    En,
+}
+
+#[derive(Deserialize)]
+struct LangParams {
+    lang: Option<Lang>,
+}
+
+#[derive(Deserialize)]
+struct LookupParams {
+    lang: Option<Lang>,
+    as_of: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct DataVersionParams {
+    limit: Option<u32>,
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+enum DiffFormat {
+    #[default]
+    Json,
+    /// One line per changed row, for loading into spreadsheets and other databases.
+    Csv,
+}
+
+#[derive(Deserialize)]
+struct DiffParams {
+    format: Option<DiffFormat>,
+}
+
+// Applied after caching, so both languages share one cache entry.
+fn localize(record: &mut PostalCode, lang: Lang) {
+    if lang != Lang::En {
+        return;
+    }
+    for (name, roma) in [
+        (&mut record.prefecture, &record.prefecture_roma),
+        (&mut record.city, &record.city_roma),
+        (&mut record.town, &record.town_roma),
+    ] {
+        if !roma.is_empty() {
+            name.clone_from(roma);
+        }
+    }
+}
+
+fn localize_all(mut records: Vec<PostalCode>, lang: Lang) -> Vec<PostalCode> {
+    for record in &mut records {
+        localize(record, lang);
+    }
+    records
+}
+
+fn localize_ranked(mut records: Vec<ScoredPostalCode>, lang: Lang) -> Vec<ScoredPostalCode> {
+    for scored in &mut records {
+        localize(&mut scored.record, lang);
+    }
+    records
+}
+
+#[derive(Deserialize)]
+struct ValidateParams {
+    zip_code: String,
+}
+
+#[derive(Deserialize)]
+struct ResolveParams {
+    address: String,
+}
+
+#[derive(Deserialize)]
+struct SuggestParams {
+    q: String,
+    limit: Option<u32>,
 }
 
 #[derive(Deserialize)]
 struct CityParams {
-    prefecture_id: i16,
+    prefecture_id: Option<i16>,
+    city_id: Option<String>,
 }
 
 #[derive(Debug, Clone, Copy, Default, Deserialize, PartialEq, Eq, Hash)]
@@ -184,6 +446,106 @@ enum SearchMode {
     Prefix,
     #[default]
     Partial,
+    /// Partial match that tolerates variant spellings and a few typos.
+    Fuzzy,
+}
+
+/// Which columns the address keyword is matched against.
+#[derive(Debug, Clone, Copy, Default, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+enum SearchField {
+    #[default]
+    All,
+    City,
+    Town,
+}
+
+impl SearchField {
+    fn as_cache_key(self) -> &'static str {
+        match self {
+            Self::All => "all",
+            Self::City => "city",
+            Self::Town => "town",
+        }
+    }
+
+    fn columns(self) -> &'static [&'static str] {
+        match self {
+            Self::All => &[
+                "prefecture_normalized",
+                "city_normalized",
+                "town_normalized",
+            ],
+            Self::City => &["city_normalized"],
+            Self::Town => &["town_normalized"],
+        }
+    }
+
+    /// Positions of `columns()` in the (prefecture, city, town) name triple.
+    fn name_indexes(self) -> &'static [usize] {
+        match self {
+            Self::All => &[0, 1, 2],
+            Self::City => &[1],
+            Self::Town => &[2],
+        }
+    }
+}
+
+/// The columns a keyword is compared against: the search keys of the Japanese names
+/// (`search_key`), or for romaji input the romanized names folded the same way as
+/// `fold_romaji` folds the keyword.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct SearchTarget {
+    field: SearchField,
+    romaji: bool,
+}
+
+impl SearchTarget {
+    fn columns(self) -> &'static [&'static str] {
+        const PREFECTURE: &str = "lower(replace(replace(prefecture_roma, '-', ''), ' ', ''))";
+        const CITY: &str = "lower(replace(replace(city_roma, '-', ''), ' ', ''))";
+        const TOWN: &str = "lower(replace(replace(town_roma, '-', ''), ' ', ''))";
+        if !self.romaji {
+            return self.field.columns();
+        }
+        match self.field {
+            SearchField::All => &[PREFECTURE, CITY, TOWN],
+            SearchField::City => &[CITY],
+            SearchField::Town => &[TOWN],
+        }
+    }
+
+    fn values(self, record: &PostalCode) -> Vec<String> {
+        let names: Vec<&str> = match (self.field, self.romaji) {
+            (SearchField::All, false) => vec![
+                record.prefecture.as_str(),
+                record.city.as_str(),
+                record.town.as_str(),
+            ],
+            (SearchField::City, false) => vec![record.city.as_str()],
+            (SearchField::Town, false) => vec![record.town.as_str()],
+            (SearchField::All, true) => vec![
+                record.prefecture_roma.as_str(),
+                record.city_roma.as_str(),
+                record.town_roma.as_str(),
+            ],
+            (SearchField::City, true) => vec![record.city_roma.as_str()],
+            (SearchField::Town, true) => vec![record.town_roma.as_str()],
+        };
+        names
+            .into_iter()
+            .map(|name| {
+                if self.romaji {
+                    name.chars()
+                        .filter(|c| !matches!(c, '-' | ' '))
+                        .map(|c| c.to_ascii_lowercase())
+                        .collect()
+                } else {
+                    search_key(name)
+                }
+            })
+            .collect()
+    }
 }
 
 impl SearchMode {
@@ -192,29 +554,82 @@ impl SearchMode {
             Self::Exact => "exact",
             Self::Prefix => "prefix",
             Self::Partial => "partial",
+            Self::Fuzzy => "fuzzy",
         }
     }
 
     fn needs_like(self) -> bool {
-        !matches!(self, Self::Exact)
+        matches!(self, Self::Prefix | Self::Partial)
     }
 }
 
 fn build_search_term(mode: SearchMode, address: &str) -> String {
     match mode {
-        SearchMode::Exact => address.to_string(),
+        SearchMode::Exact | SearchMode::Fuzzy => address.to_string(),
         SearchMode::Prefix => format!("{address}%"),
         SearchMode::Partial => format!("%{address}%"),
     }
 }
 
-fn normalize_search_input(input: &str) -> String {
-    input
-        .nfkc()
-        .collect::<String>()
-        .chars()
-        .filter(|c| !c.is_whitespace())
-        .collect::<String>()
+struct SearchFilters {
+    field: SearchField,
+    town_detail_filter: String,
+    floor: String,
+    /// LIKE pattern for `town_detail_filter`.
+    town_detail_term: String,
+    /// 0 when not filtering; prefecture ids start at 1.
+    prefecture_id: i16,
+    city_id: String,
+}
+
+impl SearchFilters {
+    fn from_params(params: &SearchParams) -> Self {
+        let town_detail_filter = params
+            .town_detail
+            .as_deref()
+            .map(normalize_detail_filter)
+            .unwrap_or_default();
+        let floor = params
+            .floor
+            .as_deref()
+            .map(normalize_input)
+            .unwrap_or_default();
+        // Empty filters match every row, so the SQL stays the same whether or not they are given.
+        let town_detail_term = if town_detail_filter.is_empty() {
+            String::new()
+        } else {
+            format!("%{town_detail_filter}%")
+        };
+        Self {
+            field: params.field.unwrap_or_default(),
+            town_detail_filter,
+            floor,
+            town_detail_term,
+            prefecture_id: params.prefecture_id.unwrap_or(0),
+            city_id: params
+                .city_id
+                .as_deref()
+                .map(str::trim)
+                .unwrap_or_default()
+                .to_string(),
+        }
+    }
+
+    fn cache_key(&self) -> String {
+        format!(
+            "{}:{}:{}:{}:{}",
+            self.field.as_cache_key(),
+            self.prefecture_id,
+            self.city_id,
+            self.town_detail_filter,
+            self.floor
+        )
+    }
+}
+
+// The crawler stores parenthetical detail with ',' separators and full-width '～'.
+fn normalize_detail_filter(input: &str) -> String {
+    normalize_input(input).replace('~', "～").replace('、', ",")
 }
 
 fn hiragana_to_katakana(input: &str) -> String {
@@ -252,13 +667,126 @@ fn push_unique_candidate(candidates: &mut Vec<String>, candidate: String) {
 }
 
 fn build_search_candidates(normalized_address: &str) -> Vec<String> {
+    if is_romaji(normalized_address) {
+        return vec![fold_romaji(normalized_address)];
+    }
     let mut candidates = Vec::with_capacity(3);
-    push_unique_candidate(&mut candidates, normalized_address.to_string());
-    push_unique_candidate(&mut candidates, hiragana_to_katakana(normalized_address));
-    push_unique_candidate(&mut candidates, katakana_to_hiragana(normalized_address));
+    push_unique_candidate(&mut candidates, search_key(normalized_address));
+    push_unique_candidate(
+        &mut candidates,
+        search_key(&hiragana_to_katakana(normalized_address)),
+    );
+    push_unique_candidate(
+        &mut candidates,
+        search_key(&katakana_to_hiragana(normalized_address)),
+    );
     candidates
 }
 
+// Column list shared by every postal_codes SELECT; the order must match the row mappers below.
+macro_rules! postal_code_columns {
+    () => {
+        "zip_code, prefecture_id, city_id, prefecture, city, COALESCE(town, ''),
+        prefecture_kana, city_kana, town_kana, town_detail, chome_range, floor,
+        kind, business_name, business_name_kana, street_address,
+        town_has_multiple_zip_codes, numbered_per_koaza, has_chome, covers_multiple_towns,
+        update_status, update_reason, prefecture_roma, city_roma, town_roma"
+    };
+}
+
+/// Number of columns in `postal_code_columns!`; extra columns are selected after them.
+const POSTAL_CODE_COLUMN_COUNT: usize = 25;
+
+fn postal_code_from_pg_row(row: &tokio_postgres::Row) -> PostalCode {
+    PostalCode {
+        zip_code: row.get(0),
+        prefecture_id: row.get(1),
+        city_id: row.get(2),
+        prefecture: row.get(3),
+        city: row.get(4),
+        town: row.get(5),
+        prefecture_kana: row.get(6),
+        city_kana: row.get(7),
+        town_kana: row.get(8),
+        town_detail: row.get(9),
+        chome_range: row.get(10),
+        floor: row.get(11),
+        kind: PostalCodeKind::from_db_value(row.get(12)),
+        business_name: row.get(13),
+        business_name_kana: row.get(14),
+        street_address: row.get(15),
+        town_has_multiple_zip_codes: row.get(16),
+        numbered_per_koaza: row.get(17),
+        has_chome: row.get(18),
+        covers_multiple_towns: row.get(19),
+        update_status: UpdateStatus::from_db_value(row.get(20)),
+        update_reason: UpdateReason::from_db_value(row.get(21)),
+        prefecture_roma: row.get(22),
+        city_roma: row.get(23),
+        town_roma: row.get(24),
+    }
+}
+
+fn postal_code_from_mysql_row(mut row: mysql_async::Row) -> PostalCode {
+    PostalCode {
+        zip_code: row.take(0).unwrap_or_default(),
+        prefecture_id: row.take(1).unwrap_or_default(),
+        city_id: row.take(2).unwrap_or_default(),
+        prefecture: row.take(3).unwrap_or_default(),
+        city: row.take(4).unwrap_or_default(),
+        town: row.take(5).unwrap_or_default(),
+        prefecture_kana: row.take(6).unwrap_or_default(),
+        city_kana: row.take(7).unwrap_or_default(),
+        town_kana: row.take(8).unwrap_or_default(),
+        town_detail: row.take(9).unwrap_or_default(),
+        chome_range: row.take(10).unwrap_or_default(),
+        floor: row.take(11).unwrap_or_default(),
+        kind: PostalCodeKind::from_db_value(&row.take::<String, _>(12).unwrap_or_default()),
+        business_name: row.take(13).unwrap_or_default(),
+        business_name_kana: row.take(14).unwrap_or_default(),
+        street_address: row.take(15).unwrap_or_default(),
+        town_has_multiple_zip_codes: row.take(16).unwrap_or_default(),
+        numbered_per_koaza: row.take(17).unwrap_or_default(),
+        has_chome: row.take(18).unwrap_or_default(),
+        covers_multiple_towns: row.take(19).unwrap_or_default(),
+        update_status: UpdateStatus::from_db_value(&row.take::<String, _>(20).unwrap_or_default()),
+        update_reason: UpdateReason::from_db_value(&row.take::<String, _>(21).unwrap_or_default()),
+        prefecture_roma: row.take(22).unwrap_or_default(),
+        city_roma: row.take(23).unwrap_or_default(),
+        town_roma: row.take(24).unwrap_or_default(),
+    }
+}
+
+fn postal_code_from_sqlite_row(row: &rusqlite::Row) -> rusqlite::Result<PostalCode> {
+    Ok(PostalCode {
+        zip_code: row.get(0)?,
+        prefecture_id: row.get(1)?,
+        city_id: row.get(2)?,
+        prefecture: row.get(3)?,
+        city: row.get(4)?,
+        town: row.get(5)?,
+        prefecture_kana: row.get(6)?,
+        city_kana: row.get(7)?,
+        town_kana: row.get(8)?,
+        town_detail: row.get(9)?,
+        chome_range: row.get(10)?,
+        floor: row.get(11)?,
+        kind: PostalCodeKind::from_db_value(&row.get::<_, String>(12)?),
+        business_name: row.get(13)?,
+        business_name_kana: row.get(14)?,
+        street_address: row.get(15)?,
+        town_has_multiple_zip_codes: row.get(16)?,
+        numbered_per_koaza: row.get(17)?,
+        has_chome: row.get(18)?,
+        covers_multiple_towns: row.get(19)?,
+        update_status: UpdateStatus::from_db_value(&row.get::<_, String>(20)?),
+        update_reason: UpdateReason::from_db_value(&row.get::<_, String>(21)?),
+        prefecture_roma: row.get(22)?,
+        city_roma: row.get(23)?,
+        town_roma: row.get(24)?,
+    })
+}
+
 fn append_unique_with_limit(
     acc: &mut Vec<PostalCode>,
     seen: &mut HashSet<PostalCode>,
@@ -279,17 +807,46 @@ fn append_unique_with_limit(
 #[openapi(
     paths(
         get_postal_code,
+        get_postal_code_history,
+        batch_postal_codes,
+        validate_postal_code,
         search_postal_code,
+        search_postal_code_v2,
+        resolve_postal_code,
+        suggest_postal_codes,
         get_prefectures,
         get_cities,
+        list_data_versions,
+        diff_data_versions,
         health,
         ready,
         metrics
     ),
     components(schemas(
         PostalCode,
+        PostalCodeKind,
+        UpdateStatus,
+        UpdateReason,
+        ResolveResponse,
+        Suggestion,
+        SuggestionKind,
+        BatchLookupStatus,
+        BatchLookupResult,
+        BatchLookupResponse,
+        ValidationReason,
+        ValidateResponse,
+        ScoredPostalCode,
+        SearchPage,
         PrefectureResponse,
         CityResponse,
+        DataVersion,
+        DataVersionDiff,
+        ModifiedRow,
+        PostalCodeHistory,
+        HistoryStatus,
+        HistoryEvent,
+        HistoryEventKind,
+        HistoryAddress,
         HealthResponse,
         ReadyResponse,
         MetricsResponse,
@@ -310,6 +867,15 @@ fn internal_error() -> ApiError {
     )
 }
 
+fn bad_request_error(message: impl Into<String>) -> ApiError {
+    (
+        StatusCode::BAD_REQUEST,
+        Json(ErrorResponse {
+            error: message.into(),
+        }),
+    )
+}
+
 fn not_found_error() -> ApiError {
     (
         StatusCode::NOT_FOUND,
@@ -573,6 +1139,42 @@ async fn cache_set<T: Serialize>(
     let _: Result<(), redis::RedisError> = conn.set_ex(key, payload, ttl_seconds).await;
 }
 
+async fn cache_mget<T: DeserializeOwned>(
+    cache: &Option<RedisConnectionManager>,
+    keys: &[String],
+) -> Vec<Option<T>> {
+    let misses = || keys.iter().map(|_| None).collect();
+    let Some(manager) = cache.as_ref().filter(|_| !keys.is_empty()) else {
+        return misses();
+    };
+    let mut conn = manager.clone();
+    let Ok(payloads) = conn.mget::<_, Vec<Option<String>>>(keys).await else {
+        return misses();
+    };
+    payloads
+        .into_iter()
+        .map(|payload| payload.and_then(|p| serde_json::from_str(&p).ok()))
+        .collect()
+}
+
+async fn cache_set_many<T: Serialize>(
+    cache: &Option<RedisConnectionManager>,
+    entries: &[(String, &T)],
+    ttl_seconds: u64,
+) {
+    let Some(manager) = cache.as_ref().filter(|_| !entries.is_empty()) else {
+        return;
+    };
+    let mut pipe = redis::pipe();
+    for (key, value) in entries {
+        if let Ok(payload) = serde_json::to_string(value) {
+            pipe.set_ex(key, payload, ttl_seconds).ignore();
+        }
+    }
+    let mut conn = manager.clone();
+    let _: Result<(), redis::RedisError> = pipe.query_async(&mut conn).await;
+}
+
 async fn request_metrics_middleware(
     State(state): State<Arc<AppState>>,
     request: Request<axum::body::Body>,
@@ -604,16 +1206,25 @@ async fn main() {
     let database_type = std::env::var("DATABASE_TYPE").unwrap_or_else(|_| "postgres".to_string());
     println!("Using database type: {}", database_type);
 
+    let mut sqlite_fts = false;
     let pool = match database_type.as_str() {
         "sqlite" => {
             let sqlite_path = std::env::var("SQLITE_DATABASE_PATH")
                 .unwrap_or_else(|_| "storage/sqlite/postal_codes.sqlite3".to_string());
 
-            if let Err(e) = rusqlite::Connection::open(&sqlite_path) {
-                eprintln!("Failed to open SQLite database at '{}': {e}", sqlite_path);
-                return;
+            match rusqlite::Connection::open(&sqlite_path) {
+                Ok(conn) => sqlite_fts = sqlite_has_fts(&conn),
+                Err(e) => {
+                    eprintln!("Failed to open SQLite database at '{}': {e}", sqlite_path);
+                    return;
+                }
             }
             println!("Using SQLite database at: {}", sqlite_path);
+            if !sqlite_fts {
+                println!(
+                    "SQLite database has no {SQLITE_FTS_TABLE} table; partial search will scan postal_codes"
+                );
+            }
             DbPool::Sqlite(sqlite_path)
         }
         "mysql" => {
@@ -642,6 +1253,15 @@ async fn main() {
         .ok()
         .and_then(|s| s.parse().ok())
         .unwrap_or(300);
+    let suggest_cache_ttl_seconds: u64 = std::env::var("SUGGEST_CACHE_TTL_SECONDS")
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(86400);
+    let batch_max_zip_codes: usize = std::env::var("BATCH_MAX_ZIP_CODES")
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .filter(|n| *n > 0)
+        .unwrap_or(1000);
 
     let redis_cache = match std::env::var("REDIS_URL") {
         Ok(redis_url) => match redis::Client::open(redis_url) {
@@ -726,6 +1346,10 @@ async fn main() {
         pool,
         cache: redis_cache,
         cache_ttl_seconds,
+        suggest_cache_ttl_seconds,
+        batch_max_zip_codes,
+        sqlite_fts,
+        fuzzy_index: tokio::sync::RwLock::new(None),
         ready_require_cache,
         ip_allowlist,
         trust_proxy_headers,
@@ -740,9 +1364,20 @@ async fn main() {
 
     let app = Router::new()
         .route("/postal_codes/{zip_code}", get(get_postal_code))
+        .route(
+            "/postal_codes/{zip_code}/history",
+            get(get_postal_code_history),
+        )
+        .route("/postal_codes/batch", post(batch_postal_codes))
         .route("/postal_codes/search", get(search_postal_code))
+        .route("/v2/postal_codes/search", get(search_postal_code_v2))
+        .route("/postal_codes/validate", get(validate_postal_code))
+        .route("/postal_codes/resolve", get(resolve_postal_code))
+        .route("/postal_codes/suggest", get(suggest_postal_codes))
         .route("/postal_codes/prefectures", get(get_prefectures))
         .route("/postal_codes/cities", get(get_cities))
+        .route("/data_versions", get(list_data_versions))
+        .route("/data_versions/{from}/diff/{to}", get(diff_data_versions))
         .route("/health", get(health))
         .route("/ready", get(ready))
         .route("/metrics", get(metrics))
@@ -778,10 +1413,13 @@ async fn main() {
     get,
     path = "/postal_codes/{zip_code}",
     params(
-        ("zip_code" = String, Path, description = "7-digit postal code")
+        ("zip_code" = String, Path, description = "7-digit postal code; hyphens, full-width digits and 〒 are accepted"),
+        ("lang" = Option<String>, Query, description = "Name language: ja (default) | en (Hepburn romanization)"),
+        ("as_of" = Option<String>, Query, description = "Answer from a past dataset: a data_version from /data_versions, a date (YYYY-MM-DD, end of day JST) or an RFC 3339 timestamp")
     ),
     responses(
         (status = 200, description = "Postal code lookup result", body = [PostalCode]),
+        (status = 400, description = "Malformed postal code or as_of", body = ErrorResponse),
         (status = 404, description = "Postal code not found", body = ErrorResponse),
         (status = 500, description = "Internal server error", body = ErrorResponse)
     )
@@ -789,19 +1427,34 @@ async fn main() {
 async fn get_postal_code(
     State(state): State<Arc<AppState>>,
     Path(zip_code): Path<String>,
+    Query(params): Query<LookupParams>,
 ) -> Result<Json<Vec<PostalCode>>, ApiError> {
-    let cache_key = format!("postal:zip:{zip_code}");
+    let lang = params.lang.unwrap_or_default();
+    let zip_code = normalize_zip_code(&zip_code).map_err(|e| bad_request_error(e.to_string()))?;
+    let version = resolve_as_of(&state, params.as_of.as_deref()).await?;
+    let cache_key = match &version {
+        Some(version) => format!("postal:zip:{zip_code}:{version}"),
+        None => format!("postal:zip:{zip_code}"),
+    };
     if let Some(cached) = cache_get::<Vec<PostalCode>>(&state.cache, &cache_key).await {
-        return Ok(Json(cached));
+        return Ok(Json(localize_all(cached, lang)));
     }
 
     match &state.pool {
         DbPool::Postgres(pool) => {
             let client = pool.get().await.map_err(|_| internal_error())?;
+            let source = postal_code_source(version.is_some(), "$2");
+            let mut query_params: Vec<&(dyn ToSql + Sync)> = vec![&zip_code];
+            if let Some(version) = &version {
+                query_params.push(version);
+            }
             let rows = client
                 .query(
-                    "SELECT zip_code, prefecture_id, city_id, prefecture, city, town FROM postal_codes WHERE zip_code = $1",
-                    &[&zip_code],
+                    &format!(
+                        "SELECT {} FROM {source} WHERE zip_code = $1",
+                        postal_code_columns!()
+                    ),
+                    &query_params,
                 )
                 .await
                 .map_err(|_| internal_error())?;
@@ -810,37 +1463,25 @@ async fn get_postal_code(
                 return Err(not_found_error());
             }
 
-            let result: Vec<PostalCode> = rows
-                .iter()
-                .map(|row| PostalCode {
-                    zip_code: row.get(0),
-                    prefecture_id: row.get(1),
-                    city_id: row.get(2),
-                    prefecture: row.get(3),
-                    city: row.get(4),
-                    town: row.get(5),
-                })
-                .collect();
+            let result: Vec<PostalCode> = rows.iter().map(postal_code_from_pg_row).collect();
             cache_set(&state.cache, &cache_key, &result, state.cache_ttl_seconds).await;
-            Ok(Json(result))
+            Ok(Json(localize_all(result, lang)))
         }
         DbPool::MySql(pool) => {
             use mysql_async::prelude::*;
             let mut conn = pool.get_conn().await.map_err(|_| internal_error())?;
+            let source = postal_code_source(version.is_some(), ":as_of");
             let result: Vec<PostalCode> = conn
                 .exec_map(
-                    "SELECT zip_code, prefecture_id, city_id, prefecture, city, town FROM postal_codes WHERE zip_code = :zip_code",
+                    format!(
+                        "SELECT {} FROM {source} WHERE zip_code = :zip_code",
+                        postal_code_columns!()
+                    ),
                     mysql_async::params! {
                         "zip_code" => zip_code,
+                        "as_of" => &version,
                     },
-                    |(zip_code, prefecture_id, city_id, prefecture, city, town)| PostalCode {
-                        zip_code,
-                        prefecture_id,
-                        city_id,
-                        prefecture,
-                        city,
-                        town,
-                    },
+                    postal_code_from_mysql_row,
                 )
                 .await
                 .map_err(|_| internal_error())?;
@@ -849,29 +1490,20 @@ async fn get_postal_code(
                 return Err(not_found_error());
             }
             cache_set(&state.cache, &cache_key, &result, state.cache_ttl_seconds).await;
-            Ok(Json(result))
+            Ok(Json(localize_all(result, lang)))
         }
         DbPool::Sqlite(path) => {
             let result: Vec<PostalCode> = {
                 let conn = rusqlite::Connection::open(path).map_err(|_| internal_error())?;
                 let mut stmt = conn
-                    .prepare(
-                        "SELECT zip_code, prefecture_id, city_id, prefecture, city, COALESCE(town, '')
-                         FROM postal_codes WHERE zip_code = ?1",
-                    )
+                    .prepare(&format!(
+                        "SELECT {} FROM postal_codes WHERE zip_code = ?1",
+                        postal_code_columns!()
+                    ))
                     .map_err(|_| internal_error())?;
 
                 let rows = stmt
-                    .query_map([zip_code], |row| {
-                        Ok(PostalCode {
-                            zip_code: row.get(0)?,
-                            prefecture_id: row.get(1)?,
-                            city_id: row.get(2)?,
-                            prefecture: row.get(3)?,
-                            city: row.get(4)?,
-                            town: row.get(5)?,
-                        })
-                    })
+                    .query_map([zip_code], postal_code_from_sqlite_row)
                     .map_err(|_| internal_error())?;
 
                 rows.collect::<Result<Vec<_>, _>>()
@@ -882,211 +1514,1709 @@ async fn get_postal_code(
                 return Err(not_found_error());
             }
             cache_set(&state.cache, &cache_key, &result, state.cache_ttl_seconds).await;
-            Ok(Json(result))
+            Ok(Json(localize_all(result, lang)))
         }
     }
 }
 
 #[utoipa::path(
     get,
-    path = "/postal_codes/search",
+    path = "/postal_codes/{zip_code}/history",
     params(
-        ("address" = String, Query, description = "Address keyword (kana normalization is applied)"),
-        ("limit" = Option<u32>, Query, description = "Result size, default=50, max=200"),
-        ("mode" = Option<String>, Query, description = "Search mode: exact | prefix | partial (default)")
+        ("zip_code" = String, Path, description = "7-digit postal code; hyphens, full-width digits and 〒 are accepted")
     ),
     responses(
-        (status = 200, description = "Address search result", body = [PostalCode]),
+        (status = 200, description = "Changes of the postal code across data versions", body = PostalCodeHistory),
+        (status = 400, description = "Malformed postal code, or DATABASE_TYPE=sqlite", body = ErrorResponse),
+        (status = 404, description = "Postal code not found in any data version", body = ErrorResponse),
         (status = 500, description = "Internal server error", body = ErrorResponse)
     )
 )]
-async fn search_postal_code(
+async fn get_postal_code_history(
     State(state): State<Arc<AppState>>,
-    Query(params): Query<SearchParams>,
-) -> Result<Json<Vec<PostalCode>>, ApiError> {
-    let normalized_address = normalize_search_input(&params.address);
-    if normalized_address.is_empty() {
-        return Ok(Json(Vec::new()));
-    }
-
-    let candidates = build_search_candidates(&normalized_address);
-    let mode = params.mode.unwrap_or_default();
-    let limit_u32 = params.limit.unwrap_or(50).clamp(1, 200);
-    let limit = i64::from(limit_u32);
-    let limit_usize = limit_u32 as usize;
-    let cache_key = format!(
-        "postal:search:{}:{}:{limit}",
-        mode.as_cache_key(),
-        normalized_address
-    );
-    if let Some(cached) = cache_get::<Vec<PostalCode>>(&state.cache, &cache_key).await {
+    Path(zip_code): Path<String>,
+) -> Result<Json<PostalCodeHistory>, ApiError> {
+    let zip_code = normalize_zip_code(&zip_code).map_err(|e| bad_request_error(e.to_string()))?;
+    let cache_key = format!("postal:history:{zip_code}");
+    if let Some(cached) = cache_get::<PostalCodeHistory>(&state.cache, &cache_key).await {
         return Ok(Json(cached));
     }
 
-    let search_terms: Vec<String> = candidates
-        .iter()
-        .map(|candidate| build_search_term(mode, candidate))
-        .collect();
+    let datasets = load_datasets(&state).await?;
+    let rows = load_snapshot_rows(&state, &zip_code).await?;
+    let history =
+        history::build_history(&zip_code, &datasets, &rows).ok_or_else(not_found_error)?;
+    cache_set(&state.cache, &cache_key, &history, state.cache_ttl_seconds).await;
+    Ok(Json(history))
+}
 
-    match &state.pool {
+/// Every dataset that went live, oldest first.
+async fn load_datasets(state: &AppState) -> Result<Vec<Dataset>, ApiError> {
+    const QUERY: &str = "SELECT data_version, status, source_url, run_finished_at
+        FROM data_update_audits
+        WHERE status IN ('success', 'rollback')
+        ORDER BY run_finished_at, id";
+    let audits: Vec<(String, String, String, chrono::DateTime<chrono::Utc>)> = match &state.pool {
         DbPool::Postgres(pool) => {
-            const QUERY_LIKE: &str =
-                "SELECT zip_code, prefecture_id, city_id, prefecture, city, town
-                FROM postal_codes WHERE
-                prefecture LIKE $1 OR
-                city LIKE $1 OR
-                town LIKE $1
-                LIMIT $2";
-            const QUERY_EXACT: &str =
-                "SELECT zip_code, prefecture_id, city_id, prefecture, city, town
-                FROM postal_codes WHERE
-                prefecture = $1 OR
-                city = $1 OR
-                town = $1
-                LIMIT $2";
-
             let client = pool.get().await.map_err(|_| internal_error())?;
-            let mut result: Vec<PostalCode> = Vec::new();
-            let mut seen: HashSet<PostalCode> = HashSet::new();
-
-            for search_term in &search_terms {
-                if result.len() >= limit_usize {
-                    break;
-                }
-                let remaining = (limit_usize - result.len()) as i64;
-                let rows = client
-                    .query(
-                        if mode.needs_like() {
-                            QUERY_LIKE
-                        } else {
-                            QUERY_EXACT
-                        },
-                        &[search_term, &remaining],
-                    )
-                    .await
-                    .map_err(|_| internal_error())?;
-
-                let chunk: Vec<PostalCode> = rows
-                    .iter()
-                    .map(|row| PostalCode {
-                        zip_code: row.get(0),
-                        prefecture_id: row.get(1),
-                        city_id: row.get(2),
-                        prefecture: row.get(3),
-                        city: row.get(4),
-                        town: row.get(5),
-                    })
-                    .collect();
-                append_unique_with_limit(&mut result, &mut seen, chunk, limit_usize);
-            }
-
-            cache_set(&state.cache, &cache_key, &result, state.cache_ttl_seconds).await;
-            Ok(Json(result))
+            let rows = client
+                .query(QUERY, &[])
+                .await
+                .map_err(|_| internal_error())?;
+            rows.iter()
+                .map(|row| (row.get(0), row.get(1), row.get(2), row.get(3)))
+                .collect()
         }
         DbPool::MySql(pool) => {
-            const QUERY_LIKE: &str =
-                "SELECT zip_code, prefecture_id, city_id, prefecture, city, town
-                FROM postal_codes WHERE
-                prefecture LIKE :search OR
-                city LIKE :search OR
-                town LIKE :search
-                LIMIT :limit";
-            const QUERY_EXACT: &str =
-                "SELECT zip_code, prefecture_id, city_id, prefecture, city, town
-                FROM postal_codes WHERE
-                prefecture = :search OR
-                city = :search OR
-                town = :search
-                LIMIT :limit";
-
             use mysql_async::prelude::*;
             let mut conn = pool.get_conn().await.map_err(|_| internal_error())?;
-            let mut result: Vec<PostalCode> = Vec::new();
-            let mut seen: HashSet<PostalCode> = HashSet::new();
-
-            for search_term in &search_terms {
-                if result.len() >= limit_usize {
-                    break;
-                }
+            let rows: Vec<(String, String, String, chrono::NaiveDateTime)> =
+                conn.query(QUERY).await.map_err(|_| internal_error())?;
+            rows.into_iter()
+                .map(|(version, status, source_url, finished)| {
+                    (version, status, source_url, finished.and_utc())
+                })
+                .collect()
+        }
+        DbPool::Sqlite(_) => {
+            return Err(bad_request_error(
+                "history is not available with DATABASE_TYPE=sqlite",
+            ))
+        }
+    };
+    Ok(audits
+        .into_iter()
+        .filter_map(|(data_version, status, source_url, effective_at)| {
+            let snapshot_version = live_snapshot(&data_version, &status, &source_url)?;
+            Some(Dataset {
+                data_version,
+                snapshot_version,
+                effective_at,
+            })
+        })
+        .collect())
+}
+
+/// A postal code's rows in every snapshot, by snapshot version.
+async fn load_snapshot_rows(
+    state: &AppState,
+    zip_code: &str,
+) -> Result<HashMap<String, Vec<PostalCode>>, ApiError> {
+    // data_version comes after the columns the row mappers read by position.
+    let query = |placeholder: &str| {
+        format!(
+            "SELECT {}, data_version FROM postal_codes_snapshots WHERE zip_code = {placeholder}",
+            postal_code_columns!()
+        )
+    };
+    let mut rows_by_version: HashMap<String, Vec<PostalCode>> = HashMap::new();
+    match &state.pool {
+        DbPool::Postgres(pool) => {
+            let client = pool.get().await.map_err(|_| internal_error())?;
+            let rows = client
+                .query(&query("$1"), &[&zip_code])
+                .await
+                .map_err(|_| internal_error())?;
+            for row in &rows {
+                rows_by_version
+                    .entry(row.get(POSTAL_CODE_COLUMN_COUNT))
+                    .or_default()
+                    .push(postal_code_from_pg_row(row));
+            }
+        }
+        DbPool::MySql(pool) => {
+            use mysql_async::prelude::*;
+            let mut conn = pool.get_conn().await.map_err(|_| internal_error())?;
+            let rows: Vec<mysql_async::Row> = conn
+                .exec(
+                    query(":zip_code"),
+                    mysql_async::params! { "zip_code" => zip_code },
+                )
+                .await
+                .map_err(|_| internal_error())?;
+            for mut row in rows {
+                let version: String = row.take(POSTAL_CODE_COLUMN_COUNT).unwrap_or_default();
+                rows_by_version
+                    .entry(version)
+                    .or_default()
+                    .push(postal_code_from_mysql_row(row));
+            }
+        }
+        DbPool::Sqlite(_) => {}
+    }
+    Ok(rows_by_version)
+}
+
+const NDJSON_CONTENT_TYPE: &str = "application/x-ndjson";
+
+fn is_ndjson(headers: &HeaderMap) -> bool {
+    headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with(NDJSON_CONTENT_TYPE))
+}
+
+// NDJSON lines may be JSON strings ("1000001") or bare codes; blank lines are skipped.
+fn parse_batch_body(body: &str, ndjson: bool) -> Result<Vec<String>, ApiError> {
+    if !ndjson {
+        return serde_json::from_str::<Vec<String>>(body)
+            .map_err(|_| bad_request_error("body must be a JSON array of postal codes"));
+    }
+    Ok(body
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(|line| serde_json::from_str::<String>(line).unwrap_or_else(|_| line.to_string()))
+        .collect())
+}
+
+async fn fetch_postal_codes_by_zip(
+    state: &AppState,
+    zip_codes: &[String],
+) -> Result<Vec<PostalCode>, ApiError> {
+    if zip_codes.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    match &state.pool {
+        DbPool::Postgres(pool) => {
+            const QUERY: &str = concat!(
+                "SELECT ",
+                postal_code_columns!(),
+                " FROM postal_codes WHERE zip_code = ANY($1)"
+            );
+            let client = pool.get().await.map_err(|_| internal_error())?;
+            let rows = client
+                .query(QUERY, &[&zip_codes])
+                .await
+                .map_err(|_| internal_error())?;
+            Ok(rows.iter().map(postal_code_from_pg_row).collect())
+        }
+        DbPool::MySql(pool) => {
+            use mysql_async::prelude::*;
+            let placeholders = vec!["?"; zip_codes.len()].join(", ");
+            let query = format!(
+                concat!(
+                    "SELECT ",
+                    postal_code_columns!(),
+                    " FROM postal_codes WHERE zip_code IN ({})"
+                ),
+                placeholders
+            );
+            let mut conn = pool.get_conn().await.map_err(|_| internal_error())?;
+            conn.exec_map(query, zip_codes.to_vec(), postal_code_from_mysql_row)
+                .await
+                .map_err(|_| internal_error())
+        }
+        DbPool::Sqlite(path) => {
+            let placeholders = vec!["?"; zip_codes.len()].join(", ");
+            let query = format!(
+                concat!(
+                    "SELECT ",
+                    postal_code_columns!(),
+                    " FROM postal_codes WHERE zip_code IN ({})"
+                ),
+                placeholders
+            );
+            let conn = rusqlite::Connection::open(path).map_err(|_| internal_error())?;
+            let mut stmt = conn.prepare(&query).map_err(|_| internal_error())?;
+            let rows = stmt
+                .query_map(
+                    rusqlite::params_from_iter(zip_codes),
+                    postal_code_from_sqlite_row,
+                )
+                .map_err(|_| internal_error())?;
+            rows.collect::<Result<Vec<_>, _>>()
+                .map_err(|_| internal_error())
+        }
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/postal_codes/batch",
+    params(
+        ("lang" = Option<String>, Query, description = "Name language: ja (default) | en (Hepburn romanization)")
+    ),
+    request_body(
+        content = Vec<String>,
+        description = "JSON array of postal codes, or one code per line with Content-Type: application/x-ndjson"
+    ),
+    responses(
+        (status = 200, description = "One result per input, in input order (NDJSON lines for NDJSON requests)", body = BatchLookupResponse),
+        (status = 400, description = "Malformed body or too many postal codes", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    )
+)]
+async fn batch_postal_codes(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<LangParams>,
+    headers: HeaderMap,
+    body: String,
+) -> Result<Response, ApiError> {
+    let lang = params.lang.unwrap_or_default();
+    let ndjson = is_ndjson(&headers);
+    let inputs = parse_batch_body(&body, ndjson)?;
+    if inputs.len() > state.batch_max_zip_codes {
+        return Err(bad_request_error(format!(
+            "too many postal codes: {} (max {})",
+            inputs.len(),
+            state.batch_max_zip_codes
+        )));
+    }
+
+    let normalized: Vec<Option<String>> = inputs
+        .iter()
+        .map(|input| normalize_zip_code(input).ok())
+        .collect();
+    let mut unique: Vec<String> = Vec::new();
+    let mut seen: HashSet<&str> = HashSet::new();
+    for zip_code in normalized.iter().flatten() {
+        if seen.insert(zip_code) {
+            unique.push(zip_code.clone());
+        }
+    }
+
+    // Same per-code keys as get_postal_code, so both endpoints share cache entries.
+    let cache_keys: Vec<String> = unique.iter().map(|z| format!("postal:zip:{z}")).collect();
+    let cached = cache_mget::<Vec<PostalCode>>(&state.cache, &cache_keys).await;
+
+    let mut found: HashMap<String, Vec<PostalCode>> = HashMap::new();
+    let mut misses: Vec<String> = Vec::new();
+    for (zip_code, hit) in unique.into_iter().zip(cached) {
+        match hit {
+            Some(records) => {
+                found.insert(zip_code, records);
+            }
+            None => misses.push(zip_code),
+        }
+    }
+
+    let mut fetched: HashMap<String, Vec<PostalCode>> = HashMap::new();
+    for record in fetch_postal_codes_by_zip(&state, &misses).await? {
+        fetched
+            .entry(record.zip_code.clone())
+            .or_default()
+            .push(record);
+    }
+    let to_cache: Vec<(String, &Vec<PostalCode>)> = fetched
+        .iter()
+        .map(|(zip_code, records)| (format!("postal:zip:{zip_code}"), records))
+        .collect();
+    cache_set_many(&state.cache, &to_cache, state.cache_ttl_seconds).await;
+    found.extend(fetched);
+
+    let results: Vec<BatchLookupResult> = inputs
+        .into_iter()
+        .zip(normalized)
+        .map(|(input, zip_code)| {
+            let (status, records) = match zip_code.as_ref().map(|z| found.get(z)) {
+                None => (BatchLookupStatus::Invalid, Vec::new()),
+                Some(Some(records)) => (
+                    BatchLookupStatus::Found,
+                    localize_all(records.clone(), lang),
+                ),
+                Some(None) => (BatchLookupStatus::NotFound, Vec::new()),
+            };
+            BatchLookupResult {
+                input,
+                zip_code,
+                status,
+                records,
+            }
+        })
+        .collect();
+
+    if ndjson {
+        let mut body = String::new();
+        for result in &results {
+            body.push_str(&serde_json::to_string(result).map_err(|_| internal_error())?);
+            body.push('\n');
+        }
+        return Ok(([(header::CONTENT_TYPE, NDJSON_CONTENT_TYPE)], body).into_response());
+    }
+    Ok(Json(BatchLookupResponse { results }).into_response())
+}
+
+// SQLite exports carry no snapshot history, so retired codes cannot be told apart there.
+async fn last_snapshot_version(
+    state: &AppState,
+    zip_code: &str,
+) -> Result<Option<String>, ApiError> {
+    match &state.pool {
+        DbPool::Postgres(pool) => {
+            let client = pool.get().await.map_err(|_| internal_error())?;
+            let row = client
+                .query_opt(
+                    "SELECT data_version FROM postal_codes_snapshots
+                     WHERE zip_code = $1
+                     ORDER BY snapshot_created_at DESC
+                     LIMIT 1",
+                    &[&zip_code],
+                )
+                .await
+                .map_err(|_| internal_error())?;
+            Ok(row.map(|row| row.get(0)))
+        }
+        DbPool::MySql(pool) => {
+            use mysql_async::prelude::*;
+            let mut conn = pool.get_conn().await.map_err(|_| internal_error())?;
+            conn.exec_first(
+                "SELECT data_version FROM postal_codes_snapshots
+                 WHERE zip_code = :zip_code
+                 ORDER BY snapshot_created_at DESC
+                 LIMIT 1",
+                mysql_async::params! { "zip_code" => zip_code },
+            )
+            .await
+            .map_err(|_| internal_error())
+        }
+        DbPool::Sqlite(_) => Ok(None),
+    }
+}
+
+/// Snapshot version to answer `as_of` from; `None` reads the live table.
+async fn resolve_as_of(state: &AppState, raw: Option<&str>) -> Result<Option<String>, ApiError> {
+    let Some(raw) = raw.map(str::trim).filter(|raw| !raw.is_empty()) else {
+        return Ok(None);
+    };
+    let as_of = history::parse_as_of(raw).map_err(bad_request_error)?;
+    let cache_key = format!("postal:as_of:{raw}");
+    if let Some(cached) = cache_get::<String>(&state.cache, &cache_key)
+        .await
+        .filter(|cached| is_data_version(cached))
+    {
+        return Ok(Some(cached));
+    }
+
+    // A date picks the last run that left data behind at that time.
+    let audit: Option<(String, String, String)> =
+        match &state.pool {
+            DbPool::Postgres(pool) => {
+                let client = pool.get().await.map_err(|_| internal_error())?;
+                let row =
+                    match &as_of {
+                        AsOf::Version(version) => client
+                            .query_opt(
+                                "SELECT data_version, status, source_url FROM data_update_audits
+                         WHERE data_version = $1",
+                                &[version],
+                            )
+                            .await,
+                        AsOf::At(at) => client
+                            .query_opt(
+                                "SELECT data_version, status, source_url FROM data_update_audits
+                         WHERE status IN ('success', 'rollback') AND run_finished_at <= $1
+                         ORDER BY run_finished_at DESC, id DESC
+                         LIMIT 1",
+                                &[at],
+                            )
+                            .await,
+                    }
+                    .map_err(|_| internal_error())?;
+                row.map(|row| (row.get(0), row.get(1), row.get(2)))
+            }
+            DbPool::MySql(pool) => {
+                use mysql_async::prelude::*;
+                let mut conn = pool.get_conn().await.map_err(|_| internal_error())?;
+                match &as_of {
+                    AsOf::Version(version) => {
+                        conn.exec_first(
+                            "SELECT data_version, status, source_url FROM data_update_audits
+                         WHERE data_version = :data_version",
+                            mysql_async::params! { "data_version" => version },
+                        )
+                        .await
+                    }
+                    AsOf::At(at) => {
+                        conn.exec_first(
+                            "SELECT data_version, status, source_url FROM data_update_audits
+                         WHERE status IN ('success', 'rollback') AND run_finished_at <= :at
+                         ORDER BY run_finished_at DESC, id DESC
+                         LIMIT 1",
+                            mysql_async::params! { "at" => at.naive_utc() },
+                        )
+                        .await
+                    }
+                }
+                .map_err(|_| internal_error())?
+            }
+            DbPool::Sqlite(_) => {
+                return Err(bad_request_error(
+                    "as_of is not available with DATABASE_TYPE=sqlite",
+                ))
+            }
+        };
+
+    let Some((mut data_version, mut status, mut source_url)) = audit else {
+        return Err(bad_request_error(match as_of {
+            AsOf::Version(version) => format!("unknown data_version: {version}"),
+            AsOf::At(_) => format!("no data version at or before {raw}"),
+        }));
+    };
+    if status == STATUS_UNCHANGED {
+        if let Some(matched) = load_audit_before_unchanged(state, &data_version).await? {
+            (data_version, status, source_url) = matched;
+        }
+    }
+    let version = live_snapshot(&data_version, &status, &source_url).ok_or_else(|| {
+        bad_request_error(format!(
+            "data_version {data_version} has no snapshot ({status})"
+        ))
+    })?;
+    cache_set(&state.cache, &cache_key, &version, state.cache_ttl_seconds).await;
+    Ok(Some(version))
+}
+
+/// The run an `unchanged` run matched: the next older one that was not `unchanged` itself.
+async fn load_audit_before_unchanged(
+    state: &AppState,
+    data_version: &str,
+) -> Result<Option<(String, String, String)>, ApiError> {
+    match &state.pool {
+        DbPool::Postgres(pool) => {
+            let client = pool.get().await.map_err(|_| internal_error())?;
+            let row = client
+                .query_opt(
+                    "SELECT data_version, status, source_url FROM data_update_audits
+                     WHERE status <> 'unchanged'
+                       AND id < (SELECT id FROM data_update_audits WHERE data_version = $1)
+                     ORDER BY id DESC
+                     LIMIT 1",
+                    &[&data_version],
+                )
+                .await
+                .map_err(|_| internal_error())?;
+            Ok(row.map(|row| (row.get(0), row.get(1), row.get(2))))
+        }
+        DbPool::MySql(pool) => {
+            use mysql_async::prelude::*;
+            let mut conn = pool.get_conn().await.map_err(|_| internal_error())?;
+            conn.exec_first(
+                "SELECT data_version, status, source_url FROM data_update_audits
+                 WHERE status <> 'unchanged'
+                   AND id < (SELECT id FROM data_update_audits WHERE data_version = :data_version)
+                 ORDER BY id DESC
+                 LIMIT 1",
+                mysql_async::params! { "data_version" => data_version },
+            )
+            .await
+            .map_err(|_| internal_error())
+        }
+        DbPool::Sqlite(_) => Ok(None),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/postal_codes/validate",
+    params(
+        ("zip_code" = String, Query, description = "Postal code; hyphens, full-width digits and 〒 are accepted")
+    ),
+    responses(
+        (status = 200, description = "Validation result with a reason when invalid", body = ValidateResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    )
+)]
+async fn validate_postal_code(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<ValidateParams>,
+) -> Result<Json<ValidateResponse>, ApiError> {
+    let mut response = ValidateResponse {
+        input: params.zip_code,
+        normalized: None,
+        valid: false,
+        reason: None,
+        message: None,
+        last_seen_data_version: None,
+        records: Vec::new(),
+    };
+
+    let zip_code = match normalize_zip_code(&response.input) {
+        Ok(zip_code) => zip_code,
+        Err(e) => {
+            response.reason = Some(ValidationReason::BadFormat);
+            response.message = Some(e.to_string());
+            return Ok(Json(response));
+        }
+    };
+    response.normalized = Some(zip_code.clone());
+
+    let cache_key = format!("postal:zip:{zip_code}");
+    let records = match cache_get::<Vec<PostalCode>>(&state.cache, &cache_key).await {
+        Some(cached) => cached,
+        None => {
+            let records =
+                fetch_postal_codes_by_zip(&state, std::slice::from_ref(&zip_code)).await?;
+            if !records.is_empty() {
+                cache_set(&state.cache, &cache_key, &records, state.cache_ttl_seconds).await;
+            }
+            records
+        }
+    };
+
+    if !records.is_empty() {
+        response.valid = true;
+        response.records = records;
+        return Ok(Json(response));
+    }
+
+    response.last_seen_data_version = last_snapshot_version(&state, &zip_code).await?;
+    response.reason = Some(if response.last_seen_data_version.is_some() {
+        ValidationReason::Retired
+    } else {
+        ValidationReason::Unknown
+    });
+    Ok(Json(response))
+}
+
+// WHERE clause shared by both search versions (no cursor, no limit). `p(n)` renders
+// placeholder n: 1-3 search candidates, 4 town_detail, 5 floor, 6 prefecture_id, 7 city_id.
+fn search_where(op: &str, target: SearchTarget, p: impl Fn(usize) -> String) -> String {
+    format!(
+        "({}) AND {}",
+        search_address_clause(op, target, &p),
+        search_filter_clause(&p)
+    )
+}
+
+fn search_address_clause(op: &str, target: SearchTarget, p: impl Fn(usize) -> String) -> String {
+    (1..=3)
+        .flat_map(|n| {
+            let term = p(n);
+            target
+                .columns()
+                .iter()
+                .map(move |column| format!("{column} {op} {term}"))
+        })
+        .collect::<Vec<_>>()
+        .join(" OR ")
+}
+
+fn search_filter_clause(p: impl Fn(usize) -> String) -> String {
+    format!(
+        "({detail} = '' OR town_detail LIKE {detail}) AND ({floor} = '' OR floor = {floor}) \
+         AND ({pref} = 0 OR prefecture_id = {pref}) AND ({city} = '' OR city_id = {city})",
+        detail = p(4),
+        floor = p(5),
+        pref = p(6),
+        city = p(7),
+    )
+}
+
+const SQLITE_FTS_TABLE: &str = "postal_codes_fts";
+
+// The trigram tokenizer serves LIKE patterns of 3+ characters from its index and scans
+// for shorter ones, so the same patterns can be matched against the FTS table.
+fn sqlite_search_where(op: &str, target: SearchTarget, use_fts: bool) -> String {
+    // The FTS table only indexes the Japanese names.
+    if !use_fts || op != "LIKE" || target.romaji {
+        return search_where(op, target, sqlite_search_placeholder);
+    }
+    format!(
+        "rowid IN (SELECT rowid FROM {SQLITE_FTS_TABLE} WHERE {}) AND {}",
+        search_address_clause(op, target, sqlite_search_placeholder),
+        search_filter_clause(sqlite_search_placeholder)
+    )
+}
+
+fn sqlite_has_fts(conn: &rusqlite::Connection) -> bool {
+    conn.query_row(
+        "SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = ?1",
+        [SQLITE_FTS_TABLE],
+        |_| Ok(()),
+    )
+    .is_ok()
+}
+
+// Postgres cannot infer a type for parameters that are only compared with literals.
+fn pg_search_placeholder(n: usize) -> String {
+    match n {
+        4 | 5 | 7 | 8 | 9 => format!("${n}::text"),
+        6 => "$6::int2".to_string(),
+        _ => format!("${n}"),
+    }
+}
+
+fn mysql_search_placeholder(n: usize) -> String {
+    let name = [
+        "",
+        "c1",
+        "c2",
+        "c3",
+        "town_detail",
+        "floor",
+        "prefecture_id",
+        "city_id",
+        "rank_exact",
+        "rank_prefix",
+    ][n];
+    format!(":{name}")
+}
+
+fn sqlite_search_placeholder(n: usize) -> String {
+    format!("?{n}")
+}
+
+// Always bind three candidates; duplicates are harmless inside the OR.
+fn pad_search_terms(mut terms: Vec<String>) -> [String; 3] {
+    while terms.len() < 3 {
+        terms.push(terms[0].clone());
+    }
+    [terms[0].clone(), terms[1].clone(), terms[2].clone()]
+}
+
+// Relevance tiers, best first; the SQL ORDER BY and `match_score` must agree.
+const SCORE_EXACT: f64 = 1.0;
+const SCORE_PREFIX: f64 = 0.8;
+const SCORE_PARTIAL: f64 = 0.5;
+const SCORE_VARIANT_PENALTY: f64 = 0.05;
+const SCORE_EDIT_PENALTY: f64 = 0.15;
+
+// ORDER BY for one candidate: placeholder 8 is the candidate itself, 9 its prefix pattern.
+fn search_rank_order(target: SearchTarget, p: impl Fn(usize) -> String) -> String {
+    let any = |op: &str, term: &str| {
+        target
+            .columns()
+            .iter()
+            .map(|column| format!("{column} {op} {term}"))
+            .collect::<Vec<_>>()
+            .join(" OR ")
+    };
+    format!(
+        " ORDER BY CASE WHEN {} THEN 0 WHEN {} THEN 1 ELSE 2 END, zip_code, prefecture_id, city, town",
+        any("=", &p(8)),
+        any("LIKE", &p(9)),
+    )
+}
+
+fn match_score(record: &PostalCode, target: SearchTarget, candidate: &str) -> f64 {
+    target
+        .values(record)
+        .iter()
+        .map(|value| {
+            if value == candidate {
+                SCORE_EXACT
+            } else if value.starts_with(candidate) {
+                SCORE_PREFIX
+            } else if value.contains(candidate) {
+                SCORE_PARTIAL
+            } else {
+                0.0
+            }
+        })
+        .fold(0.0, f64::max)
+}
+
+/// Scores each record against the candidates (original input first) and orders by score.
+/// The sort is stable, so ties keep the order the database returned.
+fn rank_search_results(
+    records: Vec<PostalCode>,
+    candidates: &[String],
+    target: SearchTarget,
+) -> Vec<ScoredPostalCode> {
+    let mut ranked: Vec<ScoredPostalCode> = records
+        .into_iter()
+        .map(|record| {
+            let score = candidates
+                .iter()
+                .enumerate()
+                .map(|(index, candidate)| {
+                    let score = match_score(&record, target, candidate);
+                    if index > 0 && score > 0.0 {
+                        score - SCORE_VARIANT_PENALTY
+                    } else {
+                        score
+                    }
+                })
+                .fold(0.0, f64::max);
+            ScoredPostalCode {
+                record,
+                score: (score * 100.0).round() / 100.0,
+                edit_distance: None,
+            }
+        })
+        .collect();
+    ranked.sort_by(|a, b| b.score.total_cmp(&a.score));
+    ranked
+}
+
+/// Scans the fuzzy index for rows within the allowed edit distance of `term`, which must
+/// already be folded (`fold_variants`, or `fold_romaji` for romaji input).
+fn fuzzy_rank(
+    entries: &[FuzzyEntry],
+    term: &str,
+    target: SearchTarget,
+    filters: &SearchFilters,
+    limit: usize,
+) -> Vec<ScoredPostalCode> {
+    let term: Vec<char> = term.chars().collect();
+    let max_edits = allowed_edits(term.len());
+    let columns = target.field.name_indexes();
+    let mut ranked: Vec<ScoredPostalCode> = entries
+        .iter()
+        .filter(|entry| {
+            let r = &entry.record;
+            (filters.prefecture_id == 0 || r.prefecture_id == filters.prefecture_id)
+                && (filters.city_id.is_empty() || r.city_id == filters.city_id)
+                && (filters.floor.is_empty() || r.floor == filters.floor)
+                && r.town_detail.contains(&filters.town_detail_filter)
+        })
+        .filter_map(|entry| {
+            let distance = entry.distance(&term, columns, target.romaji, max_edits)?;
+            let score = if distance > 0 {
+                SCORE_PARTIAL - SCORE_EDIT_PENALTY * f64::from(distance)
+            } else {
+                let names = entry.names(target.romaji);
+                if columns.iter().any(|&c| names[c] == term) {
+                    SCORE_EXACT
+                } else if columns.iter().any(|&c| names[c].starts_with(&term)) {
+                    SCORE_PREFIX
+                } else {
+                    SCORE_PARTIAL
+                }
+            };
+            Some(ScoredPostalCode {
+                record: entry.record.clone(),
+                score: (score * 100.0).round() / 100.0,
+                edit_distance: Some(distance),
+            })
+        })
+        .collect();
+    // Entries are in zip_code order and the sort is stable, so ties stay in that order.
+    ranked.sort_by(|a, b| b.score.total_cmp(&a.score));
+    ranked.truncate(limit);
+    ranked
+}
+
+#[utoipa::path(
+    get,
+    path = "/postal_codes/search",
+    params(
+        ("address" = String, Query, description = "Address keyword (kana normalization is applied; romaji is matched against the romanized names)"),
+        ("limit" = Option<u32>, Query, description = "Result size, default=50, max=200"),
+        ("mode" = Option<String>, Query, description = "Search mode: exact | prefix | partial (default) | fuzzy"),
+        ("field" = Option<String>, Query, description = "Column to match: all (default) | city | town"),
+        ("prefecture_id" = Option<i16>, Query, description = "Only return rows of this prefecture"),
+        ("city_id" = Option<String>, Query, description = "Only return rows of this municipality"),
+        ("town_detail" = Option<String>, Query, description = "Partial match on the parenthetical town detail (e.g. 1丁目)"),
+        ("floor" = Option<String>, Query, description = "Exact floor of a high-rise building code (e.g. 1階, 地階・階層不明)"),
+        ("lang" = Option<String>, Query, description = "Name language: ja (default) | en (Hepburn romanization)"),
+        ("as_of" = Option<String>, Query, description = "Answer from a past dataset: a data_version, a date (YYYY-MM-DD, end of day JST) or an RFC 3339 timestamp; not combinable with mode=fuzzy")
+    ),
+    responses(
+        (status = 200, description = "Address search result, most relevant first", body = [ScoredPostalCode]),
+        (status = 400, description = "Malformed as_of", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    )
+)]
+async fn search_postal_code(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<SearchParams>,
+) -> Result<Json<Vec<ScoredPostalCode>>, ApiError> {
+    let normalized_address = normalize_input(&params.address);
+    if normalized_address.is_empty() {
+        return Ok(Json(Vec::new()));
+    }
+
+    let candidates = build_search_candidates(&normalized_address);
+    let mode = params.mode.unwrap_or_default();
+    let limit_u32 = params.limit.unwrap_or(50).clamp(1, 200);
+    let limit = i64::from(limit_u32);
+    let limit_usize = limit_u32 as usize;
+    let filters = SearchFilters::from_params(&params);
+    let target = SearchTarget {
+        field: filters.field,
+        romaji: is_romaji(&normalized_address),
+    };
+    let lang = params.lang.unwrap_or_default();
+    let version = resolve_as_of(&state, params.as_of.as_deref()).await?;
+    if version.is_some() && mode == SearchMode::Fuzzy {
+        // The fuzzy index only holds the live rows.
+        return Err(bad_request_error(
+            "as_of cannot be combined with mode=fuzzy",
+        ));
+    }
+    let cache_key = format!(
+        "postal:search:{}:{}:{limit}:{}:{}",
+        mode.as_cache_key(),
+        normalized_address,
+        filters.cache_key(),
+        version.as_deref().unwrap_or_default()
+    );
+    if let Some(cached) = cache_get::<Vec<ScoredPostalCode>>(&state.cache, &cache_key).await {
+        return Ok(Json(localize_ranked(cached, lang)));
+    }
+
+    if mode == SearchMode::Fuzzy {
+        let entries = load_fuzzy_index(&state).await?;
+        let term = if target.romaji {
+            candidates[0].clone()
+        } else {
+            fold_variants(&normalized_address)
+        };
+        // A full scan of the index; keep it off the async worker threads.
+        let ranked = tokio::task::block_in_place(|| {
+            fuzzy_rank(&entries, &term, target, &filters, limit_usize)
+        });
+        cache_set(&state.cache, &cache_key, &ranked, state.cache_ttl_seconds).await;
+        return Ok(Json(localize_ranked(ranked, lang)));
+    }
+
+    let search_terms: Vec<String> = candidates
+        .iter()
+        .map(|candidate| build_search_term(mode, candidate))
+        .collect();
+    let op = if mode.needs_like() { "LIKE" } else { "=" };
+
+    // Candidates are queried one at a time so exact spellings fill the limit first.
+    match &state.pool {
+        DbPool::Postgres(pool) => {
+            let query = format!(
+                "SELECT {} FROM {} WHERE {}{} LIMIT $10",
+                postal_code_columns!(),
+                postal_code_source(version.is_some(), "$11"),
+                search_where(op, target, pg_search_placeholder),
+                search_rank_order(target, pg_search_placeholder)
+            );
+
+            let client = pool.get().await.map_err(|_| internal_error())?;
+            let mut result: Vec<PostalCode> = Vec::new();
+            let mut seen: HashSet<PostalCode> = HashSet::new();
+
+            for (search_term, candidate) in search_terms.iter().zip(&candidates) {
+                if result.len() >= limit_usize {
+                    break;
+                }
+                let remaining = (limit_usize - result.len()) as i64;
+                let rank_prefix = format!("{candidate}%");
+                let mut query_params: Vec<&(dyn ToSql + Sync)> = vec![
+                    search_term,
+                    search_term,
+                    search_term,
+                    &filters.town_detail_term,
+                    &filters.floor,
+                    &filters.prefecture_id,
+                    &filters.city_id,
+                    candidate,
+                    &rank_prefix,
+                    &remaining,
+                ];
+                if let Some(version) = &version {
+                    query_params.push(version);
+                }
+                let rows = client
+                    .query(&query, &query_params)
+                    .await
+                    .map_err(|_| internal_error())?;
+
+                let chunk: Vec<PostalCode> = rows.iter().map(postal_code_from_pg_row).collect();
+                append_unique_with_limit(&mut result, &mut seen, chunk, limit_usize);
+            }
+
+            let ranked = rank_search_results(result, &candidates, target);
+            cache_set(&state.cache, &cache_key, &ranked, state.cache_ttl_seconds).await;
+            Ok(Json(localize_ranked(ranked, lang)))
+        }
+        DbPool::MySql(pool) => {
+            let query = format!(
+                "SELECT {} FROM {} WHERE {}{} LIMIT :limit",
+                postal_code_columns!(),
+                postal_code_source(version.is_some(), ":as_of"),
+                search_where(op, target, mysql_search_placeholder),
+                search_rank_order(target, mysql_search_placeholder)
+            );
+
+            use mysql_async::prelude::*;
+            let mut conn = pool.get_conn().await.map_err(|_| internal_error())?;
+            let mut result: Vec<PostalCode> = Vec::new();
+            let mut seen: HashSet<PostalCode> = HashSet::new();
+
+            for (search_term, candidate) in search_terms.iter().zip(&candidates) {
+                if result.len() >= limit_usize {
+                    break;
+                }
                 let remaining = (limit_usize - result.len()) as i64;
+                let rank_prefix = format!("{candidate}%");
                 let chunk: Vec<PostalCode> = conn
                     .exec_map(
-                        if mode.needs_like() {
-                            QUERY_LIKE
-                        } else {
-                            QUERY_EXACT
-                        },
+                        query.as_str(),
                         mysql_async::params! {
-                            "search" => search_term,
+                            "c1" => search_term,
+                            "c2" => search_term,
+                            "c3" => search_term,
+                            "town_detail" => &filters.town_detail_term,
+                            "floor" => &filters.floor,
+                            "prefecture_id" => filters.prefecture_id,
+                            "city_id" => &filters.city_id,
+                            "rank_exact" => candidate,
+                            "rank_prefix" => &rank_prefix,
                             "limit" => remaining,
+                            "as_of" => &version,
                         },
-                        |(zip_code, prefecture_id, city_id, prefecture, city, town)| PostalCode {
-                            zip_code,
-                            prefecture_id,
-                            city_id,
-                            prefecture,
-                            city,
-                            town,
-                        },
+                        postal_code_from_mysql_row,
                     )
                     .await
                     .map_err(|_| internal_error())?;
                 append_unique_with_limit(&mut result, &mut seen, chunk, limit_usize);
             }
 
-            cache_set(&state.cache, &cache_key, &result, state.cache_ttl_seconds).await;
-            Ok(Json(result))
+            let ranked = rank_search_results(result, &candidates, target);
+            cache_set(&state.cache, &cache_key, &ranked, state.cache_ttl_seconds).await;
+            Ok(Json(localize_ranked(ranked, lang)))
+        }
+        DbPool::Sqlite(path) => {
+            let query = format!(
+                "SELECT {} FROM postal_codes WHERE {}{} LIMIT ?10",
+                postal_code_columns!(),
+                sqlite_search_where(op, target, state.sqlite_fts),
+                search_rank_order(target, sqlite_search_placeholder)
+            );
+
+            let result: Vec<PostalCode> = {
+                let conn = rusqlite::Connection::open(path).map_err(|_| internal_error())?;
+                let mut stmt = conn.prepare(&query).map_err(|_| internal_error())?;
+                let mut result: Vec<PostalCode> = Vec::new();
+                let mut seen: HashSet<PostalCode> = HashSet::new();
+
+                for (search_term, candidate) in search_terms.iter().zip(&candidates) {
+                    if result.len() >= limit_usize {
+                        break;
+                    }
+                    let remaining = (limit_usize - result.len()) as i64;
+                    let rank_prefix = format!("{candidate}%");
+                    let rows = stmt
+                        .query_map(
+                            rusqlite::params![
+                                search_term,
+                                search_term,
+                                search_term,
+                                filters.town_detail_term,
+                                filters.floor,
+                                filters.prefecture_id,
+                                filters.city_id,
+                                candidate,
+                                rank_prefix,
+                                remaining
+                            ],
+                            postal_code_from_sqlite_row,
+                        )
+                        .map_err(|_| internal_error())?;
+
+                    let chunk = rows
+                        .collect::<Result<Vec<_>, _>>()
+                        .map_err(|_| internal_error())?;
+                    append_unique_with_limit(&mut result, &mut seen, chunk, limit_usize);
+                }
+                result
+            };
+
+            let ranked = rank_search_results(result, &candidates, target);
+            cache_set(&state.cache, &cache_key, &ranked, state.cache_ttl_seconds).await;
+            Ok(Json(localize_ranked(ranked, lang)))
+        }
+    }
+}
+
+const SEARCH_V2_ORDER: &str = " ORDER BY zip_code, prefecture_id, city, town";
+
+#[utoipa::path(
+    get,
+    path = "/v2/postal_codes/search",
+    params(
+        ("address" = String, Query, description = "Address keyword (kana normalization is applied; romaji is matched against the romanized names)"),
+        ("limit" = Option<u32>, Query, description = "Page size, default=50, max=200"),
+        ("mode" = Option<String>, Query, description = "Search mode: exact | prefix | partial (default)"),
+        ("field" = Option<String>, Query, description = "Column to match: all (default) | city | town"),
+        ("prefecture_id" = Option<i16>, Query, description = "Only return rows of this prefecture"),
+        ("city_id" = Option<String>, Query, description = "Only return rows of this municipality"),
+        ("town_detail" = Option<String>, Query, description = "Partial match on the parenthetical town detail (e.g. 1丁目)"),
+        ("floor" = Option<String>, Query, description = "Exact floor of a high-rise building code (e.g. 1階, 地階・階層不明)"),
+        ("cursor" = Option<String>, Query, description = "next_cursor of the previous page"),
+        ("include_total" = Option<bool>, Query, description = "Also count all matches (default=false)"),
+        ("lang" = Option<String>, Query, description = "Name language: ja (default) | en (Hepburn romanization)")
+    ),
+    responses(
+        (status = 200, description = "One page of the address search, ordered by postal code", body = SearchPage),
+        (status = 400, description = "Malformed cursor", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    )
+)]
+async fn search_postal_code_v2(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<SearchParams>,
+) -> Result<Json<SearchPage>, ApiError> {
+    if params.as_of.is_some() {
+        return Err(bad_request_error(
+            "as_of is only supported by /postal_codes/search",
+        ));
+    }
+    let include_total = params.include_total.unwrap_or(false);
+    let normalized_address = normalize_input(&params.address);
+    if normalized_address.is_empty() {
+        return Ok(Json(SearchPage {
+            items: Vec::new(),
+            next_cursor: None,
+            total: include_total.then_some(0),
+        }));
+    }
+
+    let cursor_raw = params.cursor.clone().unwrap_or_default();
+    let cursor = if cursor_raw.is_empty() {
+        None
+    } else {
+        Some(SearchCursor::decode(&cursor_raw).ok_or_else(|| bad_request_error("invalid cursor"))?)
+    };
+
+    let mode = params.mode.unwrap_or_default();
+    if mode == SearchMode::Fuzzy {
+        return Err(bad_request_error(
+            "mode=fuzzy is ranked and not paginated; use /postal_codes/search",
+        ));
+    }
+    let limit_u32 = params.limit.unwrap_or(50).clamp(1, 200);
+    let limit_usize = limit_u32 as usize;
+    // One extra row tells whether another page exists.
+    let fetch_limit = i64::from(limit_u32) + 1;
+    let filters = SearchFilters::from_params(&params);
+    let target = SearchTarget {
+        field: filters.field,
+        romaji: is_romaji(&normalized_address),
+    };
+    let lang = params.lang.unwrap_or_default();
+    let cache_key = format!(
+        "postal:search:v2:{}:{}:{limit_u32}:{}:{include_total}:{cursor_raw}",
+        mode.as_cache_key(),
+        normalized_address,
+        filters.cache_key()
+    );
+    if let Some(cached) = cache_get::<SearchPage>(&state.cache, &cache_key).await {
+        return Ok(Json(cached.localized(lang)));
+    }
+
+    let terms = pad_search_terms(
+        build_search_candidates(&normalized_address)
+            .iter()
+            .map(|candidate| build_search_term(mode, candidate))
+            .collect(),
+    );
+    let op = if mode.needs_like() { "LIKE" } else { "=" };
+    let after = cursor.clone().unwrap_or_default();
+
+    let (mut items, total) = match &state.pool {
+        DbPool::Postgres(pool) => {
+            let where_clause = search_where(op, target, pg_search_placeholder);
+            let query = format!(
+                "SELECT {} FROM postal_codes WHERE {where_clause} AND ($8::text = '' OR (zip_code, prefecture_id, city, town) > ($8, $9::int2, $10, $11)){SEARCH_V2_ORDER} LIMIT $12",
+                postal_code_columns!(),
+            );
+            let client = pool.get().await.map_err(|_| internal_error())?;
+            let rows = client
+                .query(
+                    &query,
+                    &[
+                        &terms[0],
+                        &terms[1],
+                        &terms[2],
+                        &filters.town_detail_term,
+                        &filters.floor,
+                        &filters.prefecture_id,
+                        &filters.city_id,
+                        &after.zip_code,
+                        &after.prefecture_id,
+                        &after.city,
+                        &after.town,
+                        &fetch_limit,
+                    ],
+                )
+                .await
+                .map_err(|_| internal_error())?;
+            let items: Vec<PostalCode> = rows.iter().map(postal_code_from_pg_row).collect();
+
+            let total = if include_total {
+                let row = client
+                    .query_one(
+                        &format!("SELECT COUNT(*) FROM postal_codes WHERE {where_clause}"),
+                        &[
+                            &terms[0],
+                            &terms[1],
+                            &terms[2],
+                            &filters.town_detail_term,
+                            &filters.floor,
+                            &filters.prefecture_id,
+                            &filters.city_id,
+                        ],
+                    )
+                    .await
+                    .map_err(|_| internal_error())?;
+                Some(row.get::<_, i64>(0))
+            } else {
+                None
+            };
+            (items, total)
+        }
+        DbPool::MySql(pool) => {
+            use mysql_async::prelude::*;
+            let where_clause = search_where(op, target, mysql_search_placeholder);
+            let query = format!(
+                "SELECT {} FROM postal_codes WHERE {where_clause} AND (:after_zip = '' OR (zip_code, prefecture_id, city, town) > (:after_zip, :after_pref, :after_city, :after_town)){SEARCH_V2_ORDER} LIMIT :limit",
+                postal_code_columns!(),
+            );
+            let mut conn = pool.get_conn().await.map_err(|_| internal_error())?;
+            let items: Vec<PostalCode> = conn
+                .exec_map(
+                    query,
+                    mysql_async::params! {
+                        "c1" => &terms[0],
+                        "c2" => &terms[1],
+                        "c3" => &terms[2],
+                        "town_detail" => &filters.town_detail_term,
+                        "floor" => &filters.floor,
+                        "prefecture_id" => filters.prefecture_id,
+                        "city_id" => &filters.city_id,
+                        "after_zip" => &after.zip_code,
+                        "after_pref" => after.prefecture_id,
+                        "after_city" => &after.city,
+                        "after_town" => &after.town,
+                        "limit" => fetch_limit,
+                    },
+                    postal_code_from_mysql_row,
+                )
+                .await
+                .map_err(|_| internal_error())?;
+
+            let total = if include_total {
+                let count: Option<i64> = conn
+                    .exec_first(
+                        format!("SELECT COUNT(*) FROM postal_codes WHERE {where_clause}"),
+                        mysql_async::params! {
+                            "c1" => &terms[0],
+                            "c2" => &terms[1],
+                            "c3" => &terms[2],
+                            "town_detail" => &filters.town_detail_term,
+                            "floor" => &filters.floor,
+                            "prefecture_id" => filters.prefecture_id,
+                            "city_id" => &filters.city_id,
+                        },
+                    )
+                    .await
+                    .map_err(|_| internal_error())?;
+                Some(count.unwrap_or(0))
+            } else {
+                None
+            };
+            (items, total)
+        }
+        DbPool::Sqlite(path) => {
+            let where_clause = sqlite_search_where(op, target, state.sqlite_fts);
+            let query = format!(
+                "SELECT {} FROM postal_codes WHERE {where_clause} AND (?8 = '' OR (zip_code, prefecture_id, city, town) > (?8, ?9, ?10, ?11)){SEARCH_V2_ORDER} LIMIT ?12",
+                postal_code_columns!(),
+            );
+            let conn = rusqlite::Connection::open(path).map_err(|_| internal_error())?;
+            let items = {
+                let mut stmt = conn.prepare(&query).map_err(|_| internal_error())?;
+                let rows = stmt
+                    .query_map(
+                        rusqlite::params![
+                            terms[0],
+                            terms[1],
+                            terms[2],
+                            filters.town_detail_term,
+                            filters.floor,
+                            filters.prefecture_id,
+                            filters.city_id,
+                            after.zip_code,
+                            after.prefecture_id,
+                            after.city,
+                            after.town,
+                            fetch_limit,
+                        ],
+                        postal_code_from_sqlite_row,
+                    )
+                    .map_err(|_| internal_error())?;
+                rows.collect::<Result<Vec<_>, _>>()
+                    .map_err(|_| internal_error())?
+            };
+
+            let total = if include_total {
+                let count: i64 = conn
+                    .query_row(
+                        &format!("SELECT COUNT(*) FROM postal_codes WHERE {where_clause}"),
+                        rusqlite::params![
+                            terms[0],
+                            terms[1],
+                            terms[2],
+                            filters.town_detail_term,
+                            filters.floor,
+                            filters.prefecture_id,
+                            filters.city_id,
+                        ],
+                        |row| row.get(0),
+                    )
+                    .map_err(|_| internal_error())?;
+                Some(count)
+            } else {
+                None
+            };
+            (items, total)
+        }
+    };
+
+    let next_cursor = if items.len() > limit_usize {
+        items.truncate(limit_usize);
+        items.last().map(|last| SearchCursor::after(last).encode())
+    } else {
+        None
+    };
+    let result = SearchPage {
+        items,
+        next_cursor,
+        total,
+    };
+    cache_set(&state.cache, &cache_key, &result, state.cache_ttl_seconds).await;
+    Ok(Json(result.localized(lang)))
+}
+
+// Municipality dictionary for the resolver, shared across prefectures.
+async fn load_all_postal_codes(state: &AppState) -> Result<Vec<PostalCode>, ApiError> {
+    const QUERY: &str = concat!(
+        "SELECT ",
+        postal_code_columns!(),
+        " FROM postal_codes ORDER BY zip_code, prefecture_id, city, town"
+    );
+
+    match &state.pool {
+        DbPool::Postgres(pool) => {
+            let client = pool.get().await.map_err(|_| internal_error())?;
+            let rows = client
+                .query(QUERY, &[])
+                .await
+                .map_err(|_| internal_error())?;
+            Ok(rows.iter().map(postal_code_from_pg_row).collect())
+        }
+        DbPool::MySql(pool) => {
+            use mysql_async::prelude::*;
+            let mut conn = pool.get_conn().await.map_err(|_| internal_error())?;
+            conn.exec_map(QUERY, (), postal_code_from_mysql_row)
+                .await
+                .map_err(|_| internal_error())
+        }
+        DbPool::Sqlite(path) => {
+            let conn = rusqlite::Connection::open(path).map_err(|_| internal_error())?;
+            let mut stmt = conn.prepare(QUERY).map_err(|_| internal_error())?;
+            let rows = stmt
+                .query_map([], postal_code_from_sqlite_row)
+                .map_err(|_| internal_error())?;
+            rows.collect::<Result<Vec<_>, _>>()
+                .map_err(|_| internal_error())
+        }
+    }
+}
+
+// Kept in process rather than in Redis: the index holds every row and is rebuilt from
+// the database once it is older than the cache TTL.
+async fn load_fuzzy_index(state: &AppState) -> Result<Arc<Vec<FuzzyEntry>>, ApiError> {
+    let max_age = Duration::from_secs(state.cache_ttl_seconds);
+    if let Some(index) = state.fuzzy_index.read().await.as_ref() {
+        if index.loaded_at.elapsed() < max_age {
+            return Ok(index.entries.clone());
+        }
+    }
+
+    let mut index = state.fuzzy_index.write().await;
+    // Another request may have rebuilt it while we waited for the lock.
+    if let Some(index) = index.as_ref() {
+        if index.loaded_at.elapsed() < max_age {
+            return Ok(index.entries.clone());
+        }
+    }
+    let records = load_all_postal_codes(state).await?;
+    let entries = Arc::new(tokio::task::block_in_place(|| {
+        records.into_iter().map(FuzzyEntry::new).collect()
+    }));
+    *index = Some(FuzzyIndex {
+        loaded_at: Instant::now(),
+        entries: Arc::clone(&entries),
+    });
+    Ok(entries)
+}
+
+async fn load_city_dictionary(state: &AppState) -> Result<Vec<CityEntry>, ApiError> {
+    let cache_key = "postal:resolve:cities";
+    if let Some(cached) = cache_get::<Vec<CityEntry>>(&state.cache, cache_key).await {
+        return Ok(cached);
+    }
+
+    const QUERY: &str = "SELECT DISTINCT prefecture_id, prefecture, city_id, city
+        FROM postal_codes WHERE kind = 'residential' ORDER BY city_id";
+
+    let result: Vec<CityEntry> = match &state.pool {
+        DbPool::Postgres(pool) => {
+            let client = pool.get().await.map_err(|_| internal_error())?;
+            let rows = client
+                .query(QUERY, &[])
+                .await
+                .map_err(|_| internal_error())?;
+            rows.iter()
+                .map(|row| CityEntry {
+                    prefecture_id: row.get(0),
+                    prefecture: row.get(1),
+                    city_id: row.get(2),
+                    city: row.get(3),
+                })
+                .collect()
+        }
+        DbPool::MySql(pool) => {
+            use mysql_async::prelude::*;
+            let mut conn = pool.get_conn().await.map_err(|_| internal_error())?;
+            conn.exec_map(QUERY, (), |(prefecture_id, prefecture, city_id, city)| {
+                CityEntry {
+                    prefecture_id,
+                    prefecture,
+                    city_id,
+                    city,
+                }
+            })
+            .await
+            .map_err(|_| internal_error())?
+        }
+        DbPool::Sqlite(path) => {
+            let conn = rusqlite::Connection::open(path).map_err(|_| internal_error())?;
+            let mut stmt = conn.prepare(QUERY).map_err(|_| internal_error())?;
+            let rows = stmt
+                .query_map([], |row| {
+                    Ok(CityEntry {
+                        prefecture_id: row.get(0)?,
+                        prefecture: row.get(1)?,
+                        city_id: row.get(2)?,
+                        city: row.get(3)?,
+                    })
+                })
+                .map_err(|_| internal_error())?;
+            rows.collect::<Result<Vec<_>, _>>()
+                .map_err(|_| internal_error())?
+        }
+    };
+
+    cache_set(&state.cache, cache_key, &result, state.cache_ttl_seconds).await;
+    Ok(result)
+}
+
+async fn load_city_rows(state: &AppState, city_id: &str) -> Result<Vec<PostalCode>, ApiError> {
+    match &state.pool {
+        DbPool::Postgres(pool) => {
+            const QUERY: &str = concat!(
+                "SELECT ",
+                postal_code_columns!(),
+                " FROM postal_codes WHERE city_id = $1 AND kind = 'residential' ORDER BY zip_code"
+            );
+            let client = pool.get().await.map_err(|_| internal_error())?;
+            let rows = client
+                .query(QUERY, &[&city_id])
+                .await
+                .map_err(|_| internal_error())?;
+            Ok(rows.iter().map(postal_code_from_pg_row).collect())
+        }
+        DbPool::MySql(pool) => {
+            const QUERY: &str = concat!(
+                "SELECT ",
+                postal_code_columns!(),
+                " FROM postal_codes WHERE city_id = :city_id AND kind = 'residential' ORDER BY zip_code"
+            );
+            use mysql_async::prelude::*;
+            let mut conn = pool.get_conn().await.map_err(|_| internal_error())?;
+            conn.exec_map(
+                QUERY,
+                mysql_async::params! { "city_id" => city_id },
+                postal_code_from_mysql_row,
+            )
+            .await
+            .map_err(|_| internal_error())
+        }
+        DbPool::Sqlite(path) => {
+            const QUERY: &str = concat!(
+                "SELECT ",
+                postal_code_columns!(),
+                " FROM postal_codes WHERE city_id = ?1 AND kind = 'residential' ORDER BY zip_code"
+            );
+            let conn = rusqlite::Connection::open(path).map_err(|_| internal_error())?;
+            let mut stmt = conn.prepare(QUERY).map_err(|_| internal_error())?;
+            let rows = stmt
+                .query_map([city_id], postal_code_from_sqlite_row)
+                .map_err(|_| internal_error())?;
+            rows.collect::<Result<Vec<_>, _>>()
+                .map_err(|_| internal_error())
+        }
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/postal_codes/resolve",
+    params(
+        ("address" = String, Query, description = "Free-form address, e.g. 東京都千代田区千代田1-1")
+    ),
+    responses(
+        (status = 200, description = "Best matching postal code with a confidence score", body = ResolveResponse),
+        (status = 404, description = "No municipality matched", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    )
+)]
+async fn resolve_postal_code(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<ResolveParams>,
+) -> Result<Json<ResolveResponse>, ApiError> {
+    let normalized_address = normalize_input(&params.address);
+    if normalized_address.is_empty() {
+        return Err(not_found_error());
+    }
+
+    let cache_key = format!("postal:resolve:{normalized_address}");
+    if let Some(cached) = cache_get::<ResolveResponse>(&state.cache, &cache_key).await {
+        return Ok(Json(cached));
+    }
+
+    let cities = load_city_dictionary(&state).await?;
+    let head = resolve::match_head(&normalized_address, &cities).ok_or_else(not_found_error)?;
+    let rows = load_city_rows(&state, &head.city.city_id).await?;
+    let result = resolve::resolve_town(&head, &rows);
+
+    cache_set(&state.cache, &cache_key, &result, state.cache_ttl_seconds).await;
+    Ok(Json(result))
+}
+
+#[utoipa::path(
+    get,
+    path = "/postal_codes/suggest",
+    params(
+        ("q" = String, Query, description = "What has been typed so far: the start of a postal code (100, 〒100-00) or of an address"),
+        ("limit" = Option<u32>, Query, description = "Result size, default=10, max=50")
+    ),
+    responses(
+        (status = 200, description = "Completions; postal codes are grouped by municipality", body = [Suggestion]),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    )
+)]
+async fn suggest_postal_codes(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<SuggestParams>,
+) -> Result<Json<Vec<Suggestion>>, ApiError> {
+    let input = normalize_input(&params.q);
+    if input.is_empty() {
+        return Ok(Json(Vec::new()));
+    }
+    let limit = params.limit.unwrap_or(10).clamp(1, 50) as usize;
+
+    let cache_key = format!("postal:suggest:{limit}:{input}");
+    if let Some(cached) = cache_get::<Vec<Suggestion>>(&state.cache, &cache_key).await {
+        return Ok(Json(cached));
+    }
+
+    let result = match suggest::zip_prefix(&input) {
+        Some(prefix) if prefix.len() < suggest::MIN_ZIP_PREFIX => Vec::new(),
+        Some(prefix) => {
+            let rows = load_zip_prefix_rows(&state, &prefix, limit).await?;
+            suggest::postal_code_suggestions(rows)
+        }
+        None => suggest_addresses(&state, &input, limit).await?,
+    };
+
+    cache_set(
+        &state.cache,
+        &cache_key,
+        &result,
+        state.suggest_cache_ttl_seconds,
+    )
+    .await;
+    Ok(Json(result))
+}
+
+async fn suggest_addresses(
+    state: &AppState,
+    input: &str,
+    limit: usize,
+) -> Result<Vec<Suggestion>, ApiError> {
+    let romaji = is_romaji(input);
+    let mut result = Vec::new();
+    if !romaji {
+        let cities = load_city_dictionary(state).await?;
+        let key = search_key(input);
+        // Once a municipality has been typed out, complete its towns.
+        if let Some(head) = resolve::match_head(input, &cities) {
+            if head.rest.is_empty() {
+                result = suggest::city_suggestions(&key, &cities, limit);
+            }
+            let rows = load_city_rows(state, &head.city.city_id).await?;
+            let remaining = limit.saturating_sub(result.len());
+            result.extend(suggest::city_town_suggestions(&head, &rows, remaining));
+            return Ok(result);
+        }
+        result = suggest::city_suggestions(&key, &cities, limit);
+    }
+
+    if result.len() < limit && input.chars().count() >= suggest::MIN_TOWN_PREFIX {
+        let remaining = limit - result.len();
+        let rows = load_town_prefix_rows(state, input, romaji, remaining).await?;
+        result.extend(suggest::town_suggestions(&rows, remaining));
+    }
+    Ok(result)
+}
+
+// One row per postal code; a code shared by several towns is labelled with the first.
+async fn load_zip_prefix_rows(
+    state: &AppState,
+    prefix: &str,
+    limit: usize,
+) -> Result<Vec<PostalCode>, ApiError> {
+    const COLUMNS: &str =
+        "zip_code, prefecture_id, city_id, prefecture, city, MIN(COALESCE(town, ''))";
+    const GROUP: &str =
+        "GROUP BY zip_code, prefecture_id, city_id, prefecture, city ORDER BY zip_code";
+    let (from, to) = suggest::zip_range(prefix);
+    let limit = limit as i64;
+    let row = |zip_code, prefecture_id, city_id, prefecture, city, town| PostalCode {
+        zip_code,
+        prefecture_id,
+        city_id,
+        prefecture,
+        city,
+        town,
+        ..Default::default()
+    };
+
+    match &state.pool {
+        DbPool::Postgres(pool) => {
+            let query = format!(
+                "SELECT {COLUMNS} FROM postal_codes WHERE zip_code BETWEEN $1 AND $2 {GROUP} LIMIT $3"
+            );
+            let client = pool.get().await.map_err(|_| internal_error())?;
+            let rows = client
+                .query(&query, &[&from, &to, &limit])
+                .await
+                .map_err(|_| internal_error())?;
+            Ok(rows
+                .iter()
+                .map(|r| row(r.get(0), r.get(1), r.get(2), r.get(3), r.get(4), r.get(5)))
+                .collect())
+        }
+        DbPool::MySql(pool) => {
+            let query = format!(
+                "SELECT {COLUMNS} FROM postal_codes WHERE zip_code BETWEEN :from AND :to {GROUP} LIMIT :limit"
+            );
+            use mysql_async::prelude::*;
+            let mut conn = pool.get_conn().await.map_err(|_| internal_error())?;
+            conn.exec_map(
+                query.as_str(),
+                mysql_async::params! { "from" => &from, "to" => &to, "limit" => limit },
+                |(zip_code, prefecture_id, city_id, prefecture, city, town)| {
+                    row(zip_code, prefecture_id, city_id, prefecture, city, town)
+                },
+            )
+            .await
+            .map_err(|_| internal_error())
         }
         DbPool::Sqlite(path) => {
-            const QUERY_LIKE: &str =
-                "SELECT zip_code, prefecture_id, city_id, prefecture, city, COALESCE(town, '')
-                FROM postal_codes
-                WHERE prefecture LIKE ?1 OR city LIKE ?1 OR town LIKE ?1
-                LIMIT ?2";
-            const QUERY_EXACT: &str =
-                "SELECT zip_code, prefecture_id, city_id, prefecture, city, COALESCE(town, '')
-                FROM postal_codes
-                WHERE prefecture = ?1 OR city = ?1 OR town = ?1
-                LIMIT ?2";
-
+            let query = format!(
+                "SELECT {COLUMNS} FROM postal_codes WHERE zip_code BETWEEN ?1 AND ?2 {GROUP} LIMIT ?3"
+            );
             let conn = rusqlite::Connection::open(path).map_err(|_| internal_error())?;
-            let mut result: Vec<PostalCode> = Vec::new();
-            let mut seen: HashSet<PostalCode> = HashSet::new();
-
-            for search_term in &search_terms {
-                if result.len() >= limit_usize {
-                    break;
-                }
-                let remaining = (limit_usize - result.len()) as i64;
-                let mut stmt = conn
-                    .prepare(if mode.needs_like() {
-                        QUERY_LIKE
-                    } else {
-                        QUERY_EXACT
-                    })
-                    .map_err(|_| internal_error())?;
-
-                let rows = stmt
-                    .query_map(rusqlite::params![search_term, remaining], |row| {
-                        Ok(PostalCode {
-                            zip_code: row.get(0)?,
-                            prefecture_id: row.get(1)?,
-                            city_id: row.get(2)?,
-                            prefecture: row.get(3)?,
-                            city: row.get(4)?,
-                            town: row.get(5)?,
-                        })
-                    })
-                    .map_err(|_| internal_error())?;
+            let mut stmt = conn.prepare(&query).map_err(|_| internal_error())?;
+            let rows = stmt
+                .query_map(rusqlite::params![from, to, limit], |r| {
+                    Ok(row(
+                        r.get(0)?,
+                        r.get(1)?,
+                        r.get(2)?,
+                        r.get(3)?,
+                        r.get(4)?,
+                        r.get(5)?,
+                    ))
+                })
+                .map_err(|_| internal_error())?;
+            rows.collect::<Result<Vec<_>, _>>()
+                .map_err(|_| internal_error())
+        }
+    }
+}
 
-                let chunk = rows
-                    .collect::<Result<Vec<_>, _>>()
-                    .map_err(|_| internal_error())?;
-                append_unique_with_limit(&mut result, &mut seen, chunk, limit_usize);
-            }
+// Towns anywhere in the country whose name starts with `input`. Several rows may share a
+// town, so a few more than `limit` are fetched for `town_suggestions` to collapse.
+async fn load_town_prefix_rows(
+    state: &AppState,
+    input: &str,
+    romaji: bool,
+    limit: usize,
+) -> Result<Vec<PostalCode>, ApiError> {
+    let target = SearchTarget {
+        field: SearchField::Town,
+        romaji,
+    };
+    let terms = pad_search_terms(
+        build_search_candidates(input)
+            .iter()
+            .map(|candidate| build_search_term(SearchMode::Prefix, candidate))
+            .collect(),
+    );
+    let fetch_limit = (limit * 4) as i64;
+    const FILTER: &str = "AND kind = 'residential' AND town <> '' ORDER BY town, zip_code";
 
-            cache_set(&state.cache, &cache_key, &result, state.cache_ttl_seconds).await;
-            Ok(Json(result))
+    match &state.pool {
+        DbPool::Postgres(pool) => {
+            let query = format!(
+                "SELECT {} FROM postal_codes WHERE ({}) {FILTER} LIMIT $4",
+                postal_code_columns!(),
+                search_address_clause("LIKE", target, pg_search_placeholder)
+            );
+            let client = pool.get().await.map_err(|_| internal_error())?;
+            let rows = client
+                .query(&query, &[&terms[0], &terms[1], &terms[2], &fetch_limit])
+                .await
+                .map_err(|_| internal_error())?;
+            Ok(rows.iter().map(postal_code_from_pg_row).collect())
+        }
+        DbPool::MySql(pool) => {
+            let query = format!(
+                "SELECT {} FROM postal_codes WHERE ({}) {FILTER} LIMIT :limit",
+                postal_code_columns!(),
+                search_address_clause("LIKE", target, mysql_search_placeholder)
+            );
+            use mysql_async::prelude::*;
+            let mut conn = pool.get_conn().await.map_err(|_| internal_error())?;
+            conn.exec_map(
+                query.as_str(),
+                mysql_async::params! {
+                    "c1" => &terms[0],
+                    "c2" => &terms[1],
+                    "c3" => &terms[2],
+                    "limit" => fetch_limit,
+                },
+                postal_code_from_mysql_row,
+            )
+            .await
+            .map_err(|_| internal_error())
+        }
+        DbPool::Sqlite(path) => {
+            let clause = search_address_clause("LIKE", target, sqlite_search_placeholder);
+            let clause = if state.sqlite_fts && !romaji {
+                format!("rowid IN (SELECT rowid FROM {SQLITE_FTS_TABLE} WHERE {clause})")
+            } else {
+                format!("({clause})")
+            };
+            let query = format!(
+                "SELECT {} FROM postal_codes WHERE {clause} {FILTER} LIMIT ?4",
+                postal_code_columns!()
+            );
+            let conn = rusqlite::Connection::open(path).map_err(|_| internal_error())?;
+            let mut stmt = conn.prepare(&query).map_err(|_| internal_error())?;
+            let rows = stmt
+                .query_map(
+                    rusqlite::params![terms[0], terms[1], terms[2], fetch_limit],
+                    postal_code_from_sqlite_row,
+                )
+                .map_err(|_| internal_error())?;
+            rows.collect::<Result<Vec<_>, _>>()
+                .map_err(|_| internal_error())
         }
     }
 }
@@ -1179,10 +3309,12 @@ async fn get_prefectures(
     get,
     path = "/postal_codes/cities",
     params(
-        ("prefecture_id" = i16, Query, description = "Prefecture id")
+        ("prefecture_id" = Option<i16>, Query, description = "Prefecture id"),
+        ("city_id" = Option<String>, Query, description = "Look up one municipality instead; a code retired by a merger returns its successors")
     ),
     responses(
         (status = 200, description = "City list", body = [CityResponse]),
+        (status = 400, description = "Neither prefecture_id nor city_id given", body = ErrorResponse),
         (status = 500, description = "Internal server error", body = ErrorResponse)
     )
 )]
@@ -1190,7 +3322,15 @@ async fn get_cities(
     State(state): State<Arc<AppState>>,
     Query(params): Query<CityParams>,
 ) -> Result<Json<Vec<CityResponse>>, ApiError> {
-    let cache_key = format!("postal:cities:{}", params.prefecture_id);
+    if let Some(city_id) = params.city_id.as_deref().map(str::trim) {
+        if !city_id.is_empty() {
+            return resolve_city(&state, city_id).await.map(Json);
+        }
+    }
+    let Some(prefecture_id) = params.prefecture_id else {
+        return Err(bad_request_error("prefecture_id or city_id is required"));
+    };
+    let cache_key = format!("postal:cities:{prefecture_id}");
     if let Some(cached) = cache_get::<Vec<CityResponse>>(&state.cache, &cache_key).await {
         return Ok(Json(cached));
     }
@@ -1201,7 +3341,7 @@ async fn get_cities(
             let rows = client
                 .query(
                     "SELECT DISTINCT city_id, city FROM postal_codes WHERE prefecture_id = $1 ORDER BY city_id",
-                    &[&params.prefecture_id],
+                    &[&prefecture_id],
                 )
                 .await
                 .map_err(|_| internal_error())?;
@@ -1211,6 +3351,7 @@ async fn get_cities(
                 .map(|row| CityResponse {
                     city_id: row.get(0),
                     city: row.get(1),
+                    successor_of: None,
                 })
                 .collect();
             cache_set(&state.cache, &cache_key, &result, state.cache_ttl_seconds).await;
@@ -1223,9 +3364,13 @@ async fn get_cities(
                 .exec_map(
                     "SELECT DISTINCT city_id, city FROM postal_codes WHERE prefecture_id = :prefecture_id ORDER BY city_id",
                     mysql_async::params! {
-                        "prefecture_id" => params.prefecture_id,
+                        "prefecture_id" => prefecture_id,
+                    },
+                    |(city_id, city)| CityResponse {
+                        city_id,
+                        city,
+                        successor_of: None,
                     },
-                    |(city_id, city)| CityResponse { city_id, city },
                 )
                 .await
                 .map_err(|_| internal_error())?;
@@ -1245,10 +3390,11 @@ async fn get_cities(
                     .map_err(|_| internal_error())?;
 
                 let rows = stmt
-                    .query_map([params.prefecture_id], |row| {
+                    .query_map([prefecture_id], |row| {
                         Ok(CityResponse {
                             city_id: row.get(0)?,
                             city: row.get(1)?,
+                            successor_of: None,
                         })
                     })
                     .map_err(|_| internal_error())?;
@@ -1263,6 +3409,358 @@ async fn get_cities(
     }
 }
 
+/// A city by code. A code retired by a merger resolves to the municipalities that replaced it.
+async fn resolve_city(state: &AppState, city_id: &str) -> Result<Vec<CityResponse>, ApiError> {
+    let cache_key = format!("postal:cities:city:{city_id}");
+    if let Some(cached) = cache_get::<Vec<CityResponse>>(&state.cache, &cache_key).await {
+        return Ok(cached);
+    }
+
+    let cities = load_city_dictionary(state).await?;
+    let find = |city_id: &str| cities.iter().find(|entry| entry.city_id == city_id);
+    let response = |entry: &CityEntry, successor_of: Option<&str>| CityResponse {
+        city_id: entry.city_id.clone(),
+        city: entry.city.clone(),
+        successor_of: successor_of.map(str::to_string),
+    };
+    let result: Vec<CityResponse> = match find(city_id) {
+        Some(entry) => vec![response(entry, None)],
+        None => {
+            let changes = load_municipality_changes(state).await?;
+            municipality::successors(city_id, &changes, |id| find(id).is_some())
+                .iter()
+                .filter_map(|id| find(id))
+                .map(|entry| response(entry, Some(city_id)))
+                .collect()
+        }
+    };
+    cache_set(&state.cache, &cache_key, &result, state.cache_ttl_seconds).await;
+    Ok(result)
+}
+
+async fn load_municipality_changes(state: &AppState) -> Result<Vec<MunicipalityChange>, ApiError> {
+    const QUERY: &str = "SELECT old_city_id, new_city_id, old_city, new_city, effective_date,
+            data_version, source, shared_zip_codes
+        FROM municipality_changes";
+    /// `Date` is how the backend hands over `effective_date`.
+    type ChangeRow<Date = String> = (
+        String,
+        String,
+        String,
+        String,
+        Option<Date>,
+        Option<String>,
+        String,
+        i64,
+    );
+    let change = |(
+        old_city_id,
+        new_city_id,
+        old_city,
+        new_city,
+        effective_date,
+        data_version,
+        source,
+        shared_zip_codes,
+    ): ChangeRow| {
+        MunicipalityChange {
+            old_city_id,
+            new_city_id,
+            old_city,
+            new_city,
+            effective_date,
+            data_version,
+            source: MunicipalityChangeSource::from_db_value(&source),
+            shared_zip_codes,
+        }
+    };
+
+    match &state.pool {
+        DbPool::Postgres(pool) => {
+            let client = pool.get().await.map_err(|_| internal_error())?;
+            let rows = client
+                .query(QUERY, &[])
+                .await
+                .map_err(|_| internal_error())?;
+            Ok(rows
+                .iter()
+                .map(|row| {
+                    let effective_date: Option<chrono::NaiveDate> = row.get(4);
+                    change((
+                        row.get(0),
+                        row.get(1),
+                        row.get(2),
+                        row.get(3),
+                        effective_date.map(|date| date.to_string()),
+                        row.get(5),
+                        row.get(6),
+                        row.get(7),
+                    ))
+                })
+                .collect())
+        }
+        DbPool::MySql(pool) => {
+            use mysql_async::prelude::*;
+            let mut conn = pool.get_conn().await.map_err(|_| internal_error())?;
+            let rows: Vec<ChangeRow<chrono::NaiveDate>> =
+                conn.query(QUERY).await.map_err(|_| internal_error())?;
+            Ok(rows
+                .into_iter()
+                .map(
+                    |(old, new, old_city, new_city, date, version, source, shared)| {
+                        change((
+                            old,
+                            new,
+                            old_city,
+                            new_city,
+                            date.map(|date| date.to_string()),
+                            version,
+                            source,
+                            shared,
+                        ))
+                    },
+                )
+                .collect())
+        }
+        DbPool::Sqlite(path) => {
+            let conn = rusqlite::Connection::open(path).map_err(|_| internal_error())?;
+            // Exports built before the table existed simply have no changes.
+            let has_table: bool = conn
+                .query_row(
+                    "SELECT COUNT(*) > 0 FROM sqlite_master
+                     WHERE type = 'table' AND name = 'municipality_changes'",
+                    [],
+                    |row| row.get(0),
+                )
+                .map_err(|_| internal_error())?;
+            if !has_table {
+                return Ok(Vec::new());
+            }
+            let mut stmt = conn.prepare(QUERY).map_err(|_| internal_error())?;
+            let rows = stmt
+                .query_map([], |row| {
+                    Ok((
+                        row.get(0)?,
+                        row.get(1)?,
+                        row.get(2)?,
+                        row.get(3)?,
+                        row.get(4)?,
+                        row.get(5)?,
+                        row.get(6)?,
+                        row.get(7)?,
+                    ))
+                })
+                .map_err(|_| internal_error())?;
+            let rows = rows
+                .collect::<Result<Vec<ChangeRow>, _>>()
+                .map_err(|_| internal_error())?;
+            Ok(rows.into_iter().map(change).collect())
+        }
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/data_versions",
+    params(
+        ("limit" = Option<u32>, Query, description = "Result size, default=100, max=1000")
+    ),
+    responses(
+        (status = 200, description = "Crawler and rollback runs, newest first; empty with DATABASE_TYPE=sqlite", body = [DataVersion]),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    )
+)]
+async fn list_data_versions(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<DataVersionParams>,
+) -> Result<Json<Vec<DataVersion>>, ApiError> {
+    const QUERY: &str = "SELECT data_version, status, source_url, run_finished_at, total_count,
+            inserted_count, updated_count, deleted_count, diff_month, source_sha256
+        FROM data_update_audits ORDER BY id DESC";
+    let limit = params.limit.unwrap_or(100).clamp(1, 1000);
+    let cache_key = format!("postal:data_versions:{limit}");
+    if let Some(cached) = cache_get::<Vec<DataVersion>>(&state.cache, &cache_key).await {
+        return Ok(Json(cached));
+    }
+
+    let mut result: Vec<DataVersion> = match &state.pool {
+        DbPool::Postgres(pool) => {
+            let client = pool.get().await.map_err(|_| internal_error())?;
+            let rows = client
+                .query(&format!("{QUERY} LIMIT $1"), &[&i64::from(limit)])
+                .await
+                .map_err(|_| internal_error())?;
+            rows.iter()
+                .map(|row| {
+                    let source_url: String = row.get(2);
+                    DataVersion::new(
+                        row.get(0),
+                        row.get(1),
+                        &source_url,
+                        row.get(3),
+                        row.get(4),
+                        row.get(5),
+                        row.get(6),
+                        row.get(7),
+                        row.get(8),
+                        row.get(9),
+                    )
+                })
+                .collect()
+        }
+        DbPool::MySql(pool) => {
+            use mysql_async::prelude::*;
+            let mut conn = pool.get_conn().await.map_err(|_| internal_error())?;
+            type AuditRow = (
+                String,
+                String,
+                String,
+                chrono::NaiveDateTime,
+                i64,
+                i64,
+                i64,
+                i64,
+                Option<String>,
+                Option<String>,
+            );
+            let rows: Vec<AuditRow> = conn
+                .exec(
+                    format!("{QUERY} LIMIT :limit"),
+                    mysql_async::params! { "limit" => limit },
+                )
+                .await
+                .map_err(|_| internal_error())?;
+            rows.into_iter()
+                .map(
+                    |(
+                        data_version,
+                        status,
+                        source_url,
+                        finished,
+                        total,
+                        ins,
+                        upd,
+                        del,
+                        month,
+                        sha,
+                    )| {
+                        DataVersion::new(
+                            data_version,
+                            status,
+                            &source_url,
+                            finished.and_utc(),
+                            total,
+                            ins,
+                            upd,
+                            del,
+                            month,
+                            sha,
+                        )
+                    },
+                )
+                .collect()
+        }
+        // SQLite exports are built from a single version and carry no audit history.
+        DbPool::Sqlite(_) => Vec::new(),
+    };
+    history::carry_unchanged_snapshots(&mut result);
+
+    cache_set(&state.cache, &cache_key, &result, state.cache_ttl_seconds).await;
+    Ok(Json(result))
+}
+
+#[utoipa::path(
+    get,
+    path = "/data_versions/{from}/diff/{to}",
+    params(
+        ("from" = String, Path, description = "Older data_version"),
+        ("to" = String, Path, description = "Newer data_version"),
+        ("format" = Option<String>, Query, description = "json (default) | csv")
+    ),
+    responses(
+        (status = 200, description = "Rows added, removed and modified between the two versions", body = DataVersionDiff),
+        (status = 400, description = "Unknown data_version, a version without snapshot, or DATABASE_TYPE=sqlite", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    )
+)]
+async fn diff_data_versions(
+    State(state): State<Arc<AppState>>,
+    Path((from, to)): Path<(String, String)>,
+    Query(params): Query<DiffParams>,
+) -> Result<Response, ApiError> {
+    if matches!(state.pool, DbPool::Sqlite(_)) {
+        return Err(bad_request_error(
+            "diff is not available with DATABASE_TYPE=sqlite",
+        ));
+    }
+    for version in [&from, &to] {
+        if !is_data_version(version) {
+            return Err(bad_request_error(format!("not a data_version: {version}")));
+        }
+    }
+    let from_snapshot = resolve_as_of(&state, Some(&from))
+        .await?
+        .ok_or_else(internal_error)?;
+    let to_snapshot = resolve_as_of(&state, Some(&to))
+        .await?
+        .ok_or_else(internal_error)?;
+
+    // Not cached: a diff across many months holds most of the dataset.
+    let only_in_from = load_changed_snapshot_rows(&state, &from_snapshot, &to_snapshot).await?;
+    let only_in_to = load_changed_snapshot_rows(&state, &to_snapshot, &from_snapshot).await?;
+    let diff = diff::diff_rows(from, to, only_in_from, only_in_to);
+
+    match params.format.unwrap_or_default() {
+        DiffFormat::Json => Ok(Json(diff).into_response()),
+        DiffFormat::Csv => {
+            let body = diff::diff_to_csv(&diff).map_err(|_| internal_error())?;
+            Ok(([(header::CONTENT_TYPE, "text/csv; charset=utf-8")], body).into_response())
+        }
+    }
+}
+
+/// Rows of snapshot `version` that `other` has no identical row for, in key order.
+async fn load_changed_snapshot_rows(
+    state: &AppState,
+    version: &str,
+    other: &str,
+) -> Result<Vec<PostalCode>, ApiError> {
+    let query = |version: &str, other: &str, binary: bool| {
+        format!(
+            "SELECT {} FROM postal_codes_snapshots postal_codes
+             WHERE postal_codes.data_version = {version} AND NOT EXISTS (
+                 SELECT 1 FROM postal_codes_snapshots other
+                 WHERE other.data_version = {other} AND {}
+             )
+             ORDER BY zip_code, prefecture_id, city, town",
+            postal_code_columns!(),
+            diff::identical_row_condition(binary)
+        )
+    };
+    match &state.pool {
+        DbPool::Postgres(pool) => {
+            let client = pool.get().await.map_err(|_| internal_error())?;
+            let rows = client
+                .query(&query("$1", "$2", false), &[&version, &other])
+                .await
+                .map_err(|_| internal_error())?;
+            Ok(rows.iter().map(postal_code_from_pg_row).collect())
+        }
+        DbPool::MySql(pool) => {
+            use mysql_async::prelude::*;
+            let mut conn = pool.get_conn().await.map_err(|_| internal_error())?;
+            conn.exec_map(
+                query(":version", ":other", true),
+                mysql_async::params! { "version" => version, "other" => other },
+                postal_code_from_mysql_row,
+            )
+            .await
+            .map_err(|_| internal_error())
+        }
+        DbPool::Sqlite(_) => Ok(Vec::new()),
+    }
+}
+
 #[utoipa::path(
     get,
     path = "/health",
@@ -1382,11 +3880,15 @@ async fn swagger_ui() -> Html<&'static str> {
 mod tests {
     use super::{
         build_search_candidates, build_search_term, extract_forwarded_for_ip,
-        extract_non_empty_header, hiragana_to_katakana, is_truthy, katakana_to_hiragana,
-        normalize_search_input, parse_auth_mode, parse_ip_allowlist, parse_path_prefixes,
-        path_matches_prefix, resolve_cache_state, resolve_client_ip, ApiMetrics, AuthConfig,
-        AuthMode, SearchMode,
+        extract_non_empty_header, fuzzy_rank, hiragana_to_katakana, is_ndjson, is_truthy,
+        katakana_to_hiragana, localize, parse_auth_mode, parse_batch_body, parse_ip_allowlist,
+        parse_path_prefixes, path_matches_prefix, pg_search_placeholder, rank_search_results,
+        resolve_cache_state, resolve_client_ip, search_rank_order, search_where, sqlite_has_fts,
+        sqlite_search_placeholder, sqlite_search_where, ApiMetrics, AuthConfig, AuthMode, Lang,
+        SearchCursor, SearchField, SearchFilters, SearchMode, SearchParams, SearchTarget,
+        POSTAL_CODE_COLUMN_COUNT,
     };
+    use crate::fuzzy::{fold_variants, FuzzyEntry};
     use axum::{extract::connect_info::ConnectInfo, http::StatusCode};
     use std::{
         net::{IpAddr, Ipv4Addr, SocketAddr},
@@ -1408,11 +3910,6 @@ mod tests {
         assert_eq!(build_search_term(SearchMode::Partial, "新宿"), "%新宿%");
     }
 
-    #[test]
-    fn normalize_search_input_nfkc_and_trim_spaces() {
-        assert_eq!(normalize_search_input("  ｼﾝ ｼﾞｭｸ  "), "シンジュク");
-    }
-
     #[test]
     fn kana_conversion_hiragana_to_katakana() {
         assert_eq!(hiragana_to_katakana("しんじゅく"), "シンジュク");
@@ -1439,6 +3936,13 @@ mod tests {
         assert_eq!(c, vec!["しんじゅく", "シンジュク"]);
     }
 
+    #[test]
+    fn build_search_candidates_use_the_stored_search_key() {
+        assert_eq!(build_search_candidates("霞ヶ関"), vec!["霞ケ関"]);
+        assert_eq!(build_search_candidates("髙田馬場")[0], "高田馬場");
+        assert_eq!(build_search_candidates("センター")[0], "センタ-");
+    }
+
     #[test]
     fn metrics_snapshot_aggregates_values() {
         let metrics = ApiMetrics::default();
@@ -1597,4 +4101,290 @@ mod tests {
         .expect("value must be present");
         assert_eq!(value, "user@example.com");
     }
+
+    #[test]
+    fn parse_batch_body_accepts_json_array() {
+        let codes = parse_batch_body(r#"["1000001", "100-0001"]"#, false).expect("must parse");
+        assert_eq!(codes, vec!["1000001", "100-0001"]);
+        assert!(parse_batch_body(r#"{"zip_code":"1000001"}"#, false).is_err());
+    }
+
+    #[test]
+    fn parse_batch_body_accepts_ndjson_lines() {
+        let codes = parse_batch_body("\"1000001\"\n\n1600023\r\n", true).expect("must parse");
+        assert_eq!(codes, vec!["1000001", "1600023"]);
+    }
+
+    #[test]
+    fn is_ndjson_checks_content_type() {
+        let mut headers = axum::http::HeaderMap::new();
+        assert!(!is_ndjson(&headers));
+        headers.insert(
+            axum::http::header::CONTENT_TYPE,
+            "application/x-ndjson; charset=utf-8"
+                .parse()
+                .expect("header value must parse"),
+        );
+        assert!(is_ndjson(&headers));
+    }
+
+    #[test]
+    fn search_cursor_roundtrips() {
+        let cursor = SearchCursor {
+            zip_code: "1600023".to_string(),
+            prefecture_id: 13,
+            city: "新宿区".to_string(),
+            town: "西新宿".to_string(),
+        };
+        let encoded = cursor.encode();
+        assert!(encoded.bytes().all(|b| b.is_ascii_hexdigit()));
+        assert_eq!(SearchCursor::decode(&encoded), Some(cursor));
+    }
+
+    #[test]
+    fn search_cursor_rejects_garbage() {
+        assert_eq!(SearchCursor::decode("abc"), None);
+        assert_eq!(SearchCursor::decode("zz"), None);
+        assert_eq!(SearchCursor::decode("7b7d"), None);
+    }
+
+    fn japanese(field: SearchField) -> SearchTarget {
+        SearchTarget {
+            field,
+            romaji: false,
+        }
+    }
+
+    #[test]
+    fn search_where_binds_all_candidates_and_filters() {
+        let clause = search_where(
+            "LIKE",
+            japanese(SearchField::All),
+            sqlite_search_placeholder,
+        );
+        assert!(clause.starts_with(
+            "(prefecture_normalized LIKE ?1 OR city_normalized LIKE ?1 OR town_normalized LIKE ?1 OR"
+        ));
+        assert!(clause.contains("town_normalized LIKE ?3)"));
+        assert!(clause.contains("AND (?5 = '' OR floor = ?5)"));
+        assert!(clause.ends_with("AND (?7 = '' OR city_id = ?7)"));
+    }
+
+    #[test]
+    fn search_where_limits_columns_by_field() {
+        let clause = search_where("=", japanese(SearchField::Town), pg_search_placeholder);
+        assert!(clause
+            .starts_with("(town_normalized = $1 OR town_normalized = $2 OR town_normalized = $3)"));
+        assert!(!clause.contains("prefecture_normalized ="));
+        assert!(clause.contains("($6::int2 = 0 OR prefecture_id = $6::int2)"));
+    }
+
+    #[test]
+    fn search_rank_order_puts_exact_before_prefix() {
+        let order = search_rank_order(japanese(SearchField::City), sqlite_search_placeholder);
+        assert_eq!(
+            order,
+            " ORDER BY CASE WHEN city_normalized = ?8 THEN 0 WHEN city_normalized LIKE ?9 THEN 1 ELSE 2 END, zip_code, prefecture_id, city, town"
+        );
+    }
+
+    #[test]
+    fn rank_search_results_orders_by_match_quality() {
+        let row = |zip_code: &str, town: &str| common::models::PostalCode {
+            zip_code: zip_code.to_string(),
+            city: "新宿区".to_string(),
+            town: town.to_string(),
+            ..Default::default()
+        };
+        let records = vec![
+            row("1690072", "大久保"),
+            row("1600022", "新宿"),
+            row("1600023", "西新宿"),
+            row("1620845", "新宿区市谷本村町"),
+        ];
+        let candidates = vec!["新宿".to_string(), "シンジュク".to_string()];
+        let ranked = rank_search_results(records, &candidates, japanese(SearchField::Town));
+
+        let order: Vec<(&str, f64)> = ranked
+            .iter()
+            .map(|r| (r.record.zip_code.as_str(), r.score))
+            .collect();
+        assert_eq!(
+            order,
+            vec![
+                ("1600022", 1.0),
+                ("1620845", 0.8),
+                ("1600023", 0.5),
+                ("1690072", 0.0),
+            ]
+        );
+
+        let kana = rank_search_results(
+            vec![row("1600022", "シンジュク")],
+            &candidates,
+            japanese(SearchField::Town),
+        );
+        assert_eq!(kana[0].score, 0.95);
+    }
+
+    #[test]
+    fn sqlite_search_where_uses_fts_for_like_only() {
+        let fts = sqlite_search_where("LIKE", japanese(SearchField::Town), true);
+        assert!(fts.starts_with(
+            "rowid IN (SELECT rowid FROM postal_codes_fts WHERE town_normalized LIKE ?1 OR town_normalized LIKE ?2 OR town_normalized LIKE ?3) AND"
+        ));
+        assert!(fts.ends_with("AND (?7 = '' OR city_id = ?7)"));
+
+        let exact = sqlite_search_where("=", japanese(SearchField::Town), true);
+        assert_eq!(
+            exact,
+            search_where("=", japanese(SearchField::Town), sqlite_search_placeholder)
+        );
+        let scan = sqlite_search_where("LIKE", japanese(SearchField::Town), false);
+        assert!(scan.starts_with("(town_normalized LIKE ?1"));
+    }
+
+    #[test]
+    fn sqlite_fts_matches_the_same_rows_as_like() {
+        let conn = rusqlite::Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE postal_codes (zip_code TEXT, prefecture_id INTEGER, city_id TEXT,
+                 prefecture_normalized TEXT, city_normalized TEXT, town_normalized TEXT,
+                 town_detail TEXT, floor TEXT);
+             INSERT INTO postal_codes VALUES
+                 ('1600023', 13, '13104', '東京都', '新宿区', '西新宿', '', ''),
+                 ('1600022', 13, '13104', '東京都', '新宿区', '新宿', '', ''),
+                 ('1000001', 13, '13101', '東京都', '千代田区', '千代田', '', '');",
+        )
+        .unwrap();
+        assert!(!sqlite_has_fts(&conn));
+        conn.execute_batch(
+            "CREATE VIRTUAL TABLE postal_codes_fts USING fts5(
+                 prefecture_normalized, city_normalized, town_normalized,
+                 content='postal_codes', tokenize='trigram');
+             INSERT INTO postal_codes_fts(postal_codes_fts) VALUES('rebuild');",
+        )
+        .unwrap();
+        assert!(sqlite_has_fts(&conn));
+
+        let zip_codes = |use_fts: bool, term: &str| -> Vec<String> {
+            let query = format!(
+                "SELECT zip_code FROM postal_codes WHERE {} ORDER BY zip_code",
+                sqlite_search_where("LIKE", japanese(SearchField::All), use_fts)
+            );
+            let mut stmt = conn.prepare(&query).unwrap();
+            stmt.query_map(rusqlite::params![term, term, term, "", "", 0, ""], |row| {
+                row.get(0)
+            })
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap()
+        };
+        // 3+ characters go through the trigram index, shorter terms fall back to a scan.
+        for term in ["%西新宿%", "%新宿%", "千代田%"] {
+            assert_eq!(zip_codes(true, term), zip_codes(false, term), "{term}");
+        }
+        assert_eq!(zip_codes(true, "%新宿%"), vec!["1600022", "1600023"]);
+    }
+
+    #[test]
+    fn romaji_input_is_folded_and_matched_against_romanized_names() {
+        assert_eq!(build_search_candidates("Chiyoda-ku"), vec!["chiyodaku"]);
+
+        let target = SearchTarget {
+            field: SearchField::City,
+            romaji: true,
+        };
+        let clause = search_where("LIKE", target, sqlite_search_placeholder);
+        assert!(clause.starts_with("(lower(replace(replace(city_roma, '-', ''), ' ', '')) LIKE ?1"));
+        assert!(!sqlite_search_where("LIKE", target, true).contains("postal_codes_fts"));
+
+        let record = common::models::PostalCode {
+            city: "千代田区".to_string(),
+            city_roma: "Chiyoda-ku".to_string(),
+            ..Default::default()
+        };
+        let ranked = rank_search_results(vec![record], &["chiyoda".to_string()], target);
+        assert_eq!(ranked[0].score, 0.8);
+    }
+
+    #[test]
+    fn localize_swaps_in_romanized_names() {
+        let mut record = common::models::PostalCode {
+            prefecture: "東京都".to_string(),
+            city: "新宿区".to_string(),
+            town: "西新宿".to_string(),
+            prefecture_roma: "Tokyo-to".to_string(),
+            city_roma: "Shinjuku-ku".to_string(),
+            ..Default::default()
+        };
+        localize(&mut record, Lang::Ja);
+        assert_eq!(record.city, "新宿区");
+
+        localize(&mut record, Lang::En);
+        assert_eq!(record.prefecture, "Tokyo-to");
+        assert_eq!(record.city, "Shinjuku-ku");
+        // No reading: the Japanese name is kept.
+        assert_eq!(record.town, "西新宿");
+    }
+
+    #[test]
+    fn fuzzy_rank_orders_by_edit_distance_and_applies_filters() {
+        let row = |zip_code: &str, prefecture_id: i16, town: &str| {
+            FuzzyEntry::new(common::models::PostalCode {
+                zip_code: zip_code.to_string(),
+                prefecture_id,
+                town: town.to_string(),
+                ..Default::default()
+            })
+        };
+        let entries = vec![
+            row("1000013", 13, "霞が関"),
+            row("3000000", 8, "霞ケ関"),
+            row("1000014", 13, "永田町"),
+            row("1000015", 13, "霞ヶ岡"),
+        ];
+        let params: SearchParams = serde_json::from_value(serde_json::json!({
+            "address": "霞ヶ関",
+            "field": "town",
+            "prefecture_id": 13
+        }))
+        .unwrap();
+        let filters = SearchFilters::from_params(&params);
+        let target = SearchTarget {
+            field: SearchField::Town,
+            romaji: false,
+        };
+
+        let ranked = fuzzy_rank(&entries, &fold_variants("霞ヶ関"), target, &filters, 10);
+        let found: Vec<(&str, f64, Option<u32>)> = ranked
+            .iter()
+            .map(|r| (r.record.zip_code.as_str(), r.score, r.edit_distance))
+            .collect();
+        assert_eq!(
+            found,
+            vec![("1000013", 1.0, Some(0)), ("1000015", 0.35, Some(1))]
+        );
+
+        assert_eq!(
+            fuzzy_rank(&entries, &fold_variants("霞ヶ関"), target, &filters, 1).len(),
+            1
+        );
+    }
+
+    #[test]
+    fn fuzzy_mode_parses_and_skips_like() {
+        let mode: SearchMode = serde_json::from_value(serde_json::json!("fuzzy")).unwrap();
+        assert_eq!(mode, SearchMode::Fuzzy);
+        assert!(!mode.needs_like());
+        assert_eq!(build_search_term(mode, "新宿"), "新宿");
+    }
+
+    #[test]
+    fn postal_code_column_count_matches_column_list() {
+        let columns = postal_code_columns!().replace("COALESCE(town, '')", "town");
+        let columns: Vec<&str> = columns.split(',').map(str::trim).collect();
+        assert_eq!(columns.len(), POSTAL_CODE_COLUMN_COUNT);
+        assert_eq!(columns, crate::diff::SNAPSHOT_COLUMNS);
+    }
 }
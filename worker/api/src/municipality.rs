@@ -0,0 +1,86 @@
+use common::models::MunicipalityChange;
+use std::collections::HashSet;
+
+/// Mergers rarely chain more than two or three times; the bound only guards against cycles
+/// in hand-written seed files.
+const MAX_HOPS: usize = 10;
+
+/// Current codes that replaced the retired `city_id`, following chains of changes
+/// (A merged into B, B later into C). Successors that took over more postal codes come first.
+pub fn successors(
+    city_id: &str,
+    changes: &[MunicipalityChange],
+    is_current: impl Fn(&str) -> bool,
+) -> Vec<String> {
+    let mut result: Vec<String> = Vec::new();
+    let mut visited: HashSet<&str> = HashSet::from([city_id]);
+    let mut frontier: Vec<&str> = vec![city_id];
+    for _ in 0..MAX_HOPS {
+        let mut next: Vec<&str> = Vec::new();
+        for old in frontier {
+            let mut direct: Vec<&MunicipalityChange> = changes
+                .iter()
+                .filter(|change| change.old_city_id == old)
+                .collect();
+            direct.sort_by(|a, b| {
+                b.shared_zip_codes
+                    .cmp(&a.shared_zip_codes)
+                    .then_with(|| a.new_city_id.cmp(&b.new_city_id))
+            });
+            for change in direct {
+                let new = change.new_city_id.as_str();
+                if !visited.insert(new) {
+                    continue;
+                }
+                if is_current(new) {
+                    result.push(new.to_string());
+                } else {
+                    next.push(new);
+                }
+            }
+        }
+        if next.is_empty() {
+            break;
+        }
+        frontier = next;
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::successors;
+    use common::models::MunicipalityChange;
+
+    fn change(old: &str, new: &str, shared_zip_codes: i64) -> MunicipalityChange {
+        MunicipalityChange {
+            old_city_id: old.to_string(),
+            new_city_id: new.to_string(),
+            shared_zip_codes,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn successors_follow_chains_to_current_codes() {
+        let changes = vec![change("01001", "01002", 3), change("01002", "01003", 5)];
+        let current = |city_id: &str| city_id == "01003";
+        assert_eq!(successors("01001", &changes, current), vec!["01003"]);
+        assert!(successors("09999", &changes, current).is_empty());
+    }
+
+    #[test]
+    fn successors_of_a_split_are_ordered_by_shared_codes() {
+        let changes = vec![change("01001", "01002", 2), change("01001", "01003", 7)];
+        assert_eq!(
+            successors("01001", &changes, |_| true),
+            vec!["01003", "01002"]
+        );
+    }
+
+    #[test]
+    fn successors_stop_at_cycles() {
+        let changes = vec![change("01001", "01002", 1), change("01002", "01001", 1)];
+        assert!(successors("01001", &changes, |_| false).is_empty());
+    }
+}
@@ -0,0 +1,342 @@
+use common::models::PostalCode;
+use serde::{Deserialize, Serialize};
+use std::sync::OnceLock;
+use utoipa::ToSchema;
+
+const PREF_JSON: &str = include_str!("../../common/pref.json");
+
+/// One municipality of the city dictionary the resolver matches against.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct CityEntry {
+    pub prefecture_id: i16,
+    pub prefecture: String,
+    pub city_id: String,
+    pub city: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct ResolveResponse {
+    pub prefecture: String,
+    pub city: String,
+    pub town: String,
+    /// Part of the input after the matched town (banchi, building, ...).
+    pub remainder: String,
+    /// 0.0 - 1.0; how much of the address could be matched unambiguously.
+    pub confidence: f64,
+    pub best_match: Option<PostalCode>,
+    /// Other rows that matched equally well.
+    pub candidates: Vec<PostalCode>,
+}
+
+/// Prefecture and municipality found at the head of an address.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HeadMatch {
+    pub city: CityEntry,
+    pub prefecture_given: bool,
+    pub city_ambiguous: bool,
+    pub rest: String,
+}
+
+#[derive(Deserialize)]
+struct PrefJsonEntry {
+    id: i16,
+    label: String,
+}
+
+fn prefectures() -> &'static [(i16, String)] {
+    static PREFECTURES: OnceLock<Vec<(i16, String)>> = OnceLock::new();
+    PREFECTURES.get_or_init(|| {
+        serde_json::from_str::<Vec<PrefJsonEntry>>(PREF_JSON)
+            .expect("pref.json is malformed")
+            .into_iter()
+            // id 0 is the "please select" placeholder
+            .filter(|p| p.id > 0)
+            .map(|p| (p.id, p.label))
+            .collect()
+    })
+}
+
+fn split_prefecture(address: &str) -> (Option<i16>, &str) {
+    for (id, label) in prefectures() {
+        if let Some(rest) = address.strip_prefix(label.as_str()) {
+            return (Some(*id), rest);
+        }
+    }
+    (None, address)
+}
+
+// County towns ("余市郡余市町") are usually written without the county part.
+fn city_aliases(city: &str) -> impl Iterator<Item = &str> {
+    let without_county = city
+        .split_once('郡')
+        .map(|(_, town)| town)
+        .filter(|town| !town.is_empty());
+    std::iter::once(city).chain(without_county)
+}
+
+/// Finds the prefecture and the longest matching municipality at the start of `address`.
+/// `address` is expected to be normalized (NFKC, no whitespace).
+pub fn match_head(address: &str, cities: &[CityEntry]) -> Option<HeadMatch> {
+    let (prefecture_id, rest) = split_prefecture(address);
+
+    let mut best: Vec<(&CityEntry, usize)> = Vec::new();
+    for entry in cities {
+        if prefecture_id.is_some_and(|id| id != entry.prefecture_id) {
+            continue;
+        }
+        let Some(len) = city_aliases(&entry.city)
+            .filter(|alias| rest.starts_with(alias))
+            .map(str::len)
+            .max()
+        else {
+            continue;
+        };
+        match best.first() {
+            Some((_, best_len)) if len < *best_len => {}
+            Some((_, best_len)) if len == *best_len => best.push((entry, len)),
+            _ => best = vec![(entry, len)],
+        }
+    }
+
+    let (city, len) = best.first()?;
+    Some(HeadMatch {
+        city: (*city).clone(),
+        prefecture_given: prefecture_id.is_some(),
+        city_ambiguous: best.len() > 1,
+        rest: rest[*len..].to_string(),
+    })
+}
+
+fn kanji_digit(c: char) -> Option<u32> {
+    "〇一二三四五六七八九"
+        .chars()
+        .position(|k| k == c)
+        .map(|p| p as u32)
+}
+
+/// Reads the chome / banchi number at the start of `s` ("1-2", "三丁目", "十二丁目").
+fn leading_number(s: &str) -> Option<u32> {
+    let digits: String = s.chars().take_while(|c| c.is_ascii_digit()).collect();
+    if !digits.is_empty() {
+        return digits.parse().ok();
+    }
+
+    let mut value = 0;
+    let mut current = 0;
+    let mut seen = false;
+    for c in s.chars() {
+        if c == '十' {
+            value += if current == 0 { 10 } else { current * 10 };
+            current = 0;
+        } else if let Some(d) = kanji_digit(c) {
+            current = current * 10 + d;
+        } else {
+            break;
+        }
+        seen = true;
+    }
+    seen.then_some(value + current)
+}
+
+/// Whether a stored `chome_range` such as "1～19丁目" or "1,3丁目" covers chome `n`.
+fn chome_range_contains(range: &str, n: u32) -> bool {
+    range
+        .replace("丁目", "")
+        .split(',')
+        .any(|part| match part.split_once(['～', '~', '-']) {
+            Some((from, to)) => match (from.parse::<u32>(), to.parse::<u32>()) {
+                (Ok(from), Ok(to)) => (from..=to).contains(&n),
+                _ => false,
+            },
+            None => part.parse::<u32>() == Ok(n),
+        })
+}
+
+fn match_town<'a>(rest: &str, rows: &'a [PostalCode]) -> (Vec<&'a PostalCode>, usize) {
+    let mut best: Vec<&PostalCode> = Vec::new();
+    let mut best_len = 0;
+    for row in rows.iter().filter(|r| !r.town.is_empty()) {
+        if !rest.starts_with(&row.town) {
+            continue;
+        }
+        let len = row.town.len();
+        if len > best_len {
+            best = vec![row];
+            best_len = len;
+        } else if len == best_len {
+            best.push(row);
+        }
+    }
+    (best, best_len)
+}
+
+/// Picks the postal code for the town part of an address within the matched municipality.
+pub fn resolve_town(head: &HeadMatch, rows: &[PostalCode]) -> ResolveResponse {
+    let mut rest = head.rest.as_str();
+    let (mut towns, mut len) = match_town(rest, rows);
+    if towns.is_empty() {
+        // "大字" / "字" are often written in addresses but omitted from KEN_ALL.
+        if let Some(stripped) = rest
+            .strip_prefix("大字")
+            .or_else(|| rest.strip_prefix('字'))
+        {
+            (towns, len) = match_town(stripped, rows);
+            if !towns.is_empty() {
+                rest = stripped;
+            }
+        }
+    }
+    let town_matched = !towns.is_empty();
+    let remainder = &rest[len..];
+
+    if !town_matched {
+        // Fall back to the municipality-wide code ("以下に掲載がない場合").
+        towns = rows.iter().filter(|r| r.town.is_empty()).collect();
+    }
+
+    let mut disambiguated = towns.len() == 1;
+    if towns.len() > 1 {
+        if let Some(n) = leading_number(remainder) {
+            let narrowed: Vec<&PostalCode> = towns
+                .iter()
+                .copied()
+                .filter(|r| chome_range_contains(&r.chome_range, n))
+                .collect();
+            if !narrowed.is_empty() {
+                disambiguated = narrowed.len() == 1;
+                towns = narrowed;
+            }
+        }
+    }
+
+    let mut confidence = if head.prefecture_given { 0.2 } else { 0.1 };
+    confidence += if head.city_ambiguous { 0.15 } else { 0.3 };
+    confidence += match (town_matched, towns.is_empty()) {
+        (true, _) => 0.4,
+        (false, false) => 0.1,
+        (false, true) => 0.0,
+    };
+    if disambiguated {
+        confidence += 0.1;
+    }
+
+    let mut matches = towns.into_iter().cloned();
+    let best_match = matches.next();
+    ResolveResponse {
+        prefecture: head.city.prefecture.clone(),
+        city: head.city.city.clone(),
+        town: best_match
+            .as_ref()
+            .filter(|_| town_matched)
+            .map(|r| r.town.clone())
+            .unwrap_or_default(),
+        remainder: remainder.to_string(),
+        confidence: (confidence * 100.0_f64).round() / 100.0,
+        best_match,
+        candidates: matches.collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{chome_range_contains, leading_number, match_head, resolve_town, CityEntry};
+    use common::models::PostalCode;
+
+    fn city(prefecture_id: i16, prefecture: &str, city_id: &str, city: &str) -> CityEntry {
+        CityEntry {
+            prefecture_id,
+            prefecture: prefecture.to_string(),
+            city_id: city_id.to_string(),
+            city: city.to_string(),
+        }
+    }
+
+    fn cities() -> Vec<CityEntry> {
+        vec![
+            city(13, "東京都", "13101", "千代田区"),
+            city(13, "東京都", "13206", "府中市"),
+            city(34, "広島県", "34208", "府中市"),
+            city(1, "北海道", "01408", "余市郡余市町"),
+            city(1, "北海道", "01101", "札幌市中央区"),
+        ]
+    }
+
+    #[test]
+    fn match_head_splits_prefecture_and_city() {
+        let head = match_head("東京都千代田区千代田1-1", &cities()).unwrap();
+
+        assert_eq!(head.city.city_id, "13101");
+        assert!(head.prefecture_given);
+        assert!(!head.city_ambiguous);
+        assert_eq!(head.rest, "千代田1-1");
+    }
+
+    #[test]
+    fn match_head_accepts_city_without_county_or_prefecture() {
+        let head = match_head("北海道余市町黒川町", &cities()).unwrap();
+        assert_eq!(head.city.city_id, "01408");
+        assert_eq!(head.rest, "黒川町");
+
+        let head = match_head("府中市宮西町", &cities()).unwrap();
+        assert!(!head.prefecture_given);
+        assert!(head.city_ambiguous);
+
+        assert!(match_head("東京都府中市宮西町", &cities())
+            .is_some_and(|h| !h.city_ambiguous && h.city.city_id == "13206"));
+        assert_eq!(match_head("東京都新宿区", &cities()), None);
+    }
+
+    #[test]
+    fn resolve_town_prefers_longest_town_and_chome() {
+        let head = match_head("北海道札幌市中央区大通西20丁目3", &cities()).unwrap();
+        let rows = vec![
+            PostalCode::builder("0600000").build(),
+            PostalCode::builder("0600042")
+                .town("大通西")
+                .chome_range("1～19丁目")
+                .build(),
+            PostalCode::builder("0640820")
+                .town("大通西")
+                .chome_range("20～28丁目")
+                .build(),
+        ];
+        let resolved = resolve_town(&head, &rows);
+
+        assert_eq!(resolved.town, "大通西");
+        assert_eq!(resolved.remainder, "20丁目3");
+        assert_eq!(resolved.best_match.unwrap().zip_code, "0640820");
+        assert!(resolved.candidates.is_empty());
+        assert_eq!(resolved.confidence, 1.0);
+    }
+
+    #[test]
+    fn resolve_town_falls_back_to_city_code() {
+        let head = match_head("東京都千代田区どこか", &cities()).unwrap();
+        let rows = vec![
+            PostalCode::builder("1000000").build(),
+            PostalCode::builder("1000001").town("千代田").build(),
+        ];
+        let resolved = resolve_town(&head, &rows);
+
+        assert_eq!(resolved.town, "");
+        assert_eq!(resolved.best_match.unwrap().zip_code, "1000000");
+        assert_eq!(resolved.confidence, 0.7);
+    }
+
+    #[test]
+    fn leading_number_reads_digits_and_kanji() {
+        assert_eq!(leading_number("12-3"), Some(12));
+        assert_eq!(leading_number("三丁目"), Some(3));
+        assert_eq!(leading_number("二十一丁目"), Some(21));
+        assert_eq!(leading_number("丁目"), None);
+    }
+
+    #[test]
+    fn chome_range_contains_handles_lists_and_ranges() {
+        assert!(chome_range_contains("1～19丁目", 19));
+        assert!(!chome_range_contains("1～19丁目", 20));
+        assert!(chome_range_contains("1,3丁目", 3));
+        assert!(chome_range_contains("1～3丁目,5丁目", 5));
+        assert!(!chome_range_contains("", 1));
+    }
+}
@@ -0,0 +1,304 @@
+use crate::resolve::{CityEntry, HeadMatch};
+use common::models::PostalCode;
+use common::normalize::{is_hyphen, search_key};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// Shorter postal code prefixes match too many codes to be useful as suggestions.
+pub const MIN_ZIP_PREFIX: usize = 3;
+/// Town names are looked up in the database from this many characters on.
+pub const MIN_TOWN_PREFIX: usize = 2;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SuggestionKind {
+    /// A complete postal code.
+    PostalCode,
+    /// A municipality; continue typing the town name.
+    City,
+    /// A town within a municipality.
+    Town,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, ToSchema)]
+pub struct Suggestion {
+    pub kind: SuggestionKind,
+    /// Text to show in the dropdown, e.g. "100-0001 東京都千代田区千代田".
+    pub label: String,
+    /// Set when the suggestion stands for a single postal code.
+    pub zip_code: Option<String>,
+    pub prefecture_id: i16,
+    pub city_id: String,
+}
+
+/// Returns the digits of `input` when it reads as the start of a postal code
+/// ("100", "〒100-00", "１００"). `input` is expected to be NFKC-normalized.
+pub fn zip_prefix(input: &str) -> Option<String> {
+    let body = input.strip_prefix('〒').unwrap_or(input);
+    let digits: String = body.chars().filter(|c| !is_hyphen(*c)).collect();
+    (!digits.is_empty() && digits.len() <= 7 && digits.chars().all(|c| c.is_ascii_digit()))
+        .then_some(digits)
+}
+
+/// Inclusive bounds of every seven-digit code starting with `prefix`; a range instead of
+/// `LIKE 'prefix%'` so the primary key index serves it on every backend.
+pub fn zip_range(prefix: &str) -> (String, String) {
+    (format!("{prefix:0<7}"), format!("{prefix:9<7}"))
+}
+
+fn format_zip_code(zip_code: &str) -> String {
+    if zip_code.len() == 7 {
+        format!("{}-{}", &zip_code[..3], &zip_code[3..])
+    } else {
+        zip_code.to_string()
+    }
+}
+
+/// One suggestion per postal code, grouped by municipality in order of first appearance.
+/// `rows` hold one row per code, ordered by `zip_code`.
+pub fn postal_code_suggestions(rows: Vec<PostalCode>) -> Vec<Suggestion> {
+    let mut groups: Vec<(String, Vec<Suggestion>)> = Vec::new();
+    for row in rows {
+        let suggestion = Suggestion {
+            kind: SuggestionKind::PostalCode,
+            label: format!(
+                "{} {}{}{}",
+                format_zip_code(&row.zip_code),
+                row.prefecture,
+                row.city,
+                row.town
+            ),
+            zip_code: Some(row.zip_code),
+            prefecture_id: row.prefecture_id,
+            city_id: row.city_id.clone(),
+        };
+        match groups
+            .iter_mut()
+            .find(|(city_id, _)| *city_id == row.city_id)
+        {
+            Some((_, group)) => group.push(suggestion),
+            None => groups.push((row.city_id, vec![suggestion])),
+        }
+    }
+    groups.into_iter().flat_map(|(_, group)| group).collect()
+}
+
+fn city_suggestion(entry: &CityEntry) -> Suggestion {
+    Suggestion {
+        kind: SuggestionKind::City,
+        label: format!("{}{}", entry.prefecture, entry.city),
+        zip_code: None,
+        prefecture_id: entry.prefecture_id,
+        city_id: entry.city_id.clone(),
+    }
+}
+
+/// Municipalities whose name, with or without the prefecture, starts with `key`
+/// (a `search_key`).
+pub fn city_suggestions(key: &str, cities: &[CityEntry], limit: usize) -> Vec<Suggestion> {
+    cities
+        .iter()
+        .filter(|entry| {
+            search_key(&entry.city).starts_with(key)
+                || search_key(&format!("{}{}", entry.prefecture, entry.city)).starts_with(key)
+        })
+        .take(limit)
+        .map(city_suggestion)
+        .collect()
+}
+
+/// One suggestion per town, in the order of `rows`. A town spread over several postal
+/// codes is suggested once, without a `zip_code`.
+pub fn town_suggestions<'a>(
+    rows: impl IntoIterator<Item = &'a PostalCode>,
+    limit: usize,
+) -> Vec<Suggestion> {
+    let mut suggestions: Vec<Suggestion> = Vec::new();
+    let mut towns: Vec<(&str, &str)> = Vec::new();
+    for row in rows.into_iter().filter(|r| !r.town.is_empty()) {
+        let town = (row.city_id.as_str(), row.town.as_str());
+        if let Some(index) = towns.iter().position(|t| *t == town) {
+            suggestions[index].zip_code = None;
+            continue;
+        }
+        if suggestions.len() >= limit {
+            continue;
+        }
+        towns.push(town);
+        suggestions.push(Suggestion {
+            kind: SuggestionKind::Town,
+            label: format!("{}{}{}", row.prefecture, row.city, row.town),
+            zip_code: Some(row.zip_code.clone()),
+            prefecture_id: row.prefecture_id,
+            city_id: row.city_id.clone(),
+        });
+    }
+    suggestions
+}
+
+/// Towns of the municipality at the head of the input whose name starts with the rest of it.
+pub fn city_town_suggestions(
+    head: &HeadMatch,
+    rows: &[PostalCode],
+    limit: usize,
+) -> Vec<Suggestion> {
+    let rest = search_key(&head.rest);
+    town_suggestions(
+        rows.iter()
+            .filter(|r| search_key(&r.town).starts_with(&rest)),
+        limit,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        city_suggestions, city_town_suggestions, postal_code_suggestions, town_suggestions,
+        zip_prefix, zip_range, SuggestionKind,
+    };
+    use crate::resolve::{CityEntry, HeadMatch};
+    use common::models::PostalCode;
+
+    fn city(prefecture_id: i16, prefecture: &str, city_id: &str, city: &str) -> CityEntry {
+        CityEntry {
+            prefecture_id,
+            prefecture: prefecture.to_string(),
+            city_id: city_id.to_string(),
+            city: city.to_string(),
+        }
+    }
+
+    #[test]
+    fn zip_prefix_accepts_partial_codes_only() {
+        assert_eq!(zip_prefix("100").as_deref(), Some("100"));
+        assert_eq!(zip_prefix("〒100-00").as_deref(), Some("10000"));
+        assert_eq!(zip_prefix("10000011"), None);
+        assert_eq!(zip_prefix("千代田"), None);
+        assert_eq!(zip_prefix("-"), None);
+    }
+
+    #[test]
+    fn zip_range_covers_every_code_with_the_prefix() {
+        assert_eq!(
+            zip_range("100"),
+            ("1000000".to_string(), "1009999".to_string())
+        );
+        assert_eq!(
+            zip_range("1000001"),
+            ("1000001".to_string(), "1000001".to_string())
+        );
+    }
+
+    #[test]
+    fn postal_code_suggestions_are_grouped_by_city() {
+        let suggestions = postal_code_suggestions(vec![
+            PostalCode::builder("1000001")
+                .prefecture(13, "東京都")
+                .city("13101", "千代田区")
+                .town("千代田")
+                .build(),
+            PostalCode::builder("1000002")
+                .prefecture(13, "東京都")
+                .city("13102", "中央区")
+                .town("皇居外苑")
+                .build(),
+            PostalCode::builder("1000003")
+                .prefecture(13, "東京都")
+                .city("13101", "千代田区")
+                .town("一ツ橋")
+                .build(),
+        ]);
+        let labels: Vec<&str> = suggestions.iter().map(|s| s.label.as_str()).collect();
+        assert_eq!(
+            labels,
+            vec![
+                "100-0001 東京都千代田区千代田",
+                "100-0003 東京都千代田区一ツ橋",
+                "100-0002 東京都中央区皇居外苑",
+            ]
+        );
+        assert!(suggestions
+            .iter()
+            .all(|s| s.kind == SuggestionKind::PostalCode));
+    }
+
+    #[test]
+    fn city_suggestions_match_with_or_without_prefecture() {
+        let cities = vec![
+            city(13, "東京都", "13101", "千代田区"),
+            city(13, "東京都", "13102", "中央区"),
+            city(1, "北海道", "01108", "札幌市厚別区"),
+        ];
+        let by_city = city_suggestions("千代", &cities, 10);
+        assert_eq!(by_city.len(), 1);
+        assert_eq!(by_city[0].label, "東京都千代田区");
+        assert_eq!(by_city[0].zip_code, None);
+
+        assert_eq!(city_suggestions("東京都", &cities, 10).len(), 2);
+        assert_eq!(city_suggestions("東京都", &cities, 1).len(), 1);
+    }
+
+    #[test]
+    fn town_suggestions_drop_zip_code_for_towns_with_several_codes() {
+        let head = HeadMatch {
+            city: city(13, "東京都", "13104", "新宿区"),
+            prefecture_given: true,
+            city_ambiguous: false,
+            rest: "西".to_string(),
+        };
+        let rows = vec![
+            PostalCode::builder("1600023")
+                .prefecture(13, "東京都")
+                .city("13104", "新宿区")
+                .town("西新宿")
+                .build(),
+            PostalCode::builder("1631390")
+                .prefecture(13, "東京都")
+                .city("13104", "新宿区")
+                .town("西新宿")
+                .build(),
+            PostalCode::builder("1690051")
+                .prefecture(13, "東京都")
+                .city("13104", "新宿区")
+                .town("西早稲田")
+                .build(),
+            PostalCode::builder("1600022")
+                .prefecture(13, "東京都")
+                .city("13104", "新宿区")
+                .town("新宿")
+                .build(),
+        ];
+        let suggestions = city_town_suggestions(&head, &rows, 10);
+        let summary: Vec<(&str, Option<&str>)> = suggestions
+            .iter()
+            .map(|s| (s.label.as_str(), s.zip_code.as_deref()))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("東京都新宿区西新宿", None),
+                ("東京都新宿区西早稲田", Some("1690051")),
+            ]
+        );
+    }
+
+    #[test]
+    fn town_suggestions_keep_same_named_towns_of_different_cities() {
+        let rows = vec![
+            PostalCode::builder("1040061")
+                .prefecture(13, "東京都")
+                .city("13102", "中央区")
+                .town("銀座")
+                .build(),
+            PostalCode::builder("3400000")
+                .prefecture(13, "東京都")
+                .city("11222", "越谷市")
+                .town("銀座")
+                .build(),
+        ];
+        let suggestions = town_suggestions(&rows, 10);
+        assert_eq!(suggestions.len(), 2);
+        assert!(suggestions.iter().all(|s| s.zip_code.is_some()));
+        assert_eq!(town_suggestions(&rows, 1).len(), 1);
+    }
+}
@@ -0,0 +1,58 @@
+//! `data_update_audits` conventions shared by the crawler, which writes the rows, and the
+//! API, which reads them to answer `as_of` and history requests.
+
+/// `source_url` of a rollback audit entry, followed by the restored data version.
+pub const ROLLBACK_SOURCE_PREFIX: &str = "rollback_cli:";
+
+/// Audit status of a full reload that found the feed identical to the data in place
+/// and wrote nothing, snapshot included.
+pub const STATUS_UNCHANGED: &str = "unchanged";
+
+/// Versions are generated by the crawler ("v20250101120000000", rollbacks "rv...");
+/// anything else is rejected before it reaches SQL.
+pub fn is_data_version(s: &str) -> bool {
+    (2..=32).contains(&s.len())
+        && s.starts_with(['v', 'r'])
+        && s.chars().all(|c| c.is_ascii_alphanumeric())
+}
+
+/// The snapshot holding the data an audit entry left live: successful runs snapshot
+/// their own version, rollbacks restore an earlier one. Failed and unchanged runs
+/// changed nothing, so the previous entry tells.
+pub fn live_snapshot(data_version: &str, status: &str, source_url: &str) -> Option<String> {
+    match status {
+        "success" => Some(data_version.to_string()),
+        "rollback" => source_url
+            .strip_prefix(ROLLBACK_SOURCE_PREFIX)
+            .filter(|version| is_data_version(version))
+            .map(str::to_string),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_data_version, live_snapshot, STATUS_UNCHANGED};
+
+    #[test]
+    fn is_data_version_rejects_sql() {
+        assert!(is_data_version("rv20250101120000000"));
+        assert!(!is_data_version("v1' OR '1'='1"));
+        assert!(!is_data_version("20250101"));
+    }
+
+    #[test]
+    fn live_snapshot_follows_rollbacks() {
+        assert_eq!(
+            live_snapshot("v2", "success", "https://example.com").as_deref(),
+            Some("v2")
+        );
+        assert_eq!(
+            live_snapshot("rv3", "rollback", "rollback_cli:v1").as_deref(),
+            Some("v1")
+        );
+        assert_eq!(live_snapshot("rv5", "rollback", "rollback_cli:v1'--"), None);
+        assert_eq!(live_snapshot("v4", "failed", ""), None);
+        assert_eq!(live_snapshot("v6", STATUS_UNCHANGED, ""), None);
+    }
+}
@@ -1,4 +1,8 @@
 pub use serde::{self, Deserialize};
 pub use serde_json;
+pub mod audit;
 pub mod db;
 pub mod models;
+pub mod normalize;
+pub mod romaji;
+pub mod zip_code;
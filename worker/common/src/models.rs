@@ -1,7 +1,136 @@
 use serde::{Deserialize, Serialize};
 use utoipa::ToSchema;
 
-#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq, Hash, ToSchema)]
+/// Which Japan Post feed a postal code row came from.
+#[derive(Debug, Default, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Hash, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum PostalCodeKind {
+    /// Residential area code from KEN_ALL.
+    #[default]
+    Residential,
+    /// Dedicated code of a large business office from JIGYOSYO.
+    Business,
+    /// P.O. box code from JIGYOSYO.
+    PoBox,
+}
+
+impl PostalCodeKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Residential => "residential",
+            Self::Business => "business",
+            Self::PoBox => "po_box",
+        }
+    }
+
+    /// Maps the stored `kind` column back to the enum, treating unknown values as residential.
+    pub fn from_db_value(value: &str) -> Self {
+        match value {
+            "business" => Self::Business,
+            "po_box" => Self::PoBox,
+            _ => Self::Residential,
+        }
+    }
+}
+
+/// KEN_ALL update flag (index 13).
+#[derive(Debug, Default, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Hash, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum UpdateStatus {
+    #[default]
+    Unchanged,
+    Changed,
+    /// Only present in the monthly DEL files.
+    Abolished,
+}
+
+impl UpdateStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Unchanged => "unchanged",
+            Self::Changed => "changed",
+            Self::Abolished => "abolished",
+        }
+    }
+
+    pub fn from_db_value(value: &str) -> Self {
+        match value {
+            "changed" => Self::Changed,
+            "abolished" => Self::Abolished,
+            _ => Self::Unchanged,
+        }
+    }
+
+    /// Maps the numeric KEN_ALL code; unknown codes are treated as unchanged.
+    pub fn from_ken_all(code: &str) -> Self {
+        match code {
+            "1" => Self::Changed,
+            "2" => Self::Abolished,
+            _ => Self::Unchanged,
+        }
+    }
+}
+
+/// KEN_ALL reason for the last change (index 14).
+#[derive(Debug, Default, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Hash, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum UpdateReason {
+    #[default]
+    None,
+    /// 市政・区政・町政・分区・政令指定都市施行
+    MunicipalReorganization,
+    /// 住居表示の実施
+    AddressDisplay,
+    /// 区画整理
+    LandReadjustment,
+    /// 郵便区調整等
+    PostalDistrictAdjustment,
+    /// 訂正
+    Correction,
+    /// 廃止
+    Abolished,
+}
+
+impl UpdateReason {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::None => "none",
+            Self::MunicipalReorganization => "municipal_reorganization",
+            Self::AddressDisplay => "address_display",
+            Self::LandReadjustment => "land_readjustment",
+            Self::PostalDistrictAdjustment => "postal_district_adjustment",
+            Self::Correction => "correction",
+            Self::Abolished => "abolished",
+        }
+    }
+
+    pub fn from_db_value(value: &str) -> Self {
+        match value {
+            "municipal_reorganization" => Self::MunicipalReorganization,
+            "address_display" => Self::AddressDisplay,
+            "land_readjustment" => Self::LandReadjustment,
+            "postal_district_adjustment" => Self::PostalDistrictAdjustment,
+            "correction" => Self::Correction,
+            "abolished" => Self::Abolished,
+            _ => Self::None,
+        }
+    }
+
+    /// Maps the numeric KEN_ALL code; unknown codes are treated as no change.
+    pub fn from_ken_all(code: &str) -> Self {
+        match code {
+            "1" => Self::MunicipalReorganization,
+            "2" => Self::AddressDisplay,
+            "3" => Self::LandReadjustment,
+            "4" => Self::PostalDistrictAdjustment,
+            "5" => Self::Correction,
+            "6" => Self::Abolished,
+            _ => Self::None,
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize, Serialize, Clone, PartialEq, Eq, Hash, ToSchema)]
 pub struct PostalCode {
     pub zip_code: String,
     pub prefecture_id: i16,
@@ -9,4 +138,187 @@ pub struct PostalCode {
     pub prefecture: String,
     pub city: String,
     pub town: String,
+    /// Katakana reading of `prefecture` (KEN_ALL index 3).
+    pub prefecture_kana: String,
+    /// Katakana reading of `city` (KEN_ALL index 4).
+    pub city_kana: String,
+    /// Katakana reading of `town` (KEN_ALL index 5).
+    pub town_kana: String,
+    /// Text removed from `town` inside the KEN_ALL parentheses, e.g. "1丁目" or "1階".
+    pub town_detail: String,
+    /// Chome part of `town_detail` when it is only a chome list, e.g. "1～19丁目".
+    pub chome_range: String,
+    /// Floor of a high-rise building code, e.g. "1階" or "地階・階層不明".
+    pub floor: String,
+    pub kind: PostalCodeKind,
+    /// Business office name; empty for residential rows (JIGYOSYO index 2).
+    pub business_name: String,
+    /// Katakana reading of `business_name` (JIGYOSYO index 1).
+    pub business_name_kana: String,
+    /// Block / building part of the business address (JIGYOSYO index 6).
+    pub street_address: String,
+    /// The town is split across several postal codes (KEN_ALL index 9).
+    pub town_has_multiple_zip_codes: bool,
+    /// Banchi are numbered per koaza within the town (KEN_ALL index 10).
+    pub numbered_per_koaza: bool,
+    /// The town has chome (KEN_ALL index 11).
+    pub has_chome: bool,
+    /// This postal code covers several towns (KEN_ALL index 12).
+    pub covers_multiple_towns: bool,
+    pub update_status: UpdateStatus,
+    pub update_reason: UpdateReason,
+    /// Hepburn romanization of `prefecture`, e.g. "Tokyo-to".
+    pub prefecture_roma: String,
+    /// Hepburn romanization of `city`, e.g. "Shinjuku-ku"; empty for JIGYOSYO rows.
+    pub city_roma: String,
+    /// Hepburn romanization of `town`, e.g. "Nishishinjuku".
+    pub town_roma: String,
+}
+
+/// Builds `PostalCode` rows in tests; fields not set keep their `Default`.
+#[cfg(any(test, feature = "test-util"))]
+#[derive(Debug, Default)]
+pub struct PostalCodeBuilder(PostalCode);
+
+#[cfg(any(test, feature = "test-util"))]
+impl PostalCode {
+    pub fn builder(zip_code: &str) -> PostalCodeBuilder {
+        PostalCodeBuilder(PostalCode {
+            zip_code: zip_code.to_string(),
+            ..Default::default()
+        })
+    }
+}
+
+#[cfg(any(test, feature = "test-util"))]
+impl PostalCodeBuilder {
+    pub fn prefecture(mut self, prefecture_id: i16, prefecture: &str) -> Self {
+        self.0.prefecture_id = prefecture_id;
+        self.0.prefecture = prefecture.to_string();
+        self
+    }
+
+    pub fn city(mut self, city_id: &str, city: &str) -> Self {
+        self.0.city_id = city_id.to_string();
+        self.0.city = city.to_string();
+        self
+    }
+
+    pub fn town(mut self, town: &str) -> Self {
+        self.0.town = town.to_string();
+        self
+    }
+
+    pub fn town_kana(mut self, town_kana: &str) -> Self {
+        self.0.town_kana = town_kana.to_string();
+        self
+    }
+
+    pub fn chome_range(mut self, chome_range: &str) -> Self {
+        self.0.chome_range = chome_range.to_string();
+        self
+    }
+
+    pub fn update_reason(mut self, update_reason: UpdateReason) -> Self {
+        self.0.update_reason = update_reason;
+        self
+    }
+
+    pub fn build(self) -> PostalCode {
+        self.0
+    }
+}
+
+/// Where a `municipality_changes` entry came from.
+#[derive(Debug, Default, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Hash, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum MunicipalityChangeSource {
+    /// Postal codes moved from the old to the new code between two data versions.
+    #[default]
+    Detected,
+    /// Loaded from the seed file of historical mergers.
+    Seed,
+}
+
+impl MunicipalityChangeSource {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Detected => "detected",
+            Self::Seed => "seed",
+        }
+    }
+
+    pub fn from_db_value(value: &str) -> Self {
+        match value {
+            "seed" => Self::Seed,
+            _ => Self::Detected,
+        }
+    }
+}
+
+/// A municipality code (`city_id`) replaced by another one through a merger (合併・編入),
+/// a split or a change of status such as 市制施行. A code may have several successors.
+#[derive(Debug, Default, Deserialize, Serialize, Clone, PartialEq, Eq, ToSchema)]
+pub struct MunicipalityChange {
+    pub old_city_id: String,
+    pub new_city_id: String,
+    pub old_city: String,
+    pub new_city: String,
+    /// YYYY-MM-DD; only known for seeded entries.
+    pub effective_date: Option<String>,
+    /// Data version the change was detected in; `None` for seeded entries.
+    pub data_version: Option<String>,
+    pub source: MunicipalityChangeSource,
+    /// Postal codes that moved from the old to the new code; 0 for seeded entries.
+    pub shared_zip_codes: i64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MunicipalityChangeSource, PostalCodeKind, UpdateReason, UpdateStatus};
+
+    #[test]
+    fn postal_code_kind_roundtrips_db_value() {
+        for kind in [
+            PostalCodeKind::Residential,
+            PostalCodeKind::Business,
+            PostalCodeKind::PoBox,
+        ] {
+            assert_eq!(PostalCodeKind::from_db_value(kind.as_str()), kind);
+        }
+        assert_eq!(
+            PostalCodeKind::from_db_value("unknown"),
+            PostalCodeKind::Residential
+        );
+    }
+
+    #[test]
+    fn update_flags_roundtrip_db_value() {
+        for status in [
+            UpdateStatus::Unchanged,
+            UpdateStatus::Changed,
+            UpdateStatus::Abolished,
+        ] {
+            assert_eq!(UpdateStatus::from_db_value(status.as_str()), status);
+        }
+        for code in ["0", "1", "2", "3", "4", "5", "6"] {
+            let reason = UpdateReason::from_ken_all(code);
+            assert_eq!(UpdateReason::from_db_value(reason.as_str()), reason);
+        }
+        assert_eq!(UpdateReason::from_ken_all("6"), UpdateReason::Abolished);
+        assert_eq!(UpdateStatus::from_ken_all("9"), UpdateStatus::Unchanged);
+    }
+
+    #[test]
+    fn municipality_change_source_roundtrips_db_value() {
+        for source in [
+            MunicipalityChangeSource::Detected,
+            MunicipalityChangeSource::Seed,
+        ] {
+            assert_eq!(
+                MunicipalityChangeSource::from_db_value(source.as_str()),
+                source
+            );
+        }
+    }
 }
@@ -0,0 +1,162 @@
+//! Text normalization shared by the crawler and the API.
+//!
+//! Names are stored twice: in their display form (`fold_width`, which keeps the official
+//! spelling) and as a search key (`search_key`), which folds the spellings people use
+//! interchangeably. Queries go through `search_key` as well, so both sides compare equal.
+
+use unicode_normalization::UnicodeNormalization;
+
+/// Full-width characters KEN_ALL uses where ASCII reads better.
+const WIDTH_REPLACEMENTS: &[(char, char)] = &[
+    ('（', '('),
+    ('）', ')'),
+    ('ー', '-'),
+    ('、', ','),
+    ('０', '0'),
+    ('１', '1'),
+    ('２', '2'),
+    ('３', '3'),
+    ('４', '4'),
+    ('５', '5'),
+    ('６', '6'),
+    ('７', '7'),
+    ('８', '8'),
+    ('９', '9'),
+];
+
+/// Old and variant kanji (itaiji) folded to the common form. Compatibility ideographs
+/// such as U+FA10 are already handled by NFKC; U+FA11 is not, as it is a unified ideograph.
+const ITAIJI: &[(char, char)] = &[
+    ('髙', '高'),
+    ('\u{FA11}', '崎'),
+    ('嵜', '崎'),
+    ('邊', '辺'),
+    ('邉', '辺'),
+    ('澤', '沢'),
+    ('龍', '竜'),
+    ('濱', '浜'),
+    ('濵', '浜'),
+    ('嶋', '島'),
+    ('嶌', '島'),
+    ('國', '国'),
+    ('廣', '広'),
+    ('櫻', '桜'),
+    ('冨', '富'),
+    ('槇', '槙'),
+    ('德', '徳'),
+    ('籔', '薮'),
+    ('藪', '薮'),
+];
+
+/// Hyphen, dash and long-vowel look-alikes that survive NFKC ('－' already folds to '-').
+pub fn is_hyphen(c: char) -> bool {
+    matches!(
+        c,
+        '-' | 'ー' | '‐' | '‑' | '‒' | '–' | '—' | '―' | '−' | 'ｰ'
+    )
+}
+
+fn is_kanji(c: Option<&char>) -> bool {
+    c.is_some_and(|c| matches!(c, '\u{4E00}'..='\u{9FFF}' | '\u{3400}'..='\u{4DBF}' | '々'))
+}
+
+/// Folds KEN_ALL's full-width parentheses, digits, '、' and 'ー' to ASCII.
+/// This is the form names are stored and displayed in.
+pub fn fold_width(input: &str) -> String {
+    input
+        .chars()
+        .map(|c| {
+            WIDTH_REPLACEMENTS
+                .iter()
+                .find(|(from, _)| *from == c)
+                .map_or(c, |(_, to)| *to)
+        })
+        .collect()
+}
+
+/// NFKC with whitespace removed: half-width kana, full-width ASCII and compatibility
+/// ideographs all reach one form.
+pub fn normalize_input(input: &str) -> String {
+    input.nfkc().filter(|c| !c.is_whitespace()).collect()
+}
+
+/// Search key for a name or keyword: `normalize_input`, then hyphens and long vowels to '-',
+/// itaiji to the common kanji, 'ヶ' / 'ヵ' to 'ケ' / 'カ', and 'が' / 'ガ' / 'の' / '之'
+/// between two kanji to 'ケ' / 'ノ' ("霞が関", "霞ヶ関" and "霞ケ関" share one key).
+pub fn search_key(input: &str) -> String {
+    let chars: Vec<char> = normalize_input(input)
+        .chars()
+        .map(|c| {
+            if is_hyphen(c) {
+                return '-';
+            }
+            ITAIJI
+                .iter()
+                .find(|(from, _)| *from == c)
+                .map_or(c, |(_, to)| *to)
+        })
+        .collect();
+    chars
+        .iter()
+        .enumerate()
+        .map(|(i, &c)| {
+            let between_kanji = i > 0 && is_kanji(chars.get(i - 1)) && is_kanji(chars.get(i + 1));
+            match c {
+                'ヶ' | 'ゖ' => 'ケ',
+                'ヵ' | 'ゕ' => 'カ',
+                'が' | 'ガ' if between_kanji => 'ケ',
+                'の' | '之' if between_kanji => 'ノ',
+                _ => c,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{fold_width, normalize_input, search_key};
+
+    #[test]
+    fn fold_width_keeps_kanji_and_folds_punctuation() {
+        assert_eq!(fold_width("銀座（１丁目）"), "銀座(1丁目)");
+        assert_eq!(fold_width("髙田、センター"), "髙田,センタ-");
+    }
+
+    #[test]
+    fn normalize_input_applies_nfkc_and_drops_spaces() {
+        assert_eq!(normalize_input("  ｼﾝ ｼﾞｭｸ  "), "シンジュク");
+        assert_eq!(normalize_input("\u{FA10}"), "塚");
+    }
+
+    #[test]
+    fn search_key_folds_itaiji() {
+        assert_eq!(search_key("髙田馬場"), search_key("高田馬場"));
+        assert_eq!(search_key("宮\u{FA11}"), "宮崎");
+        assert_eq!(search_key("渡邊"), "渡辺");
+        assert_eq!(search_key("渡邉"), "渡辺");
+    }
+
+    #[test]
+    fn search_key_folds_hyphens_and_long_vowels() {
+        for input in [
+            "センター",
+            "センタｰ",
+            "センタ−",
+            "センタ‐",
+            "センタ—",
+            "センタ-",
+        ] {
+            assert_eq!(search_key(input), "センタ-", "{input}");
+        }
+    }
+
+    #[test]
+    fn search_key_folds_ke_family_between_kanji() {
+        assert_eq!(search_key("霞が関"), "霞ケ関");
+        assert_eq!(search_key("霞ヶ関"), "霞ケ関");
+        assert_eq!(search_key("霞ケ関"), "霞ケ関");
+        assert_eq!(search_key("三之町"), search_key("三の町"));
+        assert_eq!(search_key("つくばがおか"), "つくばがおか");
+        assert_eq!(search_key("ガーデン"), "ガ-デン");
+    }
+}
@@ -0,0 +1,346 @@
+//! Hepburn romanization of the KEN_ALL katakana readings.
+//!
+//! Long vowels are written without macrons ("トウキョウ" -> "tokyo") and `ン` is always "n",
+//! following the spelling Japan Post uses in KEN_ALL_ROME. Place names get their
+//! administrative suffix hyphenated ("Shinjuku-ku"), which `fold_romaji` strips again so
+//! "Chiyoda-ku", "chiyoda ku" and "chiyodaku" all compare equal.
+
+/// Designated cities whose wards are listed as "<city><ward>" with a single reading.
+/// The city reading cannot be told apart from the ward reading without it.
+const DESIGNATED_CITIES: &[(&str, &str)] = &[
+    ("札幌市", "サッポロシ"),
+    ("仙台市", "センダイシ"),
+    ("さいたま市", "サイタマシ"),
+    ("千葉市", "チバシ"),
+    ("横浜市", "ヨコハマシ"),
+    ("川崎市", "カワサキシ"),
+    ("相模原市", "サガミハラシ"),
+    ("新潟市", "ニイガタシ"),
+    ("静岡市", "シズオカシ"),
+    ("浜松市", "ハママツシ"),
+    ("名古屋市", "ナゴヤシ"),
+    ("京都市", "キョウトシ"),
+    ("大阪市", "オオサカシ"),
+    ("堺市", "サカイシ"),
+    ("神戸市", "コウベシ"),
+    ("岡山市", "オカヤマシ"),
+    ("広島市", "ヒロシマシ"),
+    ("北九州市", "キタキュウシュウシ"),
+    ("福岡市", "フクオカシ"),
+    ("熊本市", "クマモトシ"),
+];
+
+/// (last character of the name, ending of the reading, romanized suffix)
+const SUFFIXES: &[(char, &str, &str)] = &[
+    ('都', "ト", "to"),
+    ('府', "フ", "fu"),
+    ('県', "ケン", "ken"),
+    ('市', "シ", "shi"),
+    ('区', "ク", "ku"),
+    ('町', "チョウ", "cho"),
+    ('町', "マチ", "machi"),
+    ('村', "ソン", "son"),
+    ('村', "ムラ", "mura"),
+    ('郡', "グン", "gun"),
+];
+
+fn syllable(c: char) -> Option<&'static str> {
+    let romaji = match c {
+        'ア' | 'ァ' => "a",
+        'イ' | 'ィ' | 'ヰ' => "i",
+        'ウ' | 'ゥ' => "u",
+        'エ' | 'ェ' | 'ヱ' => "e",
+        'オ' | 'ォ' | 'ヲ' => "o",
+        'カ' | 'ヵ' => "ka",
+        'キ' => "ki",
+        'ク' => "ku",
+        'ケ' | 'ヶ' => "ke",
+        'コ' => "ko",
+        'ガ' => "ga",
+        'ギ' => "gi",
+        'グ' => "gu",
+        'ゲ' => "ge",
+        'ゴ' => "go",
+        'サ' => "sa",
+        'シ' => "shi",
+        'ス' => "su",
+        'セ' => "se",
+        'ソ' => "so",
+        'ザ' => "za",
+        'ジ' | 'ヂ' => "ji",
+        'ズ' | 'ヅ' => "zu",
+        'ゼ' => "ze",
+        'ゾ' => "zo",
+        'タ' => "ta",
+        'チ' => "chi",
+        'ツ' => "tsu",
+        'テ' => "te",
+        'ト' => "to",
+        'ダ' => "da",
+        'デ' => "de",
+        'ド' => "do",
+        'ナ' => "na",
+        'ニ' => "ni",
+        'ヌ' => "nu",
+        'ネ' => "ne",
+        'ノ' => "no",
+        'ハ' => "ha",
+        'ヒ' => "hi",
+        'フ' => "fu",
+        'ヘ' => "he",
+        'ホ' => "ho",
+        'バ' => "ba",
+        'ビ' => "bi",
+        'ブ' => "bu",
+        'ベ' => "be",
+        'ボ' => "bo",
+        'パ' => "pa",
+        'ピ' => "pi",
+        'プ' => "pu",
+        'ペ' => "pe",
+        'ポ' => "po",
+        'マ' => "ma",
+        'ミ' => "mi",
+        'ム' => "mu",
+        'メ' => "me",
+        'モ' => "mo",
+        'ヤ' | 'ャ' => "ya",
+        'ユ' | 'ュ' => "yu",
+        'ヨ' | 'ョ' => "yo",
+        'ラ' => "ra",
+        'リ' => "ri",
+        'ル' => "ru",
+        'レ' => "re",
+        'ロ' => "ro",
+        'ワ' | 'ヮ' => "wa",
+        'ン' => "n",
+        'ヴ' => "vu",
+        _ => return None,
+    };
+    Some(romaji)
+}
+
+// "キ" + "ャ" -> "kya", "シ" + "ャ" -> "sha", "フ" + "ァ" -> "fa", "ウ" + "ィ" -> "wi"
+fn combine(base: &str, small: char) -> Option<String> {
+    let vowel = syllable(small)?.chars().last()?;
+    let stem = &base[..base.len() - 1];
+    match small {
+        'ャ' | 'ュ' | 'ョ' if base.ends_with('i') => {
+            if matches!(base, "shi" | "chi" | "ji") {
+                Some(format!("{stem}{vowel}"))
+            } else {
+                Some(format!("{stem}y{vowel}"))
+            }
+        }
+        'ァ' | 'ィ' | 'ゥ' | 'ェ' | 'ォ' => {
+            let stem = if stem.is_empty() { "w" } else { stem };
+            Some(format!("{stem}{vowel}"))
+        }
+        _ => None,
+    }
+}
+
+/// Romanizes a full-width katakana reading in lowercase Hepburn ("シンジュク" -> "shinjuku").
+/// Characters other than kana are dropped, except ASCII letters and digits.
+pub fn kana_to_romaji(kana: &str) -> String {
+    let chars: Vec<char> = kana.chars().collect();
+    let mut raw = String::with_capacity(kana.len() * 2);
+    let mut geminate = false;
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        i += 1;
+        match c {
+            'ッ' => {
+                geminate = true;
+                continue;
+            }
+            // Long vowel marks are not written.
+            'ー' => continue,
+            c if c.is_ascii_alphanumeric() => {
+                raw.push(c.to_ascii_lowercase());
+                geminate = false;
+                continue;
+            }
+            _ => {}
+        }
+        let Some(base) = syllable(c) else {
+            geminate = false;
+            continue;
+        };
+        let mut romaji = base.to_string();
+        if let Some(combined) = chars.get(i).and_then(|&next| combine(base, next)) {
+            romaji = combined;
+            i += 1;
+        }
+        if geminate && romaji != "n" && !romaji.starts_with(['a', 'i', 'u', 'e', 'o']) {
+            if romaji.starts_with("ch") {
+                raw.push('t');
+            } else if let Some(first) = romaji.chars().next() {
+                raw.push(first);
+            }
+        }
+        geminate = false;
+        raw.push_str(&romaji);
+    }
+    collapse_long_vowels(&raw)
+}
+
+// "ou" / "oo" -> "o" and "uu" -> "u", as in "Tokyo" and "Osaka".
+fn collapse_long_vowels(romaji: &str) -> String {
+    let mut out = String::with_capacity(romaji.len());
+    for c in romaji.chars() {
+        let prev = out.chars().last();
+        let long = match c {
+            'u' => matches!(prev, Some('o' | 'u')),
+            'o' => prev == Some('o'),
+            _ => false,
+        };
+        if !long {
+            out.push(c);
+        }
+    }
+    out
+}
+
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first.to_ascii_uppercase().to_string() + chars.as_str(),
+        None => String::new(),
+    }
+}
+
+// One segment such as "新宿区" / "シンジュクク" -> "Shinjuku-ku".
+fn romanize_segment(name: &str, kana: &str) -> String {
+    // "北海道" keeps its conventional spelling without a suffix.
+    if name == "北海道" {
+        return capitalize(&kana_to_romaji(kana));
+    }
+    let last = name.chars().last();
+    for (suffix_char, suffix_kana, suffix) in SUFFIXES {
+        if last != Some(*suffix_char) || name.chars().count() < 2 {
+            continue;
+        }
+        if let Some(stem) = kana.strip_suffix(suffix_kana).filter(|s| !s.is_empty()) {
+            return format!("{}-{suffix}", capitalize(&kana_to_romaji(stem)));
+        }
+    }
+    capitalize(&kana_to_romaji(kana))
+}
+
+/// Romanizes a prefecture or municipality name from its reading, e.g.
+/// ("東京都", "トウキョウト") -> "Tokyo-to", ("札幌市中央区", "サッポロシチュウオウク") -> "Sapporo-shi Chuo-ku".
+/// Returns an empty string when there is no reading.
+pub fn romanize_place(name: &str, kana: &str) -> String {
+    if kana.is_empty() {
+        return String::new();
+    }
+    // County towns: "余市郡余市町" / "ヨイチグンヨイチチョウ"
+    if let (Some((county, rest)), Some(split)) = (name.split_once('郡'), kana.find("グン")) {
+        if !rest.is_empty() {
+            let (county_kana, rest_kana) = kana.split_at(split + "グン".len());
+            return format!(
+                "{} {}",
+                romanize_segment(&format!("{county}郡"), county_kana),
+                romanize_segment(rest, rest_kana)
+            );
+        }
+    }
+    for (city, city_kana) in DESIGNATED_CITIES {
+        if let (Some(ward), Some(ward_kana)) =
+            (name.strip_prefix(city), kana.strip_prefix(city_kana))
+        {
+            if !ward.is_empty() {
+                return format!(
+                    "{} {}",
+                    romanize_segment(city, city_kana),
+                    romanize_segment(ward, ward_kana)
+                );
+            }
+        }
+    }
+    romanize_segment(name, kana)
+}
+
+/// Romanizes a town name; towns carry no suffix ("ニシシンジュク" -> "Nishishinjuku").
+pub fn romanize_town(kana: &str) -> String {
+    capitalize(&kana_to_romaji(kana))
+}
+
+/// Whether a normalized search keyword is written in romaji rather than kana or kanji.
+pub fn is_romaji(input: &str) -> bool {
+    input.chars().any(|c| c.is_ascii_alphabetic())
+        && input
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '\'' | '.' | ',' | ' '))
+}
+
+/// Folds romaji for comparison: lowercase, letters and digits only, long vowels collapsed
+/// and the traditional "m" before b/p written as "n" ("Shimbashi" -> "shinbashi").
+/// Stored names only need lowercasing and separator removal to reach the same form.
+pub fn fold_romaji(input: &str) -> String {
+    let letters: String = input
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_lowercase())
+        .collect();
+    let mut out = String::with_capacity(letters.len());
+    let mut chars = letters.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == 'm'
+            && matches!(chars.peek(), Some('b' | 'p'))
+            && out.ends_with(['a', 'i', 'u', 'e', 'o'])
+        {
+            out.push('n');
+        } else {
+            out.push(c);
+        }
+    }
+    collapse_long_vowels(&out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{fold_romaji, is_romaji, kana_to_romaji, romanize_place, romanize_town};
+
+    #[test]
+    fn kana_to_romaji_uses_hepburn() {
+        assert_eq!(kana_to_romaji("シンジュク"), "shinjuku");
+        assert_eq!(kana_to_romaji("トウキョウ"), "tokyo");
+        assert_eq!(kana_to_romaji("オオサカ"), "osaka");
+        assert_eq!(kana_to_romaji("ホッカイドウ"), "hokkaido");
+        assert_eq!(kana_to_romaji("ハッチョウボリ"), "hatchobori");
+        assert_eq!(kana_to_romaji("チュウオウ"), "chuo");
+        assert_eq!(kana_to_romaji("ジンボウチョウ"), "jinbocho");
+        assert_eq!(kana_to_romaji("ファミリー"), "famiri");
+    }
+
+    #[test]
+    fn romanize_place_hyphenates_suffixes() {
+        assert_eq!(romanize_place("東京都", "トウキョウト"), "Tokyo-to");
+        assert_eq!(romanize_place("北海道", "ホッカイドウ"), "Hokkaido");
+        assert_eq!(romanize_place("千代田区", "チヨダク"), "Chiyoda-ku");
+        assert_eq!(
+            romanize_place("札幌市中央区", "サッポロシチュウオウク"),
+            "Sapporo-shi Chuo-ku"
+        );
+        assert_eq!(
+            romanize_place("余市郡余市町", "ヨイチグンヨイチチョウ"),
+            "Yoichi-gun Yoichi-cho"
+        );
+        assert_eq!(romanize_place("新宿区", ""), "");
+        assert_eq!(romanize_town("ニシシンジュク"), "Nishishinjuku");
+    }
+
+    #[test]
+    fn fold_romaji_matches_stored_spelling() {
+        assert_eq!(fold_romaji("Chiyoda-ku"), "chiyodaku");
+        assert_eq!(fold_romaji("Toukyou"), "tokyo");
+        assert_eq!(fold_romaji("Shimbashi"), "shinbashi");
+        assert!(is_romaji("shinjuku"));
+        assert!(is_romaji("Chiyoda-ku"));
+        assert!(!is_romaji("1600023"));
+        assert!(!is_romaji("新宿"));
+    }
+}
@@ -0,0 +1,84 @@
+use crate::normalize::is_hyphen;
+use std::fmt;
+use unicode_normalization::UnicodeNormalization;
+
+/// Why a postal code input could not be normalized to seven digits.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ZipCodeFormatError {
+    Empty,
+    InvalidCharacter(char),
+    WrongLength(usize),
+}
+
+impl fmt::Display for ZipCodeFormatError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Empty => write!(f, "postal code is empty"),
+            Self::InvalidCharacter(c) => write!(f, "unexpected character '{c}'"),
+            Self::WrongLength(len) => write!(f, "expected 7 digits, got {len}"),
+        }
+    }
+}
+
+impl std::error::Error for ZipCodeFormatError {}
+
+/// Normalizes user input such as "〒１００－０００１" or "100-0001" to "1000001".
+///
+/// Applies NFKC, drops whitespace, a leading '〒' and hyphens, then requires exactly
+/// seven ASCII digits.
+pub fn normalize_zip_code(input: &str) -> Result<String, ZipCodeFormatError> {
+    let folded: String = input.nfkc().filter(|c| !c.is_whitespace()).collect();
+    let body = folded.strip_prefix('〒').unwrap_or(&folded);
+    if body.is_empty() {
+        return Err(ZipCodeFormatError::Empty);
+    }
+
+    let mut digits = String::with_capacity(7);
+    for c in body.chars() {
+        if c.is_ascii_digit() {
+            digits.push(c);
+        } else if !is_hyphen(c) {
+            return Err(ZipCodeFormatError::InvalidCharacter(c));
+        }
+    }
+    if digits.len() != 7 {
+        return Err(ZipCodeFormatError::WrongLength(digits.len()));
+    }
+    Ok(digits)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{normalize_zip_code, ZipCodeFormatError};
+
+    #[test]
+    fn normalize_zip_code_accepts_common_spellings() {
+        for input in [
+            "1000001",
+            "100-0001",
+            "１００－０００１",
+            "〒100-0001",
+            " 〒 １００ー０００１ ",
+        ] {
+            assert_eq!(
+                normalize_zip_code(input).as_deref(),
+                Ok("1000001"),
+                "{input}"
+            );
+        }
+    }
+
+    #[test]
+    fn normalize_zip_code_reports_bad_format() {
+        assert_eq!(normalize_zip_code(" "), Err(ZipCodeFormatError::Empty));
+        assert_eq!(normalize_zip_code("〒"), Err(ZipCodeFormatError::Empty));
+        assert_eq!(
+            normalize_zip_code("100001"),
+            Err(ZipCodeFormatError::WrongLength(6))
+        );
+        assert_eq!(
+            normalize_zip_code("100-000a"),
+            Err(ZipCodeFormatError::InvalidCharacter('a'))
+        );
+    }
+}
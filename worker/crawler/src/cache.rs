@@ -0,0 +1,72 @@
+// Invalidation of the API's Redis response cache after the data changed.
+use crate::tlog;
+use redis::AsyncCommands;
+use serde::Serialize;
+
+/// Every key the API caches responses under.
+const CACHE_KEY_PATTERN: &str = "postal:*";
+
+#[derive(Debug, Serialize)]
+pub struct CacheInvalidation {
+    /// Keys matching `postal:*`.
+    pub matched: usize,
+    /// Keys removed; `0` for a dry run.
+    pub deleted: usize,
+}
+
+/// Deletes the `postal:*` keys from `redis_url`, or only counts them when `dry_run` is set.
+pub async fn invalidate(redis_url: &str, dry_run: bool) -> Result<CacheInvalidation, String> {
+    let client = redis::Client::open(redis_url)
+        .map_err(|e| format!("Invalid REDIS_URL for crawler cache invalidation: {e}"))?;
+    let mut conn = client
+        .get_multiplexed_async_connection()
+        .await
+        .map_err(|e| format!("Failed to connect Redis for cache invalidation: {e}"))?;
+
+    let mut cursor: u64 = 0;
+    let mut report = CacheInvalidation {
+        matched: 0,
+        deleted: 0,
+    };
+
+    loop {
+        let (next_cursor, keys): (u64, Vec<String>) = redis::cmd("SCAN")
+            .arg(cursor)
+            .arg("MATCH")
+            .arg(CACHE_KEY_PATTERN)
+            .arg("COUNT")
+            .arg(500)
+            .query_async(&mut conn)
+            .await
+            .map_err(|e| format!("Failed to scan Redis cache keys: {e}"))?;
+
+        report.matched += keys.len();
+        if !keys.is_empty() && !dry_run {
+            let result: redis::RedisResult<()> = conn.del(&keys).await;
+            result.map_err(|e| format!("Failed to delete Redis cache keys: {e}"))?;
+            report.deleted += keys.len();
+        }
+
+        if next_cursor == 0 {
+            break;
+        }
+        cursor = next_cursor;
+    }
+    Ok(report)
+}
+
+/// Invalidation after an update: skipped without `REDIS_URL`, and a failure is only
+/// logged because the data itself is already in place.
+pub async fn invalidate_after_update() {
+    let Ok(redis_url) = std::env::var("REDIS_URL") else {
+        return;
+    };
+    match invalidate(&redis_url, false).await {
+        Ok(report) => tlog!(
+            "Redis cache invalidated for {} (deleted {} keys).",
+            CACHE_KEY_PATTERN,
+            report.deleted
+        ),
+        Err(e) => eprintln!("{e}"),
+    }
+}
@@ -0,0 +1,448 @@
+// Command line of the crawler binary: subcommands, shared options and report output.
+use common::serde_json;
+use serde::Serialize;
+use std::path::PathBuf;
+
+const USAGE: &str = "\
+Usage: crawler [COMMAND] [OPTIONS]
+
+Commands:
+  run                  Update the data on CRAWLER_SCHEDULE (default when no command is given)
+  once                 Run a single update cycle and exit
+  import <SOURCE>      Load a local ZIP, CSV, directory or stdin (-) once
+  verify               Check the loaded data against the latest audit and snapshot
+  snapshot list        List snapshot versions
+  snapshot prune       Delete all but the newest snapshots (--keep N)
+  snapshot export <V>  Write the rows of snapshot V as CSV
+  audit list           Show the latest update audits
+  cache invalidate     Delete the API's postal:* keys from Redis
+  rollback <V>         Restore postal_codes from snapshot V
+
+Options (every command):
+  --dry-run                  Report what would change without writing anything
+  --output text|json         Print a single JSON report per command (logs go to stderr)
+  --database-type TYPE       postgres or mysql (default: DATABASE_TYPE, then postgres)
+  -h, --help                 Show help for the command
+
+Run `crawler <COMMAND> --help` for the options of a command.";
+
+const RUN_USAGE: &str = "\
+Usage: crawler run [--dry-run] [--output text|json] [--database-type TYPE]
+
+Updates the data right away, then on CRAWLER_SCHEDULE: a daily HH:MM or a 5-field
+cron expression in JST (`L` = last day of the month). Without it, every
+CRAWLER_INTERVAL_SECONDS. Sources come from ZIP_CODE_URL, JIGYOSYO_URL,
+CRAWLER_UPDATE_MODE and ZIP_CODE_DIFF_URL_TEMPLATE.
+Failed cycles are retried after CRAWLER_RETRY_INITIAL_SECONDS, doubling up to
+CRAWLER_RETRY_MAX_SECONDS, but never later than the next scheduled run.
+CRAWLER_JITTER_SECONDS adds a random delay; CRAWLER_BLACKOUT lists JST windows
+(HH:MM-HH:MM, Sun HH:MM-HH:MM, YYYY-MM-DDTHH:MM/YYYY-MM-DDTHH:MM) with no runs.
+CRAWLER_RUN_ONCE=true stops after the first completed cycle, like `crawler once`.
+--dry-run prints the resolved settings and the next runs, and exits. With --output json every cycle
+prints one report line.";
+
+const ONCE_USAGE: &str = "\
+Usage: crawler once [--dry-run] [--output text|json] [--database-type TYPE]
+
+Runs a single update cycle with the `run` settings and exits.
+--dry-run downloads and parses the feed and reports whether it would be loaded,
+without writing to the database, the cache or the download state.";
+
+const IMPORT_USAGE: &str = "\
+Usage: crawler import <SOURCE> [--dry-run] [--output text|json] [--database-type TYPE]
+
+SOURCE is utf_ken_all.zip or ken_all.zip, an extracted CSV, file:///PATH, a directory
+holding utf_ken_all (or ken_all) and optionally jigyosyo (ZIP or CSV), or - to read stdin.
+--dry-run parses the files and reports whether they differ from the loaded data.";
+
+const VERIFY_USAGE: &str = "\
+Usage: crawler verify [--output text|json] [--database-type TYPE]
+
+Checks that the latest update did not fail, that postal_codes holds as many rows as
+that update recorded, and that the latest full snapshot is complete.
+Exits with status 1 when a check fails. Read-only, so --dry-run changes nothing.";
+
+const SNAPSHOT_USAGE: &str = "\
+Usage: crawler snapshot list [--output text|json] [--database-type TYPE]
+       crawler snapshot prune --keep N [--dry-run] [--output text|json] [--database-type TYPE]
+       crawler snapshot export <VERSION> [--out PATH] [--dry-run] [--output text|json] [--database-type TYPE]
+
+list    Snapshot versions, newest first, with their row counts.
+prune   Deletes every snapshot except the newest N versions (N >= 1) and the one
+        postal_codes currently holds (the latest update or rollback target).
+export  Writes the rows of VERSION as CSV to PATH (default: snapshot_<VERSION>.csv).
+--dry-run reports the versions or rows affected without deleting or writing.";
+
+const AUDIT_USAGE: &str = "\
+Usage: crawler audit list [--limit N] [--output text|json] [--database-type TYPE]
+
+Shows the newest N update audits (default 20). Read-only, so --dry-run changes nothing.";
+
+const CACHE_USAGE: &str = "\
+Usage: crawler cache invalidate [--dry-run] [--output text|json]
+
+Deletes the API's postal:* keys from REDIS_URL. --dry-run only counts them.";
+
+const ROLLBACK_USAGE: &str = "\
+Usage: crawler rollback <VERSION> [--dry-run] [--output text|json] [--database-type TYPE]
+       crawler rollback --data-version <VERSION> ...
+
+Replaces postal_codes with the snapshot of VERSION and records a rollback audit.
+--dry-run checks that the snapshot exists and reports its row count.";
+
+const DEFAULT_AUDIT_LIMIT: i64 = 20;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    Text,
+    Json,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Options {
+    pub dry_run: bool,
+    pub output: OutputFormat,
+    /// `--database-type`; `None` falls back to `DATABASE_TYPE`.
+    pub database_type: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SnapshotCommand {
+    List,
+    Prune { keep: usize },
+    Export { data_version: String, out: PathBuf },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    Run,
+    Once,
+    Import { source: String },
+    Verify,
+    Snapshot(SnapshotCommand),
+    AuditList { limit: i64 },
+    CacheInvalidate,
+    Rollback { data_version: String },
+}
+
+impl Command {
+    /// The command as typed, used as `command` in JSON reports.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Command::Run => "run",
+            Command::Once => "once",
+            Command::Import { .. } => "import",
+            Command::Verify => "verify",
+            Command::Snapshot(SnapshotCommand::List) => "snapshot list",
+            Command::Snapshot(SnapshotCommand::Prune { .. }) => "snapshot prune",
+            Command::Snapshot(SnapshotCommand::Export { .. }) => "snapshot export",
+            Command::AuditList { .. } => "audit list",
+            Command::CacheInvalidate => "cache invalidate",
+            Command::Rollback { .. } => "rollback",
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Parsed {
+    /// `--help`: print the text and exit successfully.
+    Help(&'static str),
+    Invocation(Command, Options),
+}
+
+/// A usage error, printed with the usage of the command it concerns.
+#[derive(Debug, PartialEq, Eq)]
+pub struct UsageError {
+    pub message: String,
+    pub usage: &'static str,
+}
+
+fn usage_for(positional: &[String]) -> &'static str {
+    match positional.first().map(String::as_str) {
+        Some("run") => RUN_USAGE,
+        Some("once") => ONCE_USAGE,
+        Some("import") => IMPORT_USAGE,
+        Some("verify") => VERIFY_USAGE,
+        Some("snapshot") => SNAPSHOT_USAGE,
+        Some("audit") => AUDIT_USAGE,
+        Some("cache") => CACHE_USAGE,
+        Some("rollback") => ROLLBACK_USAGE,
+        _ => USAGE,
+    }
+}
+
+/// Options that take a value and belong to a single command.
+const COMMAND_OPTIONS: &[&str] = &["--keep", "--out", "--limit", "--data-version"];
+
+struct RawArgs {
+    positional: Vec<String>,
+    values: Vec<(String, String)>,
+    help: bool,
+    options: Options,
+}
+
+fn split_args(args: &[String]) -> Result<RawArgs, String> {
+    let mut raw = RawArgs {
+        positional: Vec::new(),
+        values: Vec::new(),
+        help: false,
+        options: Options {
+            dry_run: false,
+            output: OutputFormat::Text,
+            database_type: None,
+        },
+    };
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if arg == "-" || !arg.starts_with('-') {
+            raw.positional.push(arg.clone());
+            continue;
+        }
+        let (name, inline) = match arg.split_once('=') {
+            Some((name, value)) => (name, Some(value.to_string())),
+            None => (arg.as_str(), None),
+        };
+        match name {
+            "-h" | "--help" => raw.help = true,
+            "--dry-run" => raw.options.dry_run = true,
+            "--output" | "--database-type" => {
+                let value = inline
+                    .or_else(|| args.next().cloned())
+                    .ok_or_else(|| format!("{name} requires a value"))?;
+                if name == "--output" {
+                    raw.options.output = match value.as_str() {
+                        "text" => OutputFormat::Text,
+                        "json" => OutputFormat::Json,
+                        other => return Err(format!("unsupported --output: {other}")),
+                    };
+                } else {
+                    raw.options.database_type = Some(value);
+                }
+            }
+            name if COMMAND_OPTIONS.contains(&name) => {
+                let value = inline
+                    .or_else(|| args.next().cloned())
+                    .ok_or_else(|| format!("{name} requires a value"))?;
+                raw.values.push((name.to_string(), value));
+            }
+            _ => return Err(format!("unknown option: {arg}")),
+        }
+    }
+    Ok(raw)
+}
+
+fn take_value(values: &mut Vec<(String, String)>, name: &str) -> Option<String> {
+    let index = values.iter().position(|(key, _)| key == name)?;
+    Some(values.remove(index).1)
+}
+
+fn parse_number<T: std::str::FromStr>(name: &str, value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("{name} must be a positive number: {value}"))
+}
+
+fn build_command(
+    positional: &[String],
+    values: &mut Vec<(String, String)>,
+) -> Result<Command, String> {
+    let words: Vec<&str> = positional.iter().map(String::as_str).collect();
+    let command = match words.as_slice() {
+        [] | ["run"] => Command::Run,
+        ["once"] => Command::Once,
+        ["import", source] => Command::Import {
+            source: source.to_string(),
+        },
+        ["import"] => return Err("import requires a SOURCE".to_string()),
+        ["verify"] => Command::Verify,
+        ["snapshot", "list"] => Command::Snapshot(SnapshotCommand::List),
+        ["snapshot", "prune"] => {
+            let keep = take_value(values, "--keep").ok_or("snapshot prune requires --keep N")?;
+            let keep: usize = parse_number("--keep", &keep)?;
+            if keep == 0 {
+                return Err("--keep must be at least 1".to_string());
+            }
+            Command::Snapshot(SnapshotCommand::Prune { keep })
+        }
+        ["snapshot", "export", data_version] => {
+            let out = take_value(values, "--out")
+                .unwrap_or_else(|| format!("snapshot_{data_version}.csv"));
+            Command::Snapshot(SnapshotCommand::Export {
+                data_version: data_version.to_string(),
+                out: PathBuf::from(out),
+            })
+        }
+        ["snapshot", "export"] => return Err("snapshot export requires a VERSION".to_string()),
+        ["audit", "list"] => {
+            let limit = match take_value(values, "--limit") {
+                Some(limit) => parse_number("--limit", &limit)?,
+                None => DEFAULT_AUDIT_LIMIT,
+            };
+            if limit < 1 {
+                return Err("--limit must be at least 1".to_string());
+            }
+            Command::AuditList { limit }
+        }
+        ["cache", "invalidate"] => Command::CacheInvalidate,
+        ["rollback", data_version] => Command::Rollback {
+            data_version: data_version.to_string(),
+        },
+        ["rollback"] => match take_value(values, "--data-version") {
+            Some(data_version) => Command::Rollback { data_version },
+            None => return Err("rollback requires a VERSION".to_string()),
+        },
+        _ => return Err(format!("unknown command: {}", positional.join(" "))),
+    };
+    if let Some((name, _)) = values.first() {
+        return Err(format!("{name} is not an option of `{}`", command.name()));
+    }
+    Ok(command)
+}
+
+/// Parses the arguments after the program name. No command means `run`, so the
+/// container entrypoint keeps working without arguments.
+pub fn parse(args: &[String]) -> Result<Parsed, UsageError> {
+    let mut raw = split_args(args).map_err(|message| UsageError {
+        message,
+        usage: usage_for(&[]),
+    })?;
+    let usage = usage_for(&raw.positional);
+    if raw.help {
+        return Ok(Parsed::Help(usage));
+    }
+    let command = build_command(&raw.positional, &mut raw.values)
+        .map_err(|message| UsageError { message, usage })?;
+    Ok(Parsed::Invocation(command, raw.options))
+}
+
+/// A command result that prints as text lines or as the `result` of a JSON report.
+pub trait Report: Serialize {
+    fn text(&self) -> String;
+}
+
+#[derive(Serialize)]
+struct Envelope<'a, T: Serialize> {
+    command: &'a str,
+    ok: bool,
+    dry_run: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    result: Option<&'a T>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<&'a str>,
+}
+
+pub fn print_report<T: Report>(command: &Command, options: &Options, report: &T) {
+    match options.output {
+        OutputFormat::Text => {
+            println!("{}", report.text());
+            if options.dry_run {
+                println!("Dry run: nothing was written.");
+            }
+        }
+        OutputFormat::Json => print_envelope(command, options, Some(report), None),
+    }
+}
+
+/// Failures go to stderr as text, or to stdout as a JSON report with `"ok": false`.
+pub fn print_error(command: &Command, options: &Options, error: &str) {
+    match options.output {
+        OutputFormat::Text => eprintln!("{error}"),
+        OutputFormat::Json => print_envelope::<()>(command, options, None, Some(error)),
+    }
+}
+
+fn print_envelope<T: Serialize>(
+    command: &Command,
+    options: &Options,
+    result: Option<&T>,
+    error: Option<&str>,
+) {
+    let envelope = Envelope {
+        command: command.name(),
+        ok: error.is_none(),
+        dry_run: options.dry_run,
+        result,
+        error,
+    };
+    println!(
+        "{}",
+        serde_json::to_string(&envelope).expect("report serializes")
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse, Command, OutputFormat, Parsed, SnapshotCommand, SNAPSHOT_USAGE, USAGE};
+    use std::path::PathBuf;
+
+    fn args(line: &str) -> Vec<String> {
+        line.split_whitespace().map(str::to_string).collect()
+    }
+
+    fn invocation(line: &str) -> (Command, super::Options) {
+        match parse(&args(line)) {
+            Ok(Parsed::Invocation(command, options)) => (command, options),
+            other => panic!("{line}: {other:?}"),
+        }
+    }
+
+    #[test]
+    fn no_command_runs_the_daemon() {
+        let (command, options) = invocation("");
+        assert_eq!(command, Command::Run);
+        assert!(!options.dry_run);
+        assert_eq!(options.output, OutputFormat::Text);
+    }
+
+    #[test]
+    fn shared_options_are_accepted_anywhere() {
+        let (command, options) =
+            invocation("--output json snapshot prune --keep 3 --dry-run --database-type=mysql");
+        assert_eq!(
+            command,
+            Command::Snapshot(SnapshotCommand::Prune { keep: 3 })
+        );
+        assert!(options.dry_run);
+        assert_eq!(options.output, OutputFormat::Json);
+        assert_eq!(options.database_type.as_deref(), Some("mysql"));
+    }
+
+    #[test]
+    fn commands_take_their_arguments() {
+        assert_eq!(
+            invocation("import -").0,
+            Command::Import {
+                source: "-".to_string()
+            }
+        );
+        assert_eq!(
+            invocation("snapshot export v1").0,
+            Command::Snapshot(SnapshotCommand::Export {
+                data_version: "v1".to_string(),
+                out: PathBuf::from("snapshot_v1.csv"),
+            })
+        );
+        assert_eq!(invocation("audit list").0, Command::AuditList { limit: 20 });
+        assert_eq!(
+            invocation("rollback --data-version v1").0,
+            Command::Rollback {
+                data_version: "v1".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn help_and_errors_use_the_command_usage() {
+        assert_eq!(parse(&args("--help")), Ok(Parsed::Help(USAGE)));
+        assert_eq!(
+            parse(&args("snapshot prune -h")),
+            Ok(Parsed::Help(SNAPSHOT_USAGE))
+        );
+
+        let error = parse(&args("snapshot prune --keep 0")).unwrap_err();
+        assert_eq!(error.usage, SNAPSHOT_USAGE);
+        assert!(parse(&args("audit list --keep 1")).is_err());
+        assert!(parse(&args("once extra")).is_err());
+        assert!(parse(&args("--output yaml")).is_err());
+    }
+}
@@ -1,5 +1,12 @@
+use crate::db::schema::postal_code_column_list;
+use common::audit::{live_snapshot, STATUS_UNCHANGED};
+use common::models::PostalCode;
+use common::normalize::search_key;
+use common::serde_json;
 use deadpool_postgres::Pool as PgPool;
 use mysql_async::{params, prelude::Queryable, Pool as MySqlPool};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
 use tokio_postgres::Error as PgError;
 
 #[derive(Debug, Clone)]
@@ -16,8 +23,92 @@ pub struct DataUpdateAuditRecord {
     pub total_count: i64,
     pub status: String,
     pub error_message: Option<String>,
+    /// Latest Japan Post monthly diff (YYMM) reflected in the data after this run.
+    pub diff_month: Option<String>,
+    /// SHA-256 of the downloaded `source_url` file; `None` when nothing was downloaded.
+    pub source_sha256: Option<String>,
+    /// `dataset_fingerprint` of the rows loaded by a full reload.
+    pub dataset_fingerprint: Option<String>,
 }
 
+/// The data a full reload would compare its feed against.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LoadedDataset {
+    pub fingerprint: String,
+    pub total_count: i64,
+}
+
+/// Version of what the crawler derives from a feed: the parsed `PostalCode` fields and the
+/// stored search keys. Bump it whenever the parser, the romanization or `search_key` changes,
+/// so the next full reload rewrites the rows instead of reporting them unchanged.
+pub const DATASET_FORMAT_VERSION: u32 = 1;
+
+/// SHA-256 over the rows as stored, independent of their order in the feed: every
+/// serialized field plus the `*_normalized` search keys, which `PostalCode` does not carry.
+/// `DATASET_FORMAT_VERSION` is hashed in too.
+pub fn dataset_fingerprint(rows: &[PostalCode]) -> String {
+    let mut lines: Vec<String> = rows
+        .iter()
+        .map(|row| {
+            let keys = [&row.prefecture, &row.city, &row.town].map(|name| search_key(name));
+            format!(
+                "{}\t{}",
+                serde_json::to_string(row).expect("PostalCode serializes"),
+                keys.join("\t")
+            )
+        })
+        .collect();
+    lines.sort_unstable();
+    let mut hasher = Sha256::new();
+    hasher.update(DATASET_FORMAT_VERSION.to_string());
+    for line in &lines {
+        hasher.update(b"\n");
+        hasher.update(line);
+    }
+    format!("{:x}", hasher.finalize())
+}
+
+/// The dataset left by the latest run when it was a full reload that completed; any other
+/// latest run (failed, incremental, rollback) means the data must be reloaded.
+fn loaded_dataset(
+    status: &str,
+    fingerprint: Option<String>,
+    total_count: i64,
+) -> Option<LoadedDataset> {
+    if status != "success" && status != STATUS_UNCHANGED {
+        return None;
+    }
+    fingerprint.map(|fingerprint| LoadedDataset {
+        fingerprint,
+        total_count,
+    })
+}
+
+/// One `data_update_audits` row, as listed by `crawler audit list`.
+#[derive(Debug, Clone, Serialize)]
+pub struct AuditEntry {
+    pub id: i64,
+    pub data_version: String,
+    pub status: String,
+    pub source_url: String,
+    pub run_started_at: chrono::DateTime<chrono::Utc>,
+    pub run_finished_at: chrono::DateTime<chrono::Utc>,
+    pub records_in_feed: i64,
+    pub inserted_count: i64,
+    pub updated_count: i64,
+    pub deleted_count: i64,
+    pub total_count: i64,
+    pub diff_month: Option<String>,
+    pub source_sha256: Option<String>,
+    pub dataset_fingerprint: Option<String>,
+    pub error_message: Option<String>,
+}
+
+const AUDIT_ENTRY_COLUMNS: &str =
+    "id, data_version, status, source_url, run_started_at, run_finished_at,
+    records_in_feed, inserted_count, updated_count, deleted_count, total_count,
+    diff_month, source_sha256, dataset_fingerprint, error_message";
+
 pub fn build_data_version(batch_timestamp: chrono::NaiveDateTime) -> String {
     format!(
         "v{}{:03}",
@@ -50,6 +141,9 @@ pub async fn ensure_audit_table_postgres(pool: &PgPool) -> Result<(), PgError> {
                 total_count BIGINT NOT NULL,
                 status VARCHAR(16) NOT NULL,
                 error_message TEXT,
+                diff_month CHAR(4),
+                source_sha256 CHAR(64),
+                dataset_fingerprint CHAR(64),
                 created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP
             );
             CREATE INDEX IF NOT EXISTS idx_data_update_audits_created_at
@@ -88,15 +182,15 @@ pub async fn ensure_snapshot_table_postgres(pool: &PgPool) -> Result<(), PgError
 
 pub async fn create_postgres_snapshot(pool: &PgPool, data_version: &str) -> Result<u64, PgError> {
     let client = pool.get().await.expect("Failed to get client");
+    let columns = postal_code_column_list();
     client
         .execute(
-            "INSERT INTO postal_codes_snapshots (
-                data_version, zip_code, prefecture_id, city_id, prefecture, city, town, created_at, updated_at
-            )
-            SELECT
-                $1, zip_code, prefecture_id, city_id, prefecture, city, town, created_at, updated_at
-            FROM postal_codes
-            ON CONFLICT (data_version, zip_code, prefecture_id, city, town) DO NOTHING",
+            &format!(
+                "INSERT INTO postal_codes_snapshots (data_version, {columns}, created_at, updated_at)
+                SELECT $1, {columns}, created_at, updated_at
+                FROM postal_codes
+                ON CONFLICT (data_version, zip_code, prefecture_id, city, town) DO NOTHING"
+            ),
             &[&data_version],
         )
         .await
@@ -136,6 +230,83 @@ pub async fn compute_postgres_diff_counts(
     Ok((inserted_count, updated_count, total_count))
 }
 
+/// Diff month of the latest successful run, or `None` when that run predates diff tracking.
+/// A rollback after that run also yields `None`, since the restored data has no known diff month.
+pub async fn latest_diff_month_postgres(pool: &PgPool) -> Result<Option<String>, PgError> {
+    let client = pool.get().await.expect("Failed to get client");
+    let row = client
+        .query_opt(
+            "SELECT diff_month FROM data_update_audits
+             WHERE database_type = 'postgres' AND status IN ('success', 'rollback')
+             ORDER BY id DESC LIMIT 1",
+            &[],
+        )
+        .await?;
+    Ok(row.and_then(|row| row.get(0)))
+}
+
+/// The snapshot holding the data currently in postal_codes, which must never be pruned.
+pub async fn latest_live_snapshot_postgres(pool: &PgPool) -> Result<Option<String>, PgError> {
+    let client = pool.get().await.expect("Failed to get client");
+    let row = client
+        .query_opt(
+            "SELECT data_version, status, source_url FROM data_update_audits
+             WHERE database_type = 'postgres' AND status IN ('success', 'rollback')
+             ORDER BY id DESC LIMIT 1",
+            &[],
+        )
+        .await?;
+    Ok(row.and_then(|row| live_snapshot(row.get(0), row.get(1), row.get(2))))
+}
+
+pub async fn latest_dataset_postgres(pool: &PgPool) -> Result<Option<LoadedDataset>, PgError> {
+    let client = pool.get().await.expect("Failed to get client");
+    let row = client
+        .query_opt(
+            "SELECT status, dataset_fingerprint, total_count FROM data_update_audits
+             WHERE database_type = 'postgres'
+             ORDER BY id DESC LIMIT 1",
+            &[],
+        )
+        .await?;
+    Ok(row.and_then(|row| loaded_dataset(row.get(0), row.get(1), row.get(2))))
+}
+
+/// The newest `limit` audit rows of this database, newest first.
+pub async fn list_audits_postgres(pool: &PgPool, limit: i64) -> Result<Vec<AuditEntry>, PgError> {
+    let client = pool.get().await.expect("Failed to get client");
+    let rows = client
+        .query(
+            &format!(
+                "SELECT {AUDIT_ENTRY_COLUMNS} FROM data_update_audits
+                 WHERE database_type = 'postgres'
+                 ORDER BY id DESC LIMIT $1"
+            ),
+            &[&limit],
+        )
+        .await?;
+    Ok(rows
+        .iter()
+        .map(|row| AuditEntry {
+            id: row.get(0),
+            data_version: row.get(1),
+            status: row.get(2),
+            source_url: row.get(3),
+            run_started_at: row.get(4),
+            run_finished_at: row.get(5),
+            records_in_feed: row.get(6),
+            inserted_count: row.get(7),
+            updated_count: row.get(8),
+            deleted_count: row.get(9),
+            total_count: row.get(10),
+            diff_month: row.get(11),
+            source_sha256: row.get(12),
+            dataset_fingerprint: row.get(13),
+            error_message: row.get(14),
+        })
+        .collect())
+}
+
 pub async fn insert_audit_postgres(
     pool: &PgPool,
     record: &DataUpdateAuditRecord,
@@ -148,12 +319,12 @@ pub async fn insert_audit_postgres(
                 data_version, database_type, source_url,
                 run_started_at, run_finished_at, batch_timestamp,
                 records_in_feed, inserted_count, updated_count, deleted_count, total_count,
-                status, error_message
+                status, error_message, diff_month, source_sha256, dataset_fingerprint
             ) VALUES (
                 $1, 'postgres', $2,
                 $3, $4, $5,
                 $6, $7, $8, $9, $10,
-                $11, $12
+                $11, $12, $13, $14, $15
             )",
             &[
                 &record.data_version,
@@ -168,6 +339,9 @@ pub async fn insert_audit_postgres(
                 &record.total_count,
                 &record.status,
                 &record.error_message,
+                &record.diff_month,
+                &record.source_sha256,
+                &record.dataset_fingerprint,
             ],
         )
         .await?;
@@ -192,6 +366,9 @@ pub async fn ensure_audit_table_mysql(pool: &MySqlPool) -> Result<(), mysql_asyn
             total_count BIGINT NOT NULL,
             status VARCHAR(16) NOT NULL,
             error_message TEXT,
+            diff_month CHAR(4),
+            source_sha256 CHAR(64),
+            dataset_fingerprint CHAR(64),
             created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
             INDEX idx_data_update_audits_created_at (created_at)
         ) ENGINE=InnoDB DEFAULT CHARSET=utf8mb4 COLLATE=utf8mb4_general_ci",
@@ -227,13 +404,13 @@ pub async fn create_mysql_snapshot(
     data_version: &str,
 ) -> Result<u64, mysql_async::Error> {
     let mut conn = pool.get_conn().await?;
+    let columns = postal_code_column_list();
     conn.exec_drop(
-        "INSERT IGNORE INTO postal_codes_snapshots (
-            data_version, zip_code, prefecture_id, city_id, prefecture, city, town, created_at, updated_at
-        )
-        SELECT
-            :data_version, zip_code, prefecture_id, city_id, prefecture, city, town, created_at, updated_at
-        FROM postal_codes",
+        format!(
+            "INSERT IGNORE INTO postal_codes_snapshots (data_version, {columns}, created_at, updated_at)
+            SELECT :data_version, {columns}, created_at, updated_at
+            FROM postal_codes"
+        ),
         params! {
             "data_version" => data_version,
         },
@@ -279,6 +456,93 @@ pub async fn compute_mysql_diff_counts(
     Ok((inserted_count, updated_count, total_count))
 }
 
+/// Diff month of the latest successful run, or `None` when that run predates diff tracking.
+/// A rollback after that run also yields `None`, since the restored data has no known diff month.
+pub async fn latest_diff_month_mysql(
+    pool: &MySqlPool,
+) -> Result<Option<String>, mysql_async::Error> {
+    let mut conn = pool.get_conn().await?;
+    let diff_month = conn
+        .query_first::<Option<String>, _>(
+            "SELECT diff_month FROM data_update_audits
+             WHERE database_type = 'mysql' AND status IN ('success', 'rollback')
+             ORDER BY id DESC LIMIT 1",
+        )
+        .await?;
+    Ok(diff_month.flatten())
+}
+
+pub async fn latest_dataset_mysql(
+    pool: &MySqlPool,
+) -> Result<Option<LoadedDataset>, mysql_async::Error> {
+    let mut conn = pool.get_conn().await?;
+    let row = conn
+        .query_first::<(String, Option<String>, i64), _>(
+            "SELECT status, dataset_fingerprint, total_count FROM data_update_audits
+             WHERE database_type = 'mysql'
+             ORDER BY id DESC LIMIT 1",
+        )
+        .await?;
+    Ok(row.and_then(|(status, fingerprint, total_count)| {
+        loaded_dataset(&status, fingerprint, total_count)
+    }))
+}
+
+/// Same as `latest_live_snapshot_postgres`.
+pub async fn latest_live_snapshot_mysql(
+    pool: &MySqlPool,
+) -> Result<Option<String>, mysql_async::Error> {
+    let mut conn = pool.get_conn().await?;
+    let row = conn
+        .query_first::<(String, String, String), _>(
+            "SELECT data_version, status, source_url FROM data_update_audits
+             WHERE database_type = 'mysql' AND status IN ('success', 'rollback')
+             ORDER BY id DESC LIMIT 1",
+        )
+        .await?;
+    Ok(row.and_then(|(version, status, source_url)| live_snapshot(&version, &status, &source_url)))
+}
+
+/// The newest `limit` audit rows of this database, newest first.
+pub async fn list_audits_mysql(
+    pool: &MySqlPool,
+    limit: i64,
+) -> Result<Vec<AuditEntry>, mysql_async::Error> {
+    let mut conn = pool.get_conn().await?;
+    let rows: Vec<mysql_async::Row> = conn
+        .exec(
+            format!(
+                "SELECT {AUDIT_ENTRY_COLUMNS} FROM data_update_audits
+                 WHERE database_type = 'mysql'
+                 ORDER BY id DESC LIMIT :limit"
+            ),
+            params! { "limit" => limit },
+        )
+        .await?;
+    // DATETIME columns hold UTC, as written by insert_audit_mysql.
+    let utc = |value: Option<chrono::NaiveDateTime>| value.unwrap_or_default().and_utc();
+    Ok(rows
+        .into_iter()
+        .map(|mut row| AuditEntry {
+            id: row.take(0).unwrap_or_default(),
+            data_version: row.take(1).unwrap_or_default(),
+            status: row.take(2).unwrap_or_default(),
+            source_url: row.take(3).unwrap_or_default(),
+            run_started_at: utc(row.take(4)),
+            run_finished_at: utc(row.take(5)),
+            records_in_feed: row.take(6).unwrap_or_default(),
+            inserted_count: row.take(7).unwrap_or_default(),
+            updated_count: row.take(8).unwrap_or_default(),
+            deleted_count: row.take(9).unwrap_or_default(),
+            total_count: row.take(10).unwrap_or_default(),
+            diff_month: row.take(11).flatten(),
+            source_sha256: row.take(12).flatten(),
+            dataset_fingerprint: row.take(13).flatten(),
+            error_message: row.take(14).flatten(),
+        })
+        .collect())
+}
+
 pub async fn insert_audit_mysql(
     pool: &MySqlPool,
     record: &DataUpdateAuditRecord,
@@ -289,12 +553,12 @@ pub async fn insert_audit_mysql(
             data_version, database_type, source_url,
             run_started_at, run_finished_at, batch_timestamp,
             records_in_feed, inserted_count, updated_count, deleted_count, total_count,
-            status, error_message
+            status, error_message, diff_month, source_sha256, dataset_fingerprint
         ) VALUES (
             :data_version, 'mysql', :source_url,
             :run_started_at, :run_finished_at, :batch_timestamp,
             :records_in_feed, :inserted_count, :updated_count, :deleted_count, :total_count,
-            :status, :error_message
+            :status, :error_message, :diff_month, :source_sha256, :dataset_fingerprint
         )",
         params! {
             "data_version" => &record.data_version,
@@ -309,6 +573,9 @@ pub async fn insert_audit_mysql(
             "total_count" => record.total_count,
             "status" => &record.status,
             "error_message" => &record.error_message,
+            "diff_month" => &record.diff_month,
+            "source_sha256" => &record.source_sha256,
+            "dataset_fingerprint" => &record.dataset_fingerprint,
         },
     )
     .await?;
@@ -317,7 +584,9 @@ pub async fn insert_audit_mysql(
 
 #[cfg(test)]
 mod tests {
-    use super::build_data_version;
+    use super::{build_data_version, dataset_fingerprint, loaded_dataset};
+    use common::audit::STATUS_UNCHANGED;
+    use common::models::PostalCode;
 
     #[test]
     fn build_data_version_has_fixed_prefix_and_length() {
@@ -328,4 +597,29 @@ mod tests {
         let v = build_data_version(ts);
         assert_eq!(v, "v20260212213705123");
     }
+
+    #[test]
+    fn dataset_fingerprint_ignores_row_order_only() {
+        let rows = vec![
+            PostalCode::builder("1000001").town("千代田").build(),
+            PostalCode::builder("1000002").town("皇居外苑").build(),
+        ];
+        let reversed: Vec<PostalCode> = rows.iter().rev().cloned().collect();
+        assert_eq!(dataset_fingerprint(&rows), dataset_fingerprint(&reversed));
+
+        let mut changed = rows.clone();
+        changed[1].town = "一ツ橋".to_string();
+        assert_ne!(dataset_fingerprint(&rows), dataset_fingerprint(&changed));
+        assert_ne!(dataset_fingerprint(&rows), dataset_fingerprint(&rows[..1]));
+    }
+
+    #[test]
+    fn loaded_dataset_requires_a_completed_full_reload() {
+        let fingerprint = || Some("f".repeat(64));
+        assert!(loaded_dataset("success", fingerprint(), 10).is_some());
+        assert!(loaded_dataset(STATUS_UNCHANGED, fingerprint(), 10).is_some());
+        assert!(loaded_dataset("failed", fingerprint(), 10).is_none());
+        assert!(loaded_dataset("rollback", None, 10).is_none());
+        assert!(loaded_dataset("success", None, 10).is_none());
+    }
 }
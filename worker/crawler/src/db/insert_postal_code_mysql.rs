@@ -1,4 +1,7 @@
+use crate::db::schema::{postal_code_column_list, POSTAL_CODE_COLUMNS};
+use crate::tlog;
 use common::models::PostalCode;
+use common::normalize::search_key;
 use mysql_async::{params, prelude::Queryable, Pool};
 use tokio::task;
 use tokio::time::{sleep, Duration};
@@ -34,13 +37,35 @@ where
     }
 }
 
+// Named placeholders match the column names, so the statement follows POSTAL_CODE_COLUMNS.
+fn build_mysql_upsert_query() -> String {
+    let placeholders: Vec<String> = POSTAL_CODE_COLUMNS
+        .iter()
+        .map(|column| format!(":{column}"))
+        .collect();
+    let update_set: Vec<String> = POSTAL_CODE_COLUMNS
+        .iter()
+        .map(|column| format!("{column} = VALUES({column})"))
+        .collect();
+    format!(
+        "INSERT INTO postal_codes ({}, created_at, updated_at)
+        VALUES ({}, :created_at, :updated_at)
+        ON DUPLICATE KEY UPDATE
+        {},
+        updated_at = VALUES(updated_at)",
+        postal_code_column_list(),
+        placeholders.join(", "),
+        update_set.join(", ")
+    )
+}
+
 pub async fn bulk_insert(
     pool: &Pool,
     data: &[PostalCode],
     batch_timestamp: chrono::NaiveDateTime,
 ) -> Result<(), mysql_async::Error> {
     let chunk_size = 200;
-    println!("Inserting {} records", data.len());
+    tlog!("Inserting {} records", data.len());
 
     let mut handles = Vec::new();
     for chunk in data.chunks(chunk_size) {
@@ -48,17 +73,9 @@ pub async fn bulk_insert(
         let chunk_data = chunk.to_vec();
 
         let handle = task::spawn(async move {
-            println!("Chunk size: {}", chunk_data.len());
+            tlog!("Chunk size: {}", chunk_data.len());
 
-            let query = r"INSERT INTO postal_codes (zip_code, prefecture_id, city_id, prefecture, city, town, created_at, updated_at)
-        VALUES (:zip_code, :prefecture_id, :city_id, :prefecture, :city, :town, :created_at, :updated_at)
-        ON DUPLICATE KEY UPDATE
-        prefecture_id = VALUES(prefecture_id),
-        city_id = VALUES(city_id),
-        prefecture = VALUES(prefecture),
-            city = VALUES(city),
-            town = VALUES(town),
-            updated_at = VALUES(updated_at)";
+            let query = build_mysql_upsert_query();
 
             retry_transaction(3, Duration::from_millis(500), || {
                 let params: Vec<_> = chunk_data
@@ -71,16 +88,39 @@ pub async fn bulk_insert(
                             "prefecture" => &d.prefecture.trim(),
                             "city" => &d.city.trim(),
                             "town" => d.town.trim(),
+                            "prefecture_kana" => d.prefecture_kana.trim(),
+                            "city_kana" => d.city_kana.trim(),
+                            "town_kana" => d.town_kana.trim(),
+                            "town_detail" => d.town_detail.trim(),
+                            "chome_range" => d.chome_range.trim(),
+                            "floor" => d.floor.trim(),
+                            "kind" => d.kind.as_str(),
+                            "business_name" => d.business_name.trim(),
+                            "business_name_kana" => d.business_name_kana.trim(),
+                            "street_address" => d.street_address.trim(),
+                            "town_has_multiple_zip_codes" => d.town_has_multiple_zip_codes,
+                            "numbered_per_koaza" => d.numbered_per_koaza,
+                            "has_chome" => d.has_chome,
+                            "covers_multiple_towns" => d.covers_multiple_towns,
+                            "update_status" => d.update_status.as_str(),
+                            "update_reason" => d.update_reason.as_str(),
+                            "prefecture_roma" => d.prefecture_roma.trim(),
+                            "city_roma" => d.city_roma.trim(),
+                            "town_roma" => d.town_roma.trim(),
+                            "prefecture_normalized" => search_key(d.prefecture.trim()),
+                            "city_normalized" => search_key(d.city.trim()),
+                            "town_normalized" => search_key(d.town.trim()),
                             "created_at" => batch_timestamp,
                             "updated_at" => batch_timestamp,
                         }
                     })
                     .collect();
                 let conn_clone = pool_clone.clone();
+                let query = query.clone();
                 async move {
                     let mut conn = conn_clone.get_conn().await?;
                     let mut tx = conn.start_transaction(Default::default()).await?;
-                    tx.exec_batch(query, params.clone()).await?;
+                    tx.exec_batch(query.as_str(), params.clone()).await?;
                     tx.commit().await
                 }
             })
@@ -91,7 +131,7 @@ pub async fn bulk_insert(
             })?;
 
             sleep(Duration::from_millis(500)).await; // 次のタスクの前に少し待機
-            println!(
+            tlog!(
                 "Transaction committed for chunk of size: {}",
                 chunk_data.len()
             );
@@ -124,7 +164,7 @@ pub async fn delete_old_records_mysql(
     pool: &Pool,
     batch_timestamp: chrono::NaiveDateTime,
 ) -> Result<u64, mysql_async::Error> {
-    println!("Deleting records older than {:?}", batch_timestamp);
+    tlog!("Deleting records older than {:?}", batch_timestamp);
     let mut conn = pool.get_conn().await?;
     let query = "DELETE FROM postal_codes WHERE updated_at < :batch_timestamp";
     conn.exec_drop(
@@ -135,6 +175,36 @@ pub async fn delete_old_records_mysql(
     )
     .await?;
     let deleted_rows = conn.affected_rows();
-    println!("Old records deleted from MySQL");
+    tlog!("Old records deleted from MySQL");
+    Ok(deleted_rows)
+}
+
+/// Deletes the given rows by primary key; used when applying a monthly DEL file.
+pub async fn delete_postal_codes_mysql(
+    pool: &Pool,
+    data: &[PostalCode],
+) -> Result<u64, mysql_async::Error> {
+    let mut conn = pool.get_conn().await?;
+    let mut tx = conn.start_transaction(Default::default()).await?;
+    let mut deleted_rows = 0;
+    for d in data {
+        tx.exec_drop(
+            "DELETE FROM postal_codes
+             WHERE zip_code = :zip_code AND prefecture_id = :prefecture_id AND city = :city AND town = :town",
+            params! {
+                "zip_code" => &d.zip_code,
+                "prefecture_id" => d.prefecture_id,
+                "city" => d.city.trim(),
+                "town" => d.town.trim(),
+            },
+        )
+        .await?;
+        deleted_rows += tx.affected_rows();
+    }
+    tx.commit().await?;
+    tlog!(
+        "Deleted {} rows listed in the diff from MySQL",
+        deleted_rows
+    );
     Ok(deleted_rows)
 }
@@ -1,7 +1,9 @@
 use crate::db::query_builder::build_pg_bulk_insert_query;
+use crate::db::schema::POSTAL_CODE_COLUMNS;
 use crate::tlog;
 use crate::utils::thread::determine_thread_num;
 use common::models::PostalCode;
+use common::normalize::search_key;
 use deadpool_postgres::{Pool as PgPool, PoolError};
 use futures::future::join_all;
 use tokio::time::{sleep, Duration};
@@ -19,20 +21,32 @@ fn to_sql_param<T: tokio_postgres::types::ToSql + Sync>(
     value as &(dyn tokio_postgres::types::ToSql + Sync)
 }
 
+// Enum columns are stored as text; `as_str` yields a temporary, so the values are
+// collected per chunk first and bound by reference.
+fn enum_values(d: &PostalCode) -> [&'static str; 3] {
+    [
+        d.kind.as_str(),
+        d.update_status.as_str(),
+        d.update_reason.as_str(),
+    ]
+}
+
+// Search keys are derived from the display names at insert time, like the enum columns.
+fn search_keys(d: &PostalCode) -> [String; 3] {
+    [
+        search_key(&d.prefecture),
+        search_key(&d.city),
+        search_key(&d.town),
+    ]
+}
+
 async fn bulk_insert(
     pool: &PgPool,
     data: &[PostalCode],
     batch_timestamp: chrono::NaiveDateTime,
 ) -> Result<(), PgError> {
     let chunk_size = 200;
-    let columns: &[&str] = &[
-        "zip_code",
-        "prefecture_id",
-        "city_id",
-        "prefecture",
-        "city",
-        "town",
-    ];
+    let columns = POSTAL_CODE_COLUMNS;
     let mut client = db_client(pool)
         .await
         .expect("Failed to get a client from the pool");
@@ -44,19 +58,47 @@ async fn bulk_insert(
 
         let tx = client.transaction().await?;
 
-        // Collect the parameters for each postal code
+        // Collect the parameters for each postal code (same order as POSTAL_CODE_COLUMNS)
+        let chunk_enum_values: Vec<[&'static str; 3]> = chunk.iter().map(enum_values).collect();
+        let chunk_search_keys: Vec<[String; 3]> = chunk.iter().map(search_keys).collect();
         let insert_data: Vec<Vec<&(dyn tokio_postgres::types::ToSql + Sync)>> = chunk
             .iter()
-            .map(|d| {
-                vec![
-                    to_sql_param(&d.zip_code),
-                    to_sql_param(&d.prefecture_id),
-                    to_sql_param(&d.city_id),
-                    to_sql_param(&d.prefecture),
-                    to_sql_param(&d.city),
-                    to_sql_param(&d.town),
-                ]
-            })
+            .zip(&chunk_enum_values)
+            .zip(&chunk_search_keys)
+            .map(
+                |((d, [kind, update_status, update_reason]), [prefecture, city, town])| {
+                    vec![
+                        to_sql_param(&d.zip_code),
+                        to_sql_param(&d.prefecture_id),
+                        to_sql_param(&d.city_id),
+                        to_sql_param(&d.prefecture),
+                        to_sql_param(&d.city),
+                        to_sql_param(&d.town),
+                        to_sql_param(&d.prefecture_kana),
+                        to_sql_param(&d.city_kana),
+                        to_sql_param(&d.town_kana),
+                        to_sql_param(&d.town_detail),
+                        to_sql_param(&d.chome_range),
+                        to_sql_param(&d.floor),
+                        to_sql_param(kind),
+                        to_sql_param(&d.business_name),
+                        to_sql_param(&d.business_name_kana),
+                        to_sql_param(&d.street_address),
+                        to_sql_param(&d.town_has_multiple_zip_codes),
+                        to_sql_param(&d.numbered_per_koaza),
+                        to_sql_param(&d.has_chome),
+                        to_sql_param(&d.covers_multiple_towns),
+                        to_sql_param(update_status),
+                        to_sql_param(update_reason),
+                        to_sql_param(&d.prefecture_roma),
+                        to_sql_param(&d.city_roma),
+                        to_sql_param(&d.town_roma),
+                        to_sql_param(prefecture),
+                        to_sql_param(city),
+                        to_sql_param(town),
+                    ]
+                },
+            )
             .collect();
 
         let batch_timestamp_utc = batch_timestamp.and_utc();
@@ -145,7 +187,7 @@ pub async fn delete_old_records_postgres(
     batch_timestamp: chrono::NaiveDateTime,
 ) -> Result<u64, PgError> {
     let batch_timestamp_utc = batch_timestamp.and_utc();
-    println!("Deleting records older than {}", batch_timestamp_utc);
+    tlog!("Deleting records older than {}", batch_timestamp_utc);
     let client = pool.get().await.expect("Failed to get client");
     let deleted_rows = client
         .execute(
@@ -153,6 +195,33 @@ pub async fn delete_old_records_postgres(
             &[&batch_timestamp_utc],
         )
         .await?;
-    println!("Old records deleted from Postgres");
+    tlog!("Old records deleted from Postgres");
+    Ok(deleted_rows)
+}
+
+/// Deletes the given rows by primary key; used when applying a monthly DEL file.
+pub async fn delete_postal_codes_postgres(
+    pool: &PgPool,
+    data: &[PostalCode],
+) -> Result<u64, PgError> {
+    let mut client = pool.get().await.expect("Failed to get client");
+    let tx = client.transaction().await?;
+    let stmt = tx
+        .prepare(
+            "DELETE FROM postal_codes
+             WHERE zip_code = $1 AND prefecture_id = $2 AND city = $3 AND town = $4",
+        )
+        .await?;
+    let mut deleted_rows = 0;
+    for d in data {
+        deleted_rows += tx
+            .execute(&stmt, &[&d.zip_code, &d.prefecture_id, &d.city, &d.town])
+            .await?;
+    }
+    tx.commit().await?;
+    tlog!(
+        "Deleted {} rows listed in the diff from Postgres",
+        deleted_rows
+    );
     Ok(deleted_rows)
 }
@@ -2,4 +2,7 @@ pub mod audit;
 pub mod connection;
 pub mod insert_postal_code_mysql;
 pub mod insert_postal_code_postgres;
+pub mod municipality;
 pub mod query_builder;
+pub mod schema;
+pub mod snapshot;
@@ -0,0 +1,380 @@
+// Old -> new `city_id` mappings, detected between snapshots and seeded from a file.
+use crate::tlog;
+use common::audit::live_snapshot;
+use common::models::{MunicipalityChange, MunicipalityChangeSource};
+use deadpool_postgres::Pool as PgPool;
+use mysql_async::{params, prelude::Queryable, Pool as MySqlPool};
+use std::collections::HashSet;
+use tokio_postgres::Error as PgError;
+
+/// Optional CSV of historical mergers: `old_city_id,new_city_id,old_city,new_city,effective_date`.
+pub const SEED_PATH_ENV: &str = "MUNICIPALITY_CHANGES_SEED_PATH";
+
+#[derive(serde::Deserialize)]
+struct SeedRow {
+    old_city_id: String,
+    new_city_id: String,
+    #[serde(default)]
+    old_city: String,
+    #[serde(default)]
+    new_city: String,
+    #[serde(default)]
+    effective_date: String,
+}
+
+fn is_city_id(value: &str) -> bool {
+    (5..=6).contains(&value.len()) && value.bytes().all(|b| b.is_ascii_digit())
+}
+
+/// Parses the seed CSV (header row required, `#` starts a comment line).
+pub fn parse_seed<R: std::io::Read>(reader: R) -> Result<Vec<MunicipalityChange>, String> {
+    let mut csv_reader = csv::ReaderBuilder::new()
+        .comment(Some(b'#'))
+        .trim(csv::Trim::All)
+        .from_reader(reader);
+    let mut changes = Vec::new();
+    for (index, row) in csv_reader.deserialize::<SeedRow>().enumerate() {
+        let line = index + 2;
+        let row = row.map_err(|e| format!("line {line}: {e}"))?;
+        if !is_city_id(&row.old_city_id) || !is_city_id(&row.new_city_id) {
+            return Err(format!(
+                "line {line}: city ids must be 5 or 6 digit JIS codes"
+            ));
+        }
+        let effective_date = match row.effective_date.as_str() {
+            "" => None,
+            date => {
+                chrono::NaiveDate::parse_from_str(date, "%Y-%m-%d")
+                    .map_err(|_| format!("line {line}: effective_date must be YYYY-MM-DD"))?;
+                Some(date.to_string())
+            }
+        };
+        changes.push(MunicipalityChange {
+            old_city_id: row.old_city_id,
+            new_city_id: row.new_city_id,
+            old_city: row.old_city,
+            new_city: row.new_city,
+            effective_date,
+            data_version: None,
+            source: MunicipalityChangeSource::Seed,
+            shared_zip_codes: 0,
+        });
+    }
+    Ok(changes)
+}
+
+fn load_seed_from_env() -> Result<Vec<MunicipalityChange>, String> {
+    let Some(path) = std::env::var(SEED_PATH_ENV)
+        .ok()
+        .filter(|v| !v.trim().is_empty())
+    else {
+        return Ok(Vec::new());
+    };
+    let file = std::fs::File::open(&path).map_err(|e| format!("{SEED_PATH_ENV}={path}: {e}"))?;
+    parse_seed(file).map_err(|e| format!("{SEED_PATH_ENV}={path}: {e}"))
+}
+
+/// City ids of the previous dataset that the new one no longer has.
+pub fn retired_city_ids(previous: &[String], current: &[String]) -> Vec<String> {
+    let current: HashSet<&String> = current.iter().collect();
+    previous
+        .iter()
+        .filter(|city_id| !current.contains(city_id))
+        .cloned()
+        .collect()
+}
+
+fn parse_effective_date(change: &MunicipalityChange) -> Option<chrono::NaiveDate> {
+    change
+        .effective_date
+        .as_deref()
+        .and_then(|date| chrono::NaiveDate::parse_from_str(date, "%Y-%m-%d").ok())
+}
+
+pub async fn ensure_municipality_changes_table_postgres(pool: &PgPool) -> Result<(), PgError> {
+    let client = pool.get().await.expect("Failed to get client");
+    client
+        .batch_execute(
+            r#"
+            CREATE TABLE IF NOT EXISTS municipality_changes (
+                old_city_id VARCHAR(10) NOT NULL,
+                new_city_id VARCHAR(10) NOT NULL,
+                old_city VARCHAR(50) NOT NULL DEFAULT '',
+                new_city VARCHAR(50) NOT NULL DEFAULT '',
+                effective_date DATE,
+                data_version VARCHAR(32),
+                source VARCHAR(16) NOT NULL,
+                shared_zip_codes BIGINT NOT NULL DEFAULT 0,
+                created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+                PRIMARY KEY (old_city_id, new_city_id)
+            );
+        "#,
+        )
+        .await?;
+    Ok(())
+}
+
+// Seed entries are curated, so they overwrite what detection recorded for the same pair.
+async fn seed_municipality_changes_postgres(
+    pool: &PgPool,
+    changes: &[MunicipalityChange],
+) -> Result<(), PgError> {
+    let client = pool.get().await.expect("Failed to get client");
+    for change in changes {
+        client
+            .execute(
+                "INSERT INTO municipality_changes
+                    (old_city_id, new_city_id, old_city, new_city, effective_date, source)
+                 VALUES ($1, $2, $3, $4, $5, $6)
+                 ON CONFLICT (old_city_id, new_city_id) DO UPDATE SET
+                    old_city = EXCLUDED.old_city,
+                    new_city = EXCLUDED.new_city,
+                    effective_date = EXCLUDED.effective_date,
+                    source = EXCLUDED.source",
+                &[
+                    &change.old_city_id,
+                    &change.new_city_id,
+                    &change.old_city,
+                    &change.new_city,
+                    &parse_effective_date(change),
+                    &change.source.as_str(),
+                ],
+            )
+            .await?;
+    }
+    Ok(())
+}
+
+async fn detect_municipality_changes_postgres(
+    pool: &PgPool,
+    data_version: &str,
+) -> Result<u64, PgError> {
+    let client = pool.get().await.expect("Failed to get client");
+    // The current run's audit row is written last, so the newest one is the previous run.
+    let previous = client
+        .query_opt(
+            "SELECT data_version, status, source_url FROM data_update_audits
+             WHERE database_type = 'postgres' AND status IN ('success', 'rollback')
+             ORDER BY id DESC LIMIT 1",
+            &[],
+        )
+        .await?
+        .and_then(|row| live_snapshot(row.get(0), row.get(1), row.get(2)));
+    let Some(previous) = previous else {
+        return Ok(0);
+    };
+
+    let mut city_ids: Vec<Vec<String>> = Vec::new();
+    for version in [previous.as_str(), data_version] {
+        let rows = client
+            .query(
+                "SELECT DISTINCT city_id FROM postal_codes_snapshots WHERE data_version = $1",
+                &[&version],
+            )
+            .await?;
+        city_ids.push(rows.iter().map(|row| row.get(0)).collect());
+    }
+    let retired = retired_city_ids(&city_ids[0], &city_ids[1]);
+    if retired.is_empty() {
+        return Ok(0);
+    }
+
+    client
+        .execute(
+            "INSERT INTO municipality_changes
+                (old_city_id, new_city_id, old_city, new_city, data_version, source, shared_zip_codes)
+             SELECT o.city_id, n.city_id, MIN(o.city), MIN(n.city), $2, 'detected',
+                    COUNT(DISTINCT o.zip_code)
+             FROM postal_codes_snapshots o
+             JOIN postal_codes_snapshots n ON n.data_version = $2 AND n.zip_code = o.zip_code
+             WHERE o.data_version = $1 AND o.city_id = ANY($3)
+             GROUP BY o.city_id, n.city_id
+             ON CONFLICT (old_city_id, new_city_id) DO NOTHING",
+            &[&previous, &data_version, &retired],
+        )
+        .await
+}
+
+/// Loads the seed file and records the codes retired by `data_version`, whose snapshot must
+/// already exist. Failures here never fail the import.
+pub async fn track_municipality_changes_postgres(
+    pool: &PgPool,
+    data_version: &str,
+) -> Result<(), String> {
+    ensure_municipality_changes_table_postgres(pool)
+        .await
+        .map_err(|e| format!("ensure_table: {e}"))?;
+    let seed = load_seed_from_env()?;
+    seed_municipality_changes_postgres(pool, &seed)
+        .await
+        .map_err(|e| format!("seed: {e}"))?;
+    let detected = detect_municipality_changes_postgres(pool, data_version)
+        .await
+        .map_err(|e| format!("detect: {e}"))?;
+    if detected > 0 {
+        tlog!("Recorded {} municipality code changes.", detected);
+    }
+    Ok(())
+}
+
+pub async fn ensure_municipality_changes_table_mysql(
+    pool: &MySqlPool,
+) -> Result<(), mysql_async::Error> {
+    let mut conn = pool.get_conn().await?;
+    conn.query_drop(
+        r"CREATE TABLE IF NOT EXISTS municipality_changes (
+            old_city_id VARCHAR(10) NOT NULL,
+            new_city_id VARCHAR(10) NOT NULL,
+            old_city VARCHAR(50) NOT NULL DEFAULT '',
+            new_city VARCHAR(50) NOT NULL DEFAULT '',
+            effective_date DATE,
+            data_version VARCHAR(32),
+            source VARCHAR(16) NOT NULL,
+            shared_zip_codes BIGINT NOT NULL DEFAULT 0,
+            created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            PRIMARY KEY (old_city_id, new_city_id)
+        ) ENGINE=InnoDB DEFAULT CHARSET=utf8mb4 COLLATE=utf8mb4_general_ci",
+    )
+    .await?;
+    Ok(())
+}
+
+async fn seed_municipality_changes_mysql(
+    pool: &MySqlPool,
+    changes: &[MunicipalityChange],
+) -> Result<(), mysql_async::Error> {
+    let mut conn = pool.get_conn().await?;
+    for change in changes {
+        conn.exec_drop(
+            "INSERT INTO municipality_changes
+                (old_city_id, new_city_id, old_city, new_city, effective_date, source)
+             VALUES (:old_city_id, :new_city_id, :old_city, :new_city, :effective_date, :source)
+             ON DUPLICATE KEY UPDATE
+                old_city = VALUES(old_city),
+                new_city = VALUES(new_city),
+                effective_date = VALUES(effective_date),
+                source = VALUES(source)",
+            params! {
+                "old_city_id" => &change.old_city_id,
+                "new_city_id" => &change.new_city_id,
+                "old_city" => &change.old_city,
+                "new_city" => &change.new_city,
+                "effective_date" => parse_effective_date(change),
+                "source" => change.source.as_str(),
+            },
+        )
+        .await?;
+    }
+    Ok(())
+}
+
+async fn detect_municipality_changes_mysql(
+    pool: &MySqlPool,
+    data_version: &str,
+) -> Result<u64, mysql_async::Error> {
+    let mut conn = pool.get_conn().await?;
+    let previous = conn
+        .query_first::<(String, String, String), _>(
+            "SELECT data_version, status, source_url FROM data_update_audits
+             WHERE database_type = 'mysql' AND status IN ('success', 'rollback')
+             ORDER BY id DESC LIMIT 1",
+        )
+        .await?
+        .and_then(|(version, status, source_url)| live_snapshot(&version, &status, &source_url));
+    let Some(previous) = previous else {
+        return Ok(0);
+    };
+
+    let mut city_ids = Vec::new();
+    for version in [previous.as_str(), data_version] {
+        city_ids.push(
+            conn.exec::<String, _, _>(
+                "SELECT DISTINCT city_id FROM postal_codes_snapshots WHERE data_version = :data_version",
+                params! { "data_version" => version },
+            )
+            .await?,
+        );
+    }
+    let retired = retired_city_ids(&city_ids[0], &city_ids[1]);
+    if retired.is_empty() {
+        return Ok(0);
+    }
+
+    let placeholders = vec!["?"; retired.len()].join(", ");
+    let mut values: Vec<mysql_async::Value> = vec![
+        data_version.into(),
+        data_version.into(),
+        previous.as_str().into(),
+    ];
+    values.extend(retired.iter().map(|city_id| city_id.as_str().into()));
+    conn.exec_drop(
+        format!(
+            "INSERT IGNORE INTO municipality_changes
+                (old_city_id, new_city_id, old_city, new_city, data_version, source, shared_zip_codes)
+             SELECT o.city_id, n.city_id, MIN(o.city), MIN(n.city), ?, 'detected',
+                    COUNT(DISTINCT o.zip_code)
+             FROM postal_codes_snapshots o
+             JOIN postal_codes_snapshots n ON n.data_version = ? AND n.zip_code = o.zip_code
+             WHERE o.data_version = ? AND o.city_id IN ({placeholders})
+             GROUP BY o.city_id, n.city_id"
+        ),
+        mysql_async::Params::Positional(values),
+    )
+    .await?;
+    Ok(conn.affected_rows())
+}
+
+/// MySQL counterpart of `track_municipality_changes_postgres`.
+pub async fn track_municipality_changes_mysql(
+    pool: &MySqlPool,
+    data_version: &str,
+) -> Result<(), String> {
+    ensure_municipality_changes_table_mysql(pool)
+        .await
+        .map_err(|e| format!("ensure_table: {e}"))?;
+    let seed = load_seed_from_env()?;
+    seed_municipality_changes_mysql(pool, &seed)
+        .await
+        .map_err(|e| format!("seed: {e}"))?;
+    let detected = detect_municipality_changes_mysql(pool, data_version)
+        .await
+        .map_err(|e| format!("detect: {e}"))?;
+    if detected > 0 {
+        tlog!("Recorded {} municipality code changes.", detected);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_seed, retired_city_ids};
+    use common::models::MunicipalityChangeSource;
+
+    #[test]
+    fn parse_seed_reads_rows_and_skips_comments() {
+        let csv = "old_city_id,new_city_id,old_city,new_city,effective_date\n\
+                   # 2006 merger\n\
+                   01234,01100,旧町, 新市 ,2006-03-27\n\
+                   01235,01100,,,\n";
+        let changes = parse_seed(csv.as_bytes()).unwrap();
+        assert_eq!(changes.len(), 2);
+        assert_eq!(changes[0].new_city, "新市");
+        assert_eq!(changes[0].effective_date.as_deref(), Some("2006-03-27"));
+        assert_eq!(changes[0].source, MunicipalityChangeSource::Seed);
+        assert_eq!(changes[1].effective_date, None);
+    }
+
+    #[test]
+    fn parse_seed_rejects_malformed_rows() {
+        let header = "old_city_id,new_city_id,old_city,new_city,effective_date\n";
+        assert!(parse_seed(format!("{header}abc,01100,,,\n").as_bytes()).is_err());
+        assert!(parse_seed(format!("{header}01234,01100,,,2006/03/27\n").as_bytes()).is_err());
+    }
+
+    #[test]
+    fn retired_city_ids_lists_codes_missing_from_the_new_dataset() {
+        let previous = vec!["01100".to_string(), "01234".to_string()];
+        let current = vec!["01100".to_string(), "01300".to_string()];
+        assert_eq!(retired_city_ids(&previous, &current), vec!["01234"]);
+    }
+}
@@ -1,3 +1,5 @@
+use crate::db::schema::POSTAL_CODE_KEY_COLUMNS;
+
 pub fn build_pg_bulk_insert_query<'a>(
     table_name: &str,
     columns: &[&str],
@@ -33,13 +35,41 @@ pub fn build_pg_bulk_insert_query<'a>(
     }
 
     query.push_str(&placeholders.join(", "));
-    query.push_str(
-        " ON CONFLICT (zip_code, prefecture_id, city, town)
+    let update_set: Vec<String> = columns
+        .iter()
+        .filter(|column| !POSTAL_CODE_KEY_COLUMNS.contains(column))
+        .map(|column| format!("{column} = EXCLUDED.{column}"))
+        .collect();
+    query.push_str(&format!(
+        " ON CONFLICT ({})
         DO UPDATE SET
-        prefecture = EXCLUDED.prefecture,
-        town = EXCLUDED.town,
+        {},
         updated_at = EXCLUDED.updated_at;",
-    );
+        POSTAL_CODE_KEY_COLUMNS.join(", "),
+        update_set.join(", ")
+    ));
     // println!("Generated Query: {}", query);
     (query, all_params)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::build_pg_bulk_insert_query;
+
+    #[test]
+    fn bulk_insert_query_updates_non_key_columns_on_conflict() {
+        let zip_code = "1000001".to_string();
+        let prefecture = "東京都".to_string();
+        let row: Vec<&(dyn tokio_postgres::types::ToSql + Sync)> = vec![&zip_code, &prefecture];
+        let (query, params) =
+            build_pg_bulk_insert_query("postal_codes", &["zip_code", "prefecture"], &[row]);
+
+        assert!(query.starts_with(
+            "INSERT INTO postal_codes (zip_code, prefecture, created_at, updated_at) VALUES ($1, $2, $3, $3)"
+        ));
+        assert!(query.contains("ON CONFLICT (zip_code, prefecture_id, city, town)"));
+        assert!(query.contains("prefecture = EXCLUDED.prefecture"));
+        assert!(!query.contains("zip_code = EXCLUDED.zip_code"));
+        assert_eq!(params.len(), 2);
+    }
+}
@@ -0,0 +1,335 @@
+use crate::tlog;
+use common::normalize::search_key;
+use deadpool_postgres::Pool as PgPool;
+use mysql_async::{params, prelude::Queryable, Pool as MySqlPool};
+use std::collections::BTreeMap;
+use tokio_postgres::Error as PgError;
+
+/// Data columns of `postal_codes`, in the order the insert helpers bind them.
+pub const POSTAL_CODE_COLUMNS: &[&str] = &[
+    "zip_code",
+    "prefecture_id",
+    "city_id",
+    "prefecture",
+    "city",
+    "town",
+    "prefecture_kana",
+    "city_kana",
+    "town_kana",
+    "town_detail",
+    "chome_range",
+    "floor",
+    "kind",
+    "business_name",
+    "business_name_kana",
+    "street_address",
+    "town_has_multiple_zip_codes",
+    "numbered_per_koaza",
+    "has_chome",
+    "covers_multiple_towns",
+    "update_status",
+    "update_reason",
+    "prefecture_roma",
+    "city_roma",
+    "town_roma",
+    "prefecture_normalized",
+    "city_normalized",
+    "town_normalized",
+];
+
+/// Primary key of `postal_codes`; used as the upsert conflict target.
+pub const POSTAL_CODE_KEY_COLUMNS: &[&str] = &["zip_code", "prefecture_id", "city", "town"];
+
+pub fn postal_code_column_list() -> String {
+    POSTAL_CODE_COLUMNS.join(", ")
+}
+
+/// Columns added to `postal_codes` after the initial schema.
+/// Existing databases are migrated in place, so every entry must carry a default.
+pub const POSTAL_CODE_EXTRA_COLUMNS: &[(&str, &str)] = &[
+    ("prefecture_kana", "VARCHAR(64) NOT NULL DEFAULT ''"),
+    ("city_kana", "VARCHAR(100) NOT NULL DEFAULT ''"),
+    ("town_kana", "VARCHAR(1000) NOT NULL DEFAULT ''"),
+    ("town_detail", "VARCHAR(500) NOT NULL DEFAULT ''"),
+    ("chome_range", "VARCHAR(100) NOT NULL DEFAULT ''"),
+    ("floor", "VARCHAR(32) NOT NULL DEFAULT ''"),
+    ("kind", "VARCHAR(16) NOT NULL DEFAULT 'residential'"),
+    ("business_name", "VARCHAR(200) NOT NULL DEFAULT ''"),
+    ("business_name_kana", "VARCHAR(400) NOT NULL DEFAULT ''"),
+    ("street_address", "VARCHAR(200) NOT NULL DEFAULT ''"),
+    (
+        "town_has_multiple_zip_codes",
+        "BOOLEAN NOT NULL DEFAULT FALSE",
+    ),
+    ("numbered_per_koaza", "BOOLEAN NOT NULL DEFAULT FALSE"),
+    ("has_chome", "BOOLEAN NOT NULL DEFAULT FALSE"),
+    ("covers_multiple_towns", "BOOLEAN NOT NULL DEFAULT FALSE"),
+    ("update_status", "VARCHAR(16) NOT NULL DEFAULT 'unchanged'"),
+    ("update_reason", "VARCHAR(32) NOT NULL DEFAULT 'none'"),
+    ("prefecture_roma", "VARCHAR(64) NOT NULL DEFAULT ''"),
+    ("city_roma", "VARCHAR(100) NOT NULL DEFAULT ''"),
+    ("town_roma", "VARCHAR(500) NOT NULL DEFAULT ''"),
+    ("prefecture_normalized", "VARCHAR(64) NOT NULL DEFAULT ''"),
+    ("city_normalized", "VARCHAR(100) NOT NULL DEFAULT ''"),
+    ("town_normalized", "VARCHAR(500) NOT NULL DEFAULT ''"),
+];
+
+/// Columns added to `data_update_audits` after the initial schema.
+pub const AUDIT_EXTRA_COLUMNS: &[(&str, &str)] = &[
+    ("diff_month", "CHAR(4)"),
+    ("source_sha256", "CHAR(64)"),
+    ("dataset_fingerprint", "CHAR(64)"),
+];
+
+// Snapshots mirror postal_codes so rollback can restore every column.
+const POSTAL_CODE_TABLES: &[&str] = &["postal_codes", "postal_codes_snapshots"];
+
+async fn add_missing_columns_postgres(
+    client: &deadpool_postgres::Client,
+    table: &str,
+    columns: &[(&str, &str)],
+) -> Result<(), PgError> {
+    for (column, definition) in columns {
+        client
+            .batch_execute(&format!(
+                "ALTER TABLE {table} ADD COLUMN IF NOT EXISTS {column} {definition}"
+            ))
+            .await?;
+    }
+    Ok(())
+}
+
+async fn add_missing_columns_mysql(
+    conn: &mut mysql_async::Conn,
+    table: &str,
+    columns: &[(&str, &str)],
+) -> Result<(), mysql_async::Error> {
+    for (column, definition) in columns {
+        // MySQL 8 has no ADD COLUMN IF NOT EXISTS, so check information_schema first.
+        let exists = conn
+            .exec_first::<i64, _, _>(
+                "SELECT COUNT(*) FROM information_schema.columns
+                 WHERE table_schema = DATABASE() AND table_name = :table_name AND column_name = :column_name",
+                params! {
+                    "table_name" => table,
+                    "column_name" => *column,
+                },
+            )
+            .await?
+            .unwrap_or(0)
+            > 0;
+        if !exists {
+            conn.query_drop(format!(
+                "ALTER TABLE {table} ADD COLUMN {column} {definition}"
+            ))
+            .await?;
+        }
+    }
+    Ok(())
+}
+
+pub async fn ensure_postal_code_columns_postgres(pool: &PgPool) -> Result<(), PgError> {
+    let client = pool.get().await.expect("Failed to get client");
+    for table in POSTAL_CODE_TABLES {
+        add_missing_columns_postgres(&client, table, POSTAL_CODE_EXTRA_COLUMNS).await?;
+        backfill_search_keys_postgres(&client, table).await?;
+    }
+    Ok(())
+}
+
+pub async fn ensure_postal_code_columns_mysql(pool: &MySqlPool) -> Result<(), mysql_async::Error> {
+    let mut conn = pool.get_conn().await?;
+    for table in POSTAL_CODE_TABLES {
+        add_missing_columns_mysql(&mut conn, table, POSTAL_CODE_EXTRA_COLUMNS).await?;
+        backfill_search_keys_mysql(&mut conn, table).await?;
+    }
+    Ok(())
+}
+
+/// Columns matched by `/postal_codes/search`; each gets a trigram index on Postgres.
+/// They hold `common::normalize::search_key` of the display names.
+pub const SEARCH_COLUMNS: &[&str] = &[
+    "prefecture_normalized",
+    "city_normalized",
+    "town_normalized",
+];
+
+/// Display names and the `SEARCH_COLUMNS` key derived from each.
+const SEARCH_KEY_SOURCES: &[(&str, &str)] = &[
+    ("prefecture", "prefecture_normalized"),
+    ("city", "city_normalized"),
+    ("town", "town_normalized"),
+];
+
+// Rows written before the search keys existed have an empty key, or the display name copied in
+// by an earlier version of this migration. Neither matches a folded query ("霞が関" is searched
+// as "霞ケ関"), and incremental or unchanged cycles never rewrite those rows, so the keys are
+// folded here with the same `search_key` the insert helpers use. Snapshots are never rewritten
+// at all, and `as_of` searches match on their keys, so they are backfilled the same way.
+fn backfill_candidates_condition(column: &str, key_column: &str) -> String {
+    format!("{column} <> '' AND ({key_column} = '' OR {key_column} = {column})")
+}
+
+/// `(name, key)` pairs to write, from the distinct `(name, stored key)` pairs of the rows
+/// matching `backfill_candidates_condition`. Names whose stored key is already right are left out.
+pub fn search_key_backfill(stored: Vec<(String, String)>) -> Vec<(String, String)> {
+    let mut keys = BTreeMap::new();
+    for (name, stored_key) in stored {
+        let key = search_key(&name);
+        if key != stored_key {
+            keys.insert(name, key);
+        }
+    }
+    keys.into_iter().collect()
+}
+
+async fn backfill_search_keys_postgres(
+    client: &deadpool_postgres::Client,
+    table: &str,
+) -> Result<(), PgError> {
+    for (column, key_column) in SEARCH_KEY_SOURCES {
+        let condition = backfill_candidates_condition(column, key_column);
+        let rows = client
+            .query(
+                &format!("SELECT DISTINCT {column}, {key_column} FROM {table} WHERE {condition}"),
+                &[],
+            )
+            .await?;
+        let backfill =
+            search_key_backfill(rows.iter().map(|row| (row.get(0), row.get(1))).collect());
+        if backfill.is_empty() {
+            continue;
+        }
+        let (names, keys): (Vec<String>, Vec<String>) = backfill.into_iter().unzip();
+        let updated = client
+            .execute(
+                &format!(
+                    "UPDATE {table} SET {key_column} = backfill.search_key
+                     FROM UNNEST($1::TEXT[], $2::TEXT[]) AS backfill(name, search_key)
+                     WHERE {table}.{column} = backfill.name
+                       AND ({table}.{key_column} = '' OR {table}.{key_column} = {table}.{column})"
+                ),
+                &[&names, &keys],
+            )
+            .await?;
+        tlog!("Backfilled {} of {}: {} rows", key_column, table, updated);
+    }
+    Ok(())
+}
+
+async fn backfill_search_keys_mysql(
+    conn: &mut mysql_async::Conn,
+    table: &str,
+) -> Result<(), mysql_async::Error> {
+    for (column, key_column) in SEARCH_KEY_SOURCES {
+        let condition = backfill_candidates_condition(column, key_column);
+        let stored: Vec<(String, String)> = conn
+            .query(format!(
+                "SELECT DISTINCT {column}, {key_column} FROM {table} WHERE {condition}"
+            ))
+            .await?;
+        let backfill = search_key_backfill(stored);
+        if backfill.is_empty() {
+            continue;
+        }
+        conn.query_drop(
+            "CREATE TEMPORARY TABLE IF NOT EXISTS search_key_backfill (
+                 name VARCHAR(1000) NOT NULL,
+                 search_key VARCHAR(1000) NOT NULL
+             )",
+        )
+        .await?;
+        conn.query_drop("TRUNCATE TABLE search_key_backfill")
+            .await?;
+        conn.exec_batch(
+            "INSERT INTO search_key_backfill (name, search_key) VALUES (:name, :search_key)",
+            backfill
+                .iter()
+                .map(|(name, key)| params! { "name" => name, "search_key" => key }),
+        )
+        .await?;
+        conn.query_drop(format!(
+            "UPDATE {table} JOIN search_key_backfill backfill ON {table}.{column} = backfill.name
+             SET {table}.{key_column} = backfill.search_key
+             WHERE {table}.{key_column} = '' OR {table}.{key_column} = {table}.{column}"
+        ))
+        .await?;
+        tlog!(
+            "Backfilled {} of {}: {} rows",
+            key_column,
+            table,
+            conn.affected_rows()
+        );
+    }
+    Ok(())
+}
+
+/// Creates the `pg_trgm` GIN indexes that serve `LIKE '%term%'` without a sequential scan.
+/// The extension needs CREATE privilege on the database; init-postgres.sql creates it up front.
+pub async fn ensure_search_indexes_postgres(pool: &PgPool) -> Result<(), PgError> {
+    let client = pool.get().await.expect("Failed to get client");
+    client
+        .batch_execute("CREATE EXTENSION IF NOT EXISTS pg_trgm")
+        .await?;
+    // Indexes on the display names predate the search keys and no longer serve any query.
+    for column in ["prefecture", "city", "town"] {
+        client
+            .batch_execute(&format!(
+                "DROP INDEX IF EXISTS idx_postal_codes_{column}_trgm"
+            ))
+            .await?;
+    }
+    for column in SEARCH_COLUMNS {
+        client
+            .batch_execute(&format!(
+                "CREATE INDEX IF NOT EXISTS idx_postal_codes_{column}_trgm
+                 ON postal_codes USING gin ({column} gin_trgm_ops)"
+            ))
+            .await?;
+    }
+    Ok(())
+}
+
+pub async fn ensure_audit_columns_postgres(pool: &PgPool) -> Result<(), PgError> {
+    let client = pool.get().await.expect("Failed to get client");
+    add_missing_columns_postgres(&client, "data_update_audits", AUDIT_EXTRA_COLUMNS).await
+}
+
+pub async fn ensure_audit_columns_mysql(pool: &MySqlPool) -> Result<(), mysql_async::Error> {
+    let mut conn = pool.get_conn().await?;
+    add_missing_columns_mysql(&mut conn, "data_update_audits", AUDIT_EXTRA_COLUMNS).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{search_key_backfill, POSTAL_CODE_TABLES};
+    use common::normalize::search_key;
+
+    fn pair(name: &str, key: &str) -> (String, String) {
+        (name.to_string(), key.to_string())
+    }
+
+    #[test]
+    fn search_key_backfill_folds_empty_and_copied_keys() {
+        let stored = vec![
+            pair("霞が関", ""),
+            // Copied in by the first version of the migration.
+            pair("霞が関", "霞が関"),
+            pair("宮\u{FA11}", "宮\u{FA11}"),
+            // Already the folded key; nothing to write.
+            pair("北海道", "北海道"),
+        ];
+        assert_eq!(
+            search_key_backfill(stored),
+            vec![pair("宮\u{FA11}", "宮崎"), pair("霞が関", "霞ケ関")]
+        );
+    }
+
+    #[test]
+    fn snapshots_taken_without_keys_become_searchable() {
+        assert!(POSTAL_CODE_TABLES.contains(&"postal_codes_snapshots"));
+        // A snapshot row written before the keys existed, searched as "霞ヶ関" with as_of.
+        let backfill = search_key_backfill(vec![pair("霞が関", "")]);
+        assert_eq!(backfill, vec![pair("霞が関", &search_key("霞ヶ関"))]);
+    }
+}
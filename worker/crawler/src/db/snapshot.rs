@@ -0,0 +1,253 @@
+// Maintenance of postal_codes_snapshots: listing, pruning and exporting versions.
+use crate::db::schema::{POSTAL_CODE_COLUMNS, POSTAL_CODE_EXTRA_COLUMNS};
+use deadpool_postgres::Pool as PgPool;
+use mysql_async::{params, prelude::Queryable, Pool as MySqlPool};
+use serde::Serialize;
+use tokio_postgres::Error as PgError;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SnapshotVersion {
+    pub data_version: String,
+    pub row_count: i64,
+}
+
+/// Versions beyond the newest `keep`, except `live`: the snapshot postal_codes currently
+/// holds, which stays restorable however old it is. `versions` must be sorted newest
+/// first, as the list functions return them.
+pub fn versions_to_prune(
+    versions: &[SnapshotVersion],
+    keep: usize,
+    live: Option<&str>,
+) -> Vec<SnapshotVersion> {
+    versions
+        .iter()
+        .skip(keep)
+        .filter(|version| Some(version.data_version.as_str()) != live)
+        .cloned()
+        .collect()
+}
+
+fn is_boolean_column(column: &str) -> bool {
+    POSTAL_CODE_EXTRA_COLUMNS
+        .iter()
+        .any(|(name, definition)| *name == column && definition.starts_with("BOOLEAN"))
+}
+
+/// Rows in postal_codes, which `crawler verify` compares with the audits and snapshots.
+pub async fn postal_code_count_postgres(pool: &PgPool) -> Result<i64, PgError> {
+    let client = pool.get().await.expect("Failed to get client");
+    let row = client
+        .query_one("SELECT COUNT(*)::BIGINT FROM postal_codes", &[])
+        .await?;
+    Ok(row.get(0))
+}
+
+pub async fn list_snapshots_postgres(pool: &PgPool) -> Result<Vec<SnapshotVersion>, PgError> {
+    let client = pool.get().await.expect("Failed to get client");
+    let rows = client
+        .query(
+            "SELECT data_version, COUNT(*)::BIGINT FROM postal_codes_snapshots
+             GROUP BY data_version
+             ORDER BY data_version DESC",
+            &[],
+        )
+        .await?;
+    Ok(rows
+        .iter()
+        .map(|row| SnapshotVersion {
+            data_version: row.get(0),
+            row_count: row.get(1),
+        })
+        .collect())
+}
+
+pub async fn snapshot_row_count_postgres(
+    pool: &PgPool,
+    data_version: &str,
+) -> Result<i64, PgError> {
+    let client = pool.get().await.expect("Failed to get client");
+    let row = client
+        .query_one(
+            "SELECT COUNT(*)::BIGINT FROM postal_codes_snapshots WHERE data_version = $1",
+            &[&data_version],
+        )
+        .await?;
+    Ok(row.get(0))
+}
+
+pub async fn delete_snapshots_postgres(
+    pool: &PgPool,
+    data_versions: &[String],
+) -> Result<u64, PgError> {
+    let client = pool.get().await.expect("Failed to get client");
+    client
+        .execute(
+            "DELETE FROM postal_codes_snapshots WHERE data_version = ANY($1)",
+            &[&data_versions],
+        )
+        .await
+}
+
+/// Every row of a snapshot as text, in `POSTAL_CODE_COLUMNS` order and primary key order.
+pub async fn snapshot_rows_postgres(
+    pool: &PgPool,
+    data_version: &str,
+) -> Result<Vec<Vec<Option<String>>>, PgError> {
+    let client = pool.get().await.expect("Failed to get client");
+    let columns = POSTAL_CODE_COLUMNS
+        .iter()
+        .map(|column| format!("{column}::TEXT"))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let rows = client
+        .query(
+            &format!(
+                "SELECT {columns} FROM postal_codes_snapshots
+                 WHERE data_version = $1
+                 ORDER BY zip_code, prefecture_id, city, town"
+            ),
+            &[&data_version],
+        )
+        .await?;
+    Ok(rows
+        .iter()
+        .map(|row| (0..row.len()).map(|i| row.get(i)).collect())
+        .collect())
+}
+
+/// Rows in postal_codes, which `crawler verify` compares with the audits and snapshots.
+pub async fn postal_code_count_mysql(pool: &MySqlPool) -> Result<i64, mysql_async::Error> {
+    let mut conn = pool.get_conn().await?;
+    let count = conn
+        .query_first::<i64, _>("SELECT COUNT(*) FROM postal_codes")
+        .await?;
+    Ok(count.unwrap_or(0))
+}
+
+pub async fn list_snapshots_mysql(
+    pool: &MySqlPool,
+) -> Result<Vec<SnapshotVersion>, mysql_async::Error> {
+    let mut conn = pool.get_conn().await?;
+    conn.query_map(
+        "SELECT data_version, COUNT(*) FROM postal_codes_snapshots
+         GROUP BY data_version
+         ORDER BY data_version DESC",
+        |(data_version, row_count)| SnapshotVersion {
+            data_version,
+            row_count,
+        },
+    )
+    .await
+}
+
+pub async fn snapshot_row_count_mysql(
+    pool: &MySqlPool,
+    data_version: &str,
+) -> Result<i64, mysql_async::Error> {
+    let mut conn = pool.get_conn().await?;
+    let count = conn
+        .exec_first::<i64, _, _>(
+            "SELECT COUNT(*) FROM postal_codes_snapshots WHERE data_version = :data_version",
+            params! { "data_version" => data_version },
+        )
+        .await?;
+    Ok(count.unwrap_or(0))
+}
+
+pub async fn delete_snapshots_mysql(
+    pool: &MySqlPool,
+    data_versions: &[String],
+) -> Result<u64, mysql_async::Error> {
+    let mut conn = pool.get_conn().await?;
+    let mut deleted_rows = 0;
+    for data_version in data_versions {
+        conn.exec_drop(
+            "DELETE FROM postal_codes_snapshots WHERE data_version = :data_version",
+            params! { "data_version" => data_version },
+        )
+        .await?;
+        deleted_rows += conn.affected_rows();
+    }
+    Ok(deleted_rows)
+}
+
+/// Same as `snapshot_rows_postgres`; booleans are spelled `true` / `false` like PostgreSQL.
+pub async fn snapshot_rows_mysql(
+    pool: &MySqlPool,
+    data_version: &str,
+) -> Result<Vec<Vec<Option<String>>>, mysql_async::Error> {
+    let mut conn = pool.get_conn().await?;
+    let columns = POSTAL_CODE_COLUMNS
+        .iter()
+        .map(|column| {
+            if is_boolean_column(column) {
+                format!("IF({column}, 'true', 'false')")
+            } else {
+                format!("CAST({column} AS CHAR)")
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(", ");
+    let rows: Vec<mysql_async::Row> = conn
+        .exec(
+            format!(
+                "SELECT {columns} FROM postal_codes_snapshots
+                 WHERE data_version = :data_version
+                 ORDER BY zip_code, prefecture_id, city, town"
+            ),
+            params! { "data_version" => data_version },
+        )
+        .await?;
+    Ok(rows
+        .into_iter()
+        .map(|mut row| {
+            (0..row.len())
+                .map(|i| row.take::<Option<String>, _>(i).flatten())
+                .collect()
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_boolean_column, versions_to_prune, SnapshotVersion};
+
+    fn version(data_version: &str) -> SnapshotVersion {
+        SnapshotVersion {
+            data_version: data_version.to_string(),
+            row_count: 1,
+        }
+    }
+
+    #[test]
+    fn versions_to_prune_keeps_the_newest() {
+        let versions = vec![version("v3"), version("v2"), version("v1")];
+        assert_eq!(
+            versions_to_prune(&versions, 1, None),
+            vec![version("v2"), version("v1")]
+        );
+        assert!(versions_to_prune(&versions, 3, None).is_empty());
+        assert!(versions_to_prune(&versions, 5, None).is_empty());
+    }
+
+    #[test]
+    fn versions_to_prune_never_drops_the_live_snapshot() {
+        // After `crawler rollback v1`, postal_codes holds v1.
+        let versions = vec![version("v3"), version("v2"), version("v1")];
+        assert_eq!(
+            versions_to_prune(&versions, 1, Some("v1")),
+            vec![version("v2")]
+        );
+        assert_eq!(
+            versions_to_prune(&versions, 0, Some("v3")),
+            vec![version("v2"), version("v1")]
+        );
+    }
+
+    #[test]
+    fn boolean_columns_come_from_the_schema() {
+        assert!(is_boolean_column("has_chome"));
+        assert!(!is_boolean_column("zip_code"));
+        assert!(!is_boolean_column("kind"));
+    }
+}
@@ -1,74 +1,358 @@
+use crate::tlog;
+use common::serde_json;
 use futures_util::stream::StreamExt;
-use tokio::fs::File;
-use tokio::io::AsyncReadExt;
-use tokio::io::{self, AsyncWriteExt, BufReader, BufWriter}; // インポート追加
+use reqwest::header::{
+    HeaderMap, CONTENT_RANGE, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, IF_RANGE, LAST_MODIFIED,
+    RANGE,
+};
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use tokio::fs::{File, OpenOptions};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::time::{sleep, Duration};
 
-async fn fetch_url(target_url: &str, tmp_path: &str) -> io::Result<()> {
-    let response = reqwest::get(target_url)
-        .await
-        .expect("Failed to download the file");
-
-    if response.status().is_success() {
-        let mut file = File::create(tmp_path).await?;
-        let mut stream = response.bytes_stream();
-
-        while let Some(chunk) = stream.next().await {
-            let chunk = match chunk {
-                Ok(data) => data,
-                Err(e) => {
-                    eprintln!("Failed to read chunk: {:?}", e);
-                    break;
-                }
-            };
-            // println!("Chunk size: {}", chunk.len());
-            file.write_all(&chunk).await?;
+/// Attempts per download; every retry resumes from the bytes already on disk.
+const MAX_ATTEMPTS: u32 = 3;
+
+#[derive(Debug)]
+pub enum DownloadError {
+    /// The request or the transfer failed (connection reset, timeout, ...).
+    Http(reqwest::Error),
+    /// The server answered with a status other than 200, 206 or 304.
+    Status(StatusCode),
+    /// A 206 response that does not continue the partial file.
+    UnexpectedRange(String),
+    /// The body ended before `Content-Length` bytes arrived.
+    Truncated {
+        expected: u64,
+        received: u64,
+    },
+    /// The downloaded archive could not be opened or extracted.
+    Extract(std::io::Error),
+    Io(std::io::Error),
+}
+
+impl DownloadError {
+    pub fn is_not_found(&self) -> bool {
+        matches!(self, DownloadError::Status(StatusCode::NOT_FOUND))
+    }
+
+    fn is_retryable(&self) -> bool {
+        match self {
+            DownloadError::Http(_)
+            | DownloadError::UnexpectedRange(_)
+            | DownloadError::Truncated { .. } => true,
+            DownloadError::Status(status) => {
+                status.is_server_error() || *status == StatusCode::RANGE_NOT_SATISFIABLE
+            }
+            DownloadError::Extract(_) | DownloadError::Io(_) => false,
+        }
+    }
+}
+
+impl fmt::Display for DownloadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DownloadError::Http(e) => write!(f, "request failed: {e}"),
+            DownloadError::Status(status) => write!(f, "unexpected HTTP status {status}"),
+            DownloadError::UnexpectedRange(range) => {
+                write!(f, "unexpected Content-Range {range:?}")
+            }
+            DownloadError::Truncated { expected, received } => {
+                write!(f, "truncated download: {received} of {expected} bytes")
+            }
+            DownloadError::Extract(e) => write!(f, "invalid archive: {e}"),
+            DownloadError::Io(e) => write!(f, "{e}"),
         }
+    }
+}
+
+impl std::error::Error for DownloadError {}
+
+impl From<std::io::Error> for DownloadError {
+    fn from(e: std::io::Error) -> Self {
+        DownloadError::Io(e)
+    }
+}
 
-        println!("Download completed.");
-    } else {
-        eprintln!("Failed to download file: {:?}", response.status());
+impl From<reqwest::Error> for DownloadError {
+    fn from(e: reqwest::Error) -> Self {
+        DownloadError::Http(e)
     }
+}
+
+/// Cache validators of a response, sent back to get `304 Not Modified`
+/// or to resume the same representation with `If-Range`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Validators {
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+}
+
+impl Validators {
+    fn from_headers(headers: &HeaderMap) -> Self {
+        let header = |name| {
+            headers
+                .get(name)
+                .and_then(|value: &reqwest::header::HeaderValue| value.to_str().ok())
+                .map(str::to_string)
+        };
+        Validators {
+            etag: header(ETAG),
+            last_modified: header(LAST_MODIFIED),
+        }
+    }
+
+    /// Weak ETags must not be used for range requests; fall back to the date.
+    fn if_range(&self) -> Option<&str> {
+        self.etag
+            .as_deref()
+            .filter(|etag| !etag.starts_with("W/"))
+            .or(self.last_modified.as_deref())
+    }
+}
+
+pub struct Downloaded {
+    pub validators: Validators,
+    /// Hex SHA-256 of the complete file.
+    pub sha256: String,
+    pub size: u64,
+}
+
+pub enum FetchOutcome {
+    /// The server confirmed the copy described by the given validators is current.
+    NotModified,
+    Downloaded(Downloaded),
+}
+
+fn part_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".part");
+    PathBuf::from(name)
+}
 
-    Ok(())
+fn part_validators_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".part.json");
+    PathBuf::from(name)
 }
 
-async fn process_large_file(tmp_path: &str, output_path: &str) -> io::Result<()> {
-    println!("Processing large file start...: {}", tmp_path);
-    let file = File::open(tmp_path).await?;
-    println!("File open finish");
-    let mut reader = BufReader::new(file);
-    let mut writer = BufWriter::new(File::create(output_path).await?);
-    println!("BufReader and BufWriter finish");
+async fn discard_partial(path: &Path) {
+    let _ = tokio::fs::remove_file(part_path(path)).await;
+    let _ = tokio::fs::remove_file(part_validators_path(path)).await;
+}
 
-    let metadata = tokio::fs::metadata(tmp_path).await?;
-    let file_size = metadata.len();
-    println!("File size after download: {}", file_size);
+/// Bytes already on disk from an interrupted transfer, with the validators of that response.
+async fn partial_download(path: &Path) -> Option<(u64, Validators)> {
+    let size = tokio::fs::metadata(part_path(path)).await.ok()?.len();
+    let validators = tokio::fs::read(part_validators_path(path))
+        .await
+        .ok()
+        .and_then(|bytes| serde_json::from_slice::<Validators>(&bytes).ok());
+    match validators {
+        Some(validators) if size > 0 && validators.if_range().is_some() => Some((size, validators)),
+        _ => {
+            discard_partial(path).await;
+            None
+        }
+    }
+}
 
-    let mut buffer = [0; 1024];
-    let mut total_read = 0;
+/// First byte position of a `Content-Range: bytes start-end/total` header.
+fn content_range_start(value: &str) -> Option<u64> {
+    value
+        .strip_prefix("bytes ")?
+        .split('-')
+        .next()?
+        .trim()
+        .parse()
+        .ok()
+}
 
-    while total_read < file_size {
-        let n = reader.read(&mut buffer).await?; // バッファにデータを読み込む
+pub async fn sha256_file(path: &Path) -> std::io::Result<String> {
+    let mut file = File::open(path).await?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0u8; 64 * 1024];
+    loop {
+        let n = file.read(&mut buffer).await?;
         if n == 0 {
-            println!(" reader read finish");
             break;
-        } else {
-            // Display the number of bytes read
-            // println!(" {} bytes read from file", n);
         }
+        hasher.update(&buffer[..n]);
+    }
+    Ok(format!("{:x}", hasher.finalize()))
+}
 
-        writer.write_all(&buffer[..n]).await?; // 書き込む
-        total_read += n as u64; // 読み込んだバイト数を更新
-        println!(" writer write_all finish, {} bytes written", total_read);
+async fn fetch_once(
+    client: &reqwest::Client,
+    url: &str,
+    path: &Path,
+    known: Option<&Validators>,
+) -> Result<FetchOutcome, DownloadError> {
+    let partial = partial_download(path).await;
+    let mut request = client.get(url);
+    match (&partial, known) {
+        (Some((offset, validators)), _) => {
+            request = request.header(RANGE, format!("bytes={offset}-"));
+            if let Some(if_range) = validators.if_range() {
+                request = request.header(IF_RANGE, if_range);
+            }
+        }
+        (None, Some(known)) => {
+            if let Some(etag) = &known.etag {
+                request = request.header(IF_NONE_MATCH, etag);
+            }
+            if let Some(last_modified) = &known.last_modified {
+                request = request.header(IF_MODIFIED_SINCE, last_modified);
+            }
+        }
+        (None, None) => {}
     }
 
-    writer.flush().await?;
-    tokio::fs::remove_file(tmp_path).await?;
-    println!("Async download completed.");
-    Ok(())
+    let response = request.send().await?;
+    let status = response.status();
+    let (offset, validators, mut file) = match (status, partial) {
+        (StatusCode::NOT_MODIFIED, None) if known.is_some() => {
+            return Ok(FetchOutcome::NotModified)
+        }
+        (StatusCode::PARTIAL_CONTENT, Some((offset, validators))) => {
+            let range = response
+                .headers()
+                .get(CONTENT_RANGE)
+                .and_then(|value| value.to_str().ok())
+                .unwrap_or_default()
+                .to_string();
+            if content_range_start(&range) != Some(offset) {
+                discard_partial(path).await;
+                return Err(DownloadError::UnexpectedRange(range));
+            }
+            tlog!("Resuming {} from byte {}", url, offset);
+            let file = OpenOptions::new()
+                .append(true)
+                .open(part_path(path))
+                .await?;
+            (offset, validators, file)
+        }
+        (StatusCode::OK, _) => {
+            // A full response also answers a stale If-Range: start over.
+            let validators = Validators::from_headers(response.headers());
+            let json = serde_json::to_vec(&validators).map_err(std::io::Error::from)?;
+            tokio::fs::write(part_validators_path(path), json).await?;
+            (0, validators, File::create(part_path(path)).await?)
+        }
+        (status, _) => {
+            if status == StatusCode::RANGE_NOT_SATISFIABLE {
+                discard_partial(path).await;
+            }
+            return Err(DownloadError::Status(status));
+        }
+    };
+
+    let expected = response.content_length().map(|length| offset + length);
+    let mut received = offset;
+    let mut stream = response.bytes_stream();
+    while let Some(chunk) = stream.next().await {
+        // Keep what arrived so far; the next attempt resumes after it.
+        let chunk = match chunk {
+            Ok(chunk) => chunk,
+            Err(e) => {
+                file.flush().await?;
+                return Err(DownloadError::Http(e));
+            }
+        };
+        file.write_all(&chunk).await?;
+        received += chunk.len() as u64;
+    }
+    file.flush().await?;
+    drop(file);
+
+    if let Some(expected) = expected {
+        if received != expected {
+            if received > expected {
+                discard_partial(path).await;
+            }
+            return Err(DownloadError::Truncated { expected, received });
+        }
+    }
+
+    let sha256 = sha256_file(&part_path(path)).await?;
+    tokio::fs::rename(part_path(path), path).await?;
+    let _ = tokio::fs::remove_file(part_validators_path(path)).await;
+    Ok(FetchOutcome::Downloaded(Downloaded {
+        validators,
+        sha256,
+        size: received,
+    }))
+}
+
+/// Downloads `url` to `path`. With `known` validators the request is conditional and an
+/// unchanged source yields `NotModified`. Interrupted transfers resume with a Range request,
+/// both across retries here and across cycles.
+pub async fn fetch(
+    url: &str,
+    path: &Path,
+    known: Option<&Validators>,
+) -> Result<FetchOutcome, DownloadError> {
+    let client = reqwest::Client::new();
+    let mut attempt = 1;
+    loop {
+        match fetch_once(&client, url, path, known).await {
+            Err(e) if e.is_retryable() && attempt < MAX_ATTEMPTS => {
+                tlog!(
+                    "Download of {} failed ({}); retrying ({}/{})",
+                    url,
+                    e,
+                    attempt + 1,
+                    MAX_ATTEMPTS
+                );
+                sleep(Duration::from_secs(2u64.pow(attempt))).await;
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
 }
 
-pub async fn fetch_stream(in_path: &str, output_path: &str, target_url: &str) -> io::Result<()> {
-    fetch_url(target_url, in_path).await?;
-    process_large_file(in_path, output_path).await
+#[cfg(test)]
+mod tests {
+    use super::{content_range_start, DownloadError, Validators};
+    use reqwest::StatusCode;
+
+    #[test]
+    fn content_range_start_reads_first_byte() {
+        assert_eq!(content_range_start("bytes 1024-2047/2048"), Some(1024));
+        assert_eq!(content_range_start("bytes */2048"), None);
+        assert_eq!(content_range_start(""), None);
+    }
+
+    #[test]
+    fn if_range_skips_weak_etags() {
+        let strong = Validators {
+            etag: Some("\"abc\"".to_string()),
+            last_modified: Some("Wed, 01 Oct 2025 00:00:00 GMT".to_string()),
+        };
+        assert_eq!(strong.if_range(), Some("\"abc\""));
+
+        let weak = Validators {
+            etag: Some("W/\"abc\"".to_string()),
+            ..strong.clone()
+        };
+        assert_eq!(weak.if_range(), Some("Wed, 01 Oct 2025 00:00:00 GMT"));
+        assert_eq!(Validators::default().if_range(), None);
+    }
+
+    #[test]
+    fn only_transient_failures_are_retried() {
+        let truncated = DownloadError::Truncated {
+            expected: 10,
+            received: 5,
+        };
+        assert!(truncated.is_retryable());
+        assert!(DownloadError::Status(StatusCode::BAD_GATEWAY).is_retryable());
+        assert!(!DownloadError::Status(StatusCode::NOT_FOUND).is_retryable());
+        assert!(DownloadError::Status(StatusCode::NOT_FOUND).is_not_found());
+    }
 }
@@ -0,0 +1,210 @@
+// Local KEN_ALL / JIGYOSYO files for offline imports (air-gapped hosts, CI).
+use super::download::{sha256_file, DownloadError};
+use super::unfreeze;
+use crate::constants::temp_dir;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+pub const KEN_ALL_STEM: &str = "utf_ken_all";
+pub const JIGYOSYO_STEM: &str = "jigyosyo";
+/// KEN_ALL names looked up in an import directory, in order of preference: the UTF-8
+/// edition, then the Shift_JIS `ken_all.zip` that ZIP_CODE_URL points at by default.
+const KEN_ALL_DIR_STEMS: &[&str] = &[KEN_ALL_STEM, "ken_all"];
+
+/// Where an import reads from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ImportSource {
+    Stdin,
+    /// A ZIP or CSV file, or a directory holding `utf_ken_all` (or `ken_all`) and
+    /// optionally `jigyosyo`.
+    Path(PathBuf),
+}
+
+impl ImportSource {
+    /// `-` reads stdin; `file://` URLs and plain paths read the file system.
+    pub fn parse(raw: &str) -> Self {
+        match raw.trim() {
+            "-" => ImportSource::Stdin,
+            raw => ImportSource::Path(PathBuf::from(raw.strip_prefix("file://").unwrap_or(raw))),
+        }
+    }
+}
+
+/// A local feed file, ready for the CSV parsers.
+pub struct LocalFile {
+    /// Recorded as the audit `source_url`: a `file://` URL or `stdin`.
+    pub source_url: String,
+    pub csv_path: String,
+    /// SHA-256 of the file as given, ZIP or CSV.
+    pub sha256: String,
+}
+
+pub struct LocalFeed {
+    pub ken_all: LocalFile,
+    pub jigyosyo: Option<LocalFile>,
+}
+
+fn file_url(path: &Path) -> String {
+    let path = std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    format!("file://{}", path.display())
+}
+
+fn is_zip(path: &Path) -> std::io::Result<bool> {
+    let mut magic = [0u8; 4];
+    let n = std::fs::File::open(path)?.read(&mut magic)?;
+    Ok(n == 4 && magic == *b"PK\x03\x04")
+}
+
+/// `<stem>.zip` or `<stem>.csv` in `dir` for the first of `file_stems` present, ignoring
+/// case (Japan Post ships `KEN_ALL.CSV` and `JIGYOSYO.CSV`).
+fn find_in_dir(dir: &Path, file_stems: &[&str]) -> std::io::Result<Option<PathBuf>> {
+    let mut found: Vec<(usize, PathBuf)> = std::fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter_map(|path| {
+            let name = path
+                .file_name()
+                .map(|name| name.to_string_lossy().to_ascii_lowercase())
+                .unwrap_or_default();
+            let rank = file_stems.iter().position(|file_stem| {
+                name == format!("{file_stem}.zip") || name == format!("{file_stem}.csv")
+            })?;
+            Some((rank, path))
+        })
+        .collect();
+    // Earlier stems first; for one stem, prefer the ZIP over its extracted CSV.
+    found.sort_by_key(|(rank, path)| (*rank, !is_zip(path).unwrap_or(false)));
+    Ok(found.into_iter().next().map(|(_, path)| path))
+}
+
+/// Extracts a ZIP into temp_assets, or reads a CSV where it is.
+pub async fn prepare(
+    path: &Path,
+    file_stem: &str,
+    source_url: String,
+) -> Result<LocalFile, DownloadError> {
+    let sha256 = sha256_file(path).await?;
+    let csv_path = if is_zip(path)? {
+        let csv_path = temp_dir()
+            .join(format!("{file_stem}.csv"))
+            .to_string_lossy()
+            .into_owned();
+        unfreeze::unzip(&path.to_string_lossy(), &csv_path).map_err(DownloadError::Extract)?;
+        csv_path
+    } else {
+        path.to_string_lossy().into_owned()
+    };
+    Ok(LocalFile {
+        source_url,
+        csv_path,
+        sha256,
+    })
+}
+
+async fn read_stdin() -> std::io::Result<PathBuf> {
+    let path = temp_dir().join("stdin_import");
+    let mut file = tokio::fs::File::create(&path).await?;
+    tokio::io::copy(&mut tokio::io::stdin(), &mut file).await?;
+    Ok(path)
+}
+
+/// Resolves `source` to the KEN_ALL feed and, for directories, an optional JIGYOSYO feed.
+pub async fn load(source: &ImportSource) -> Result<LocalFeed, DownloadError> {
+    let path = match source {
+        ImportSource::Stdin => {
+            let path = read_stdin().await?;
+            let ken_all = prepare(&path, KEN_ALL_STEM, "stdin".to_string()).await?;
+            return Ok(LocalFeed {
+                ken_all,
+                jigyosyo: None,
+            });
+        }
+        ImportSource::Path(path) => path,
+    };
+    if !path.is_dir() {
+        let ken_all = prepare(path, KEN_ALL_STEM, file_url(path)).await?;
+        return Ok(LocalFeed {
+            ken_all,
+            jigyosyo: None,
+        });
+    }
+
+    let ken_all_path = find_in_dir(path, KEN_ALL_DIR_STEMS)?.ok_or_else(|| {
+        std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            format!("no utf_ken_all or ken_all ZIP / CSV in {}", path.display()),
+        )
+    })?;
+    let ken_all = prepare(&ken_all_path, KEN_ALL_STEM, file_url(&ken_all_path)).await?;
+    let jigyosyo = match find_in_dir(path, &[JIGYOSYO_STEM])? {
+        Some(jigyosyo_path) => {
+            Some(prepare(&jigyosyo_path, JIGYOSYO_STEM, file_url(&jigyosyo_path)).await?)
+        }
+        None => None,
+    };
+    Ok(LocalFeed { ken_all, jigyosyo })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{find_in_dir, ImportSource, KEN_ALL_DIR_STEMS};
+    use std::path::PathBuf;
+
+    #[test]
+    fn import_source_accepts_paths_urls_and_stdin() {
+        assert_eq!(ImportSource::parse("-"), ImportSource::Stdin);
+        assert_eq!(
+            ImportSource::parse("file:///data/utf_ken_all.zip"),
+            ImportSource::Path(PathBuf::from("/data/utf_ken_all.zip"))
+        );
+        assert_eq!(
+            ImportSource::parse("./utf_ken_all.csv"),
+            ImportSource::Path(PathBuf::from("./utf_ken_all.csv"))
+        );
+    }
+
+    #[test]
+    fn find_in_dir_ignores_case_and_prefers_zip() {
+        let dir = std::env::temp_dir().join(format!("crawler_local_test_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("JIGYOSYO.CSV"), "").unwrap();
+        std::fs::write(dir.join("utf_ken_all.csv"), "").unwrap();
+        std::fs::write(dir.join("utf_ken_all.zip"), b"PK\x03\x04").unwrap();
+
+        assert_eq!(
+            find_in_dir(&dir, &["jigyosyo"]).unwrap(),
+            Some(dir.join("JIGYOSYO.CSV"))
+        );
+        assert_eq!(
+            find_in_dir(&dir, &["utf_ken_all"]).unwrap(),
+            Some(dir.join("utf_ken_all.zip"))
+        );
+        assert_eq!(find_in_dir(&dir, &["ken_all"]).unwrap(), None);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn find_in_dir_falls_back_to_shift_jis_ken_all() {
+        let dir =
+            std::env::temp_dir().join(format!("crawler_local_ken_all_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("KEN_ALL.CSV"), "").unwrap();
+        std::fs::write(dir.join("ken_all.zip"), b"PK\x03\x04").unwrap();
+
+        assert_eq!(
+            find_in_dir(&dir, KEN_ALL_DIR_STEMS).unwrap(),
+            Some(dir.join("ken_all.zip"))
+        );
+        std::fs::remove_file(dir.join("ken_all.zip")).unwrap();
+        assert_eq!(
+            find_in_dir(&dir, KEN_ALL_DIR_STEMS).unwrap(),
+            Some(dir.join("KEN_ALL.CSV"))
+        );
+        // The UTF-8 edition wins over the Shift_JIS one, even as a bare CSV.
+        std::fs::write(dir.join("utf_ken_all.csv"), "").unwrap();
+        assert_eq!(
+            find_in_dir(&dir, KEN_ALL_DIR_STEMS).unwrap(),
+            Some(dir.join("utf_ken_all.csv"))
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
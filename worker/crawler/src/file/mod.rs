@@ -1,6 +1,143 @@
+use crate::constants::temp_dir;
+use crate::tlog;
+use common::serde_json;
+use download::{DownloadError, FetchOutcome, Validators};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
 pub mod download;
+pub mod local;
 pub mod unfreeze;
 pub mod parse {
     pub mod csv;
     pub mod json;
 }
+
+/// What the last successfully loaded download of a source looked like. Kept next to the
+/// archive so the next cycle can ask the server for `304 Not Modified`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SourceState {
+    pub url: String,
+    #[serde(flatten)]
+    pub validators: Validators,
+    pub sha256: String,
+}
+
+/// A downloaded and extracted source file.
+#[derive(Debug, Clone)]
+pub struct FetchedSource {
+    pub file_stem: String,
+    pub csv_path: String,
+    pub state: SourceState,
+}
+
+fn source_state_path(file_stem: &str) -> PathBuf {
+    temp_dir().join(format!("{file_stem}.source.json"))
+}
+
+fn load_source_state(url: &str, file_stem: &str) -> Option<SourceState> {
+    let bytes = std::fs::read(source_state_path(file_stem)).ok()?;
+    serde_json::from_slice::<SourceState>(&bytes)
+        .ok()
+        .filter(|state| state.url == url)
+}
+
+/// Records `source` as loaded, so the next cycle only downloads it again when it changed.
+/// Call this after the data went live; a failed load must download the file again.
+pub fn commit_source(source: &FetchedSource) -> std::io::Result<()> {
+    let json = serde_json::to_vec_pretty(&source.state)?;
+    std::fs::write(source_state_path(&source.file_stem), json)
+}
+
+// Download a Japan Post ZIP into temp_assets and extract its CSV.
+// Returns `None` when `known` validators are given and the server reports no change.
+async fn fetch_source(
+    url: &str,
+    file_stem: &str,
+    known: Option<&Validators>,
+) -> Result<Option<FetchedSource>, DownloadError> {
+    let temp_dir = temp_dir();
+    let zip_path = temp_dir.join(format!("{file_stem}.zip"));
+    let csv_path = temp_dir.join(format!("{file_stem}.csv"));
+
+    tlog!("{}", url);
+    if let Some(path) = url.strip_prefix("file://") {
+        // Local files carry no validators; an unchanged feed is caught by its fingerprint.
+        let local = local::prepare(Path::new(path), file_stem, url.to_string()).await?;
+        return Ok(Some(FetchedSource {
+            file_stem: file_stem.to_string(),
+            csv_path: local.csv_path,
+            state: SourceState {
+                url: url.to_string(),
+                validators: Validators::default(),
+                sha256: local.sha256,
+            },
+        }));
+    }
+    let downloaded = match download::fetch(url, &zip_path, known).await? {
+        FetchOutcome::NotModified => {
+            tlog!("Not modified: {}", url);
+            return Ok(None);
+        }
+        FetchOutcome::Downloaded(downloaded) => downloaded,
+    };
+    tlog!(
+        "Downloaded {} bytes, sha256 {}",
+        downloaded.size,
+        downloaded.sha256
+    );
+    let csv_path = csv_path.to_string_lossy().into_owned();
+    unfreeze::unzip(&zip_path.to_string_lossy(), &csv_path).map_err(DownloadError::Extract)?;
+    Ok(Some(FetchedSource {
+        file_stem: file_stem.to_string(),
+        csv_path,
+        state: SourceState {
+            url: url.to_string(),
+            validators: downloaded.validators,
+            sha256: downloaded.sha256,
+        },
+    }))
+}
+
+async fn fetch_source_unconditionally(
+    url: &str,
+    file_stem: &str,
+) -> Result<FetchedSource, DownloadError> {
+    fetch_source(url, file_stem, None)
+        .await?
+        .ok_or(DownloadError::Status(reqwest::StatusCode::NOT_MODIFIED))
+}
+
+// Download without validators, for files that never change such as monthly diffs.
+// Returns the extracted CSV path.
+pub async fn download_and_extract(url: &str, file_stem: &str) -> Result<String, DownloadError> {
+    fetch_source_unconditionally(url, file_stem)
+        .await
+        .map(|source| source.csv_path)
+}
+
+/// Downloads every `(url, file_stem)` source, in order. Returns `None` when all of them are
+/// unchanged since they were last committed.
+pub async fn fetch_sources(
+    sources: &[(&str, &str)],
+) -> Result<Option<Vec<FetchedSource>>, DownloadError> {
+    let mut fetched: Vec<Option<FetchedSource>> = Vec::with_capacity(sources.len());
+    for &(url, file_stem) in sources {
+        let known = load_source_state(url, file_stem).map(|state| state.validators);
+        fetched.push(fetch_source(url, file_stem, known.as_ref()).await?);
+    }
+    if fetched.iter().all(Option::is_none) {
+        return Ok(None);
+    }
+
+    // Rows of every feed share the batch timestamp, so one changed feed reloads them all.
+    let mut result = Vec::with_capacity(sources.len());
+    for (&(url, file_stem), source) in sources.iter().zip(fetched) {
+        let source = match source {
+            Some(source) => source,
+            None => fetch_source_unconditionally(url, file_stem).await?,
+        };
+        result.push(source);
+    }
+    Ok(Some(result))
+}
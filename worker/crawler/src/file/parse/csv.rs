@@ -1,88 +1,137 @@
 use crate::constants::common_path;
 use crate::file;
-use common::models::PostalCode;
+use crate::tlog;
+use common::models::{PostalCode, PostalCodeKind, UpdateReason, UpdateStatus};
+use common::normalize::fold_width;
+use common::romaji::{romanize_place, romanize_town};
 use csv_async::AsyncReaderBuilder;
 use futures::stream::StreamExt;
 use std::collections::{HashMap, VecDeque};
+use unicode_normalization::UnicodeNormalization;
 
-// Register uppercase letters in advance in the cache map
-fn build_replace_cache() -> HashMap<char, &'static str> {
-    let mut map = HashMap::new();
-    map.insert('（', "(");
-    map.insert('）', ")");
-    map.insert('ー', "-");
-    map.insert('、', ",");
-    map.insert('０', "0");
-    map.insert('１', "1");
-    map.insert('２', "2");
-    map.insert('３', "3");
-    map.insert('４', "4");
-    map.insert('５', "5");
-    map.insert('６', "6");
-    map.insert('７', "7");
-    map.insert('８', "8");
-    map.insert('９', "9");
-    map
-}
-
-fn replace_japanese_to_alphanumeric_with_cache(s: &str, cache: &HashMap<char, &str>) -> String {
-    s.chars()
-        .map(|c| cache.get(&c).unwrap_or(&c.to_string().as_str()).to_string())
-        .collect()
-}
-
-fn remove_parentheses(s: &str) -> String {
-    let mut result = String::new();
+// Split "銀座(1丁目)" into ("銀座", "1丁目"); multiple groups are joined with ','.
+fn split_parentheses(s: &str) -> (String, String) {
+    let mut outside = String::new();
+    let mut inside = String::new();
     let mut depth = 0;
     for c in s.chars() {
         if c == '(' {
+            if depth == 0 && !inside.is_empty() {
+                inside.push(',');
+            } else if depth > 0 {
+                inside.push(c);
+            }
             depth += 1;
         } else if c == ')' {
             depth = std::cmp::max(0, depth - 1);
+            if depth > 0 {
+                inside.push(c);
+            }
         } else if depth == 0 {
-            result.push(c);
+            outside.push(c);
+        } else {
+            inside.push(c);
         }
     }
-    result
+    (outside, inside)
+}
+
+// "1階" / "地階・階層不明" mark the per-floor codes of high-rise buildings.
+fn parse_floor(detail: &str) -> String {
+    let is_floor = detail == "地階・階層不明"
+        || detail
+            .strip_suffix('階')
+            .is_some_and(|n| !n.is_empty() && n.chars().all(|c| c.is_ascii_digit()));
+    if is_floor {
+        detail.to_string()
+    } else {
+        String::new()
+    }
+}
+
+// "1丁目", "1～19丁目", "1,2丁目"; anything with extra qualifiers stays in town_detail only.
+fn parse_chome_range(detail: &str) -> String {
+    let is_chome = detail.strip_suffix("丁目").is_some_and(|n| {
+        !n.is_empty()
+            && n.chars()
+                .all(|c| c.is_ascii_digit() || matches!(c, '～' | '~' | ',' | '-'))
+    });
+    if is_chome {
+        detail.to_string()
+    } else {
+        String::new()
+    }
+}
+
+// Move parenthetical text out of `town` into the detail fields.
+// Runs after continuation lines are merged, since a group can span several lines.
+fn split_town_detail(code: &mut PostalCode) {
+    let (town, detail) = split_parentheses(&code.town);
+    let (town_kana, _) = split_parentheses(&code.town_kana);
+
+    if town == "以下に掲載がない場合" {
+        code.town = String::new();
+        code.town_kana = String::new();
+        return;
+    }
+
+    code.floor = parse_floor(&detail);
+    code.chome_range = parse_chome_range(&detail);
+    code.town = town;
+    code.town_kana = town_kana;
+    code.town_detail = detail;
+}
+
+// Romanized names are derived from the readings once `town_kana` has lost its parentheses.
+fn fill_romaji(code: &mut PostalCode) {
+    code.prefecture_roma = romanize_place(&code.prefecture, &code.prefecture_kana);
+    code.city_roma = romanize_place(&code.city, &code.city_kana);
+    code.town_roma = romanize_town(&code.town_kana);
+}
+
+fn append_detail(target: &mut String, value: &str) {
+    if value.is_empty() || target.split(',').any(|v| v == value) {
+        return;
+    }
+    if !target.is_empty() {
+        target.push(',');
+    }
+    target.push_str(value);
+}
+
+// Kana readings are published in half-width katakana; NFKC folds them to full-width
+// so they match the normalized search input on the API side.
+fn normalize_kana(s: &str) -> String {
+    s.nfkc().collect()
 }
 
 //  Format CSV file records
 fn format_csv_record_with_cache(
     record: VecDeque<String>,
     pref_cache: &HashMap<String, String>,
-    replace_cache: &HashMap<char, &str>,
 ) -> (PostalCode, bool) {
     let city_id = record.front().cloned().unwrap_or_else(|| "".to_string());
     let zip_code = record.get(2).cloned().unwrap_or_else(|| "".to_string());
-    let prefecture = record.get(6).map_or_else(
-        || "".to_string(),
-        |s| replace_japanese_to_alphanumeric_with_cache(s, replace_cache),
-    );
+    let prefecture = record.get(6).map(|s| fold_width(s)).unwrap_or_default();
     let prefecture_id = pref_cache
         .get(&prefecture)
         .and_then(|s| s.parse::<i16>().ok())
         .unwrap_or(0);
-    let city = record.get(7).map_or_else(
-        || "".to_string(),
-        |s| replace_japanese_to_alphanumeric_with_cache(s, replace_cache),
-    );
-    let raw_town = record
-        .get(8)
-        .map(|s| replace_japanese_to_alphanumeric_with_cache(s, replace_cache))
-        .unwrap_or_default();
-
-    // Remove parentheses content (e.g., "銀座(1丁目)" -> "銀座")
-    // Note: Full-width parentheses '（）' are already converted to half-width '()'
-    // by replace_japanese_to_alphanumeric_with_cache above.
-    let town = remove_parentheses(&raw_town);
-
-    // Column 12 (Index 12, 13th column) indicates "One town has multiple zip codes" (1=yes, 0=no)
+    let city = record.get(7).map(|s| fold_width(s)).unwrap_or_default();
+    // Parentheses are kept here and split off by split_town_detail once continuation lines
+    // are merged. Full-width '（）' are already converted to half-width '()' by fold_width.
+    let town = record.get(8).map(|s| fold_width(s)).unwrap_or_default();
+    let prefecture_kana = record.get(3).map(|s| normalize_kana(s)).unwrap_or_default();
+    let city_kana = record.get(4).map(|s| normalize_kana(s)).unwrap_or_default();
+    let town_kana = record.get(5).map(|s| normalize_kana(s)).unwrap_or_default();
+
+    // Indexes 9-12 are 0/1 flags describing how towns and zip codes relate.
+    let flag = |index: usize| record.get(index).map(|s| s == "1").unwrap_or(false);
+
+    // Column 12 (Index 12, 13th column) indicates "One zip code covers multiple towns" (1=yes, 0=no)
     // The user suggests using this flag to determine split lines.
     // If 0, it likely means the town is simple and if split across lines, it should be merged.
-    let is_multi_town = record
-        .get(12) // Access Index 12 directly
-        .map(|s| s == "1")
-        .unwrap_or(false);
+    let is_multi_town = flag(12);
 
     (
         PostalCode {
@@ -91,16 +140,60 @@ fn format_csv_record_with_cache(
             city_id,
             prefecture,
             city,
-            town: if town == "以下に掲載がない場合" {
-                "".to_string()
-            } else {
-                town
-            },
+            town,
+            prefecture_kana,
+            city_kana,
+            town_kana,
+            town_has_multiple_zip_codes: flag(9),
+            numbered_per_koaza: flag(10),
+            has_chome: flag(11),
+            covers_multiple_towns: is_multi_town,
+            update_status: UpdateStatus::from_ken_all(record.get(13).map_or("", |s| s.as_str())),
+            update_reason: UpdateReason::from_ken_all(record.get(14).map_or("", |s| s.as_str())),
+            ..Default::default()
         },
         is_multi_town,
     )
 }
 
+// Format JIGYOSYO (business office) records; the layout differs from KEN_ALL entirely.
+fn format_jigyosyo_record_with_cache(
+    record: &csv_async::StringRecord,
+    pref_cache: &HashMap<String, String>,
+) -> PostalCode {
+    let field = |index: usize| {
+        record
+            .get(index)
+            .map(|s| fold_width(s.trim()))
+            .unwrap_or_default()
+    };
+    let prefecture = field(3);
+    let prefecture_id = pref_cache
+        .get(&prefecture)
+        .and_then(|s| s.parse::<i16>().ok())
+        .unwrap_or(0);
+    // Index 10: 0 = large business office, 1 = P.O. box
+    let kind = if record.get(10) == Some("1") {
+        PostalCodeKind::PoBox
+    } else {
+        PostalCodeKind::Business
+    };
+
+    PostalCode {
+        zip_code: field(7),
+        prefecture_id,
+        city_id: field(0),
+        prefecture,
+        city: field(4),
+        town: field(5),
+        kind,
+        business_name: field(2),
+        business_name_kana: record.get(1).map(normalize_kana).unwrap_or_default(),
+        street_address: field(6),
+        ..Default::default()
+    }
+}
+
 // make prefecture cache
 pub async fn build_prefecture_cache() -> HashMap<String, String> {
     let mut pref_map = HashMap::new();
@@ -119,21 +212,111 @@ pub async fn build_prefecture_cache() -> HashMap<String, String> {
     pref_map
 }
 
+// Japan Post ships ken_all / jigyosyo in Shift_JIS and utf_ken_all in UTF-8 (with or
+// without BOM). Kana-heavy Shift_JIS text is practically never valid UTF-8.
+fn decode_japan_post(content: Vec<u8>) -> String {
+    match String::from_utf8(content) {
+        Ok(text) => match text.strip_prefix('\u{feff}') {
+            Some(text) => text.to_string(),
+            None => text,
+        },
+        Err(e) => {
+            let (decoded, _, _) = encoding_rs::SHIFT_JIS.decode(e.as_bytes());
+            decoded.into_owned()
+        }
+    }
+}
+
+async fn read_japan_post_csv(file_path: &str) -> std::io::Result<String> {
+    let content = tokio::fs::read(file_path).await?;
+    Ok(decode_japan_post(content))
+}
+
+// Parse the JIGYOSYO CSV file; each line is one complete business record.
+pub async fn jigyosyo_stream_format(
+    file_path: &str,
+    is_header: bool,
+) -> Result<Vec<PostalCode>, Box<dyn std::error::Error>> {
+    let decoded_string = read_japan_post_csv(file_path).await?;
+
+    let csv_reader = AsyncReaderBuilder::new()
+        .has_headers(is_header)
+        .create_reader(decoded_string.as_bytes());
+
+    let pref_cache = build_prefecture_cache().await;
+
+    let mut records_vec: Vec<PostalCode> = Vec::new();
+    let mut records = csv_reader.into_records();
+    while let Some(result) = records.next().await {
+        match result {
+            Ok(record) => records_vec.push(format_jigyosyo_record_with_cache(&record, &pref_cache)),
+            Err(e) => eprintln!("Error processing record: {:?}", e),
+        }
+    }
+
+    let (records_vec, dropped) = dedupe_by_primary_key(records_vec);
+    if dropped.rows > 0 {
+        tlog!(
+            "JIGYOSYO: dropped {} rows sharing a primary key with an earlier row \
+             ({} with a different business name or street address)",
+            dropped.rows,
+            dropped.conflicting
+        );
+    }
+    Ok(records_vec)
+}
+
+/// Rows `dedupe_by_primary_key` left out.
+#[derive(Debug, Default, PartialEq, Eq)]
+struct DroppedRows {
+    rows: usize,
+    /// Dropped rows whose business name or street address differ from the row kept.
+    conflicting: usize,
+}
+
+/// Keeps the first row per `(zip_code, prefecture_id, city, town)`, the primary key of
+/// postal_codes, which several JIGYOSYO offices can share.
+fn dedupe_by_primary_key(rows: Vec<PostalCode>) -> (Vec<PostalCode>, DroppedRows) {
+    let mut kept: Vec<PostalCode> = Vec::with_capacity(rows.len());
+    let mut index: HashMap<(String, i16, String, String), usize> = HashMap::new();
+    let mut dropped = DroppedRows::default();
+    for row in rows {
+        let key = (
+            row.zip_code.clone(),
+            row.prefecture_id,
+            row.city.clone(),
+            row.town.clone(),
+        );
+        match index.get(&key) {
+            Some(&first) => {
+                dropped.rows += 1;
+                if kept[first].business_name != row.business_name
+                    || kept[first].street_address != row.street_address
+                {
+                    dropped.conflicting += 1;
+                }
+            }
+            None => {
+                index.insert(key, kept.len());
+                kept.push(row);
+            }
+        }
+    }
+    (kept, dropped)
+}
+
 // Parse CSV file and apply formatting
 pub async fn csv_stream_format(
     file_path: &str,
     is_header: bool,
 ) -> Result<Vec<PostalCode>, Box<dyn std::error::Error>> {
-    let content = tokio::fs::read(file_path).await?;
-    let (decoded, _, _) = encoding_rs::SHIFT_JIS.decode(&content);
-    let decoded_string = decoded.into_owned();
+    let decoded_string = read_japan_post_csv(file_path).await?;
 
     let csv_reader = AsyncReaderBuilder::new()
         .has_headers(is_header)
         .create_reader(decoded_string.as_bytes());
 
     let pref_cache = build_prefecture_cache().await;
-    let replace_cache = build_replace_cache();
 
     let mut records_vec: Vec<PostalCode> = Vec::new();
     let mut records = csv_reader.into_records();
@@ -144,8 +327,7 @@ pub async fn csv_stream_format(
         match result {
             Ok(record) => {
                 let deque: VecDeque<String> = record.iter().map(|s| s.to_string()).collect();
-                let (current, is_multi_town) =
-                    format_csv_record_with_cache(deque, &pref_cache, &replace_cache);
+                let (current, is_multi_town) = format_csv_record_with_cache(deque, &pref_cache);
 
                 if let Some(ref mut prev) = prev_record {
                     // Merge logic:
@@ -157,6 +339,10 @@ pub async fn csv_stream_format(
                         && !is_multi_town
                     {
                         prev.town.push_str(&current.town);
+                        // Continuation lines usually repeat the full reading instead of splitting it.
+                        if !prev.town_kana.ends_with(&current.town_kana) {
+                            prev.town_kana.push_str(&current.town_kana);
+                        }
                         // Continue to next record, keeping 'prev' as accumulator
                         continue;
                     } else {
@@ -177,19 +363,293 @@ pub async fn csv_stream_format(
         records_vec.push(last);
     }
 
+    for record in records_vec.iter_mut() {
+        split_town_detail(record);
+        fill_romaji(record);
+    }
+
     // Deduplicate records based on Primary Key (zip_code, prefecture_id, city, town)
     // to prevent "ON CONFLICT DO UPDATE command cannot affect row a second time" error.
     // This error occurs when a single batch contains multiple records with the same PK.
-    let mut seen = std::collections::HashSet::new();
-    records_vec.retain(|r| {
+    // The parenthetical detail of dropped rows is folded into the row that is kept.
+    let mut seen: HashMap<(String, i16, String, String), usize> = HashMap::new();
+    let mut deduped: Vec<PostalCode> = Vec::with_capacity(records_vec.len());
+    for r in records_vec {
         let key = (
             r.zip_code.clone(),
             r.prefecture_id,
             r.city.clone(),
             r.town.clone(),
         );
-        seen.insert(key)
-    });
+        match seen.get(&key) {
+            Some(&index) => {
+                let kept = &mut deduped[index];
+                append_detail(&mut kept.town_detail, &r.town_detail);
+                append_detail(&mut kept.chome_range, &r.chome_range);
+                append_detail(&mut kept.floor, &r.floor);
+            }
+            None => {
+                seen.insert(key, deduped.len());
+                deduped.push(r);
+            }
+        }
+    }
 
-    Ok(records_vec)
+    Ok(deduped)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        decode_japan_post, dedupe_by_primary_key, fill_romaji, format_csv_record_with_cache,
+        format_jigyosyo_record_with_cache, split_town_detail, DroppedRows,
+    };
+    use common::models::{PostalCode, PostalCodeKind, UpdateReason, UpdateStatus};
+    use std::collections::{HashMap, VecDeque};
+
+    fn ken_all_record(fields: &[&str]) -> VecDeque<String> {
+        fields.iter().map(|s| s.to_string()).collect()
+    }
+
+    fn tokyo_pref_cache() -> HashMap<String, String> {
+        HashMap::from([("東京都".to_string(), "13".to_string())])
+    }
+
+    #[test]
+    fn format_record_reads_kana_columns() {
+        let record = ken_all_record(&[
+            "13104",
+            "160  ",
+            "1600022",
+            "ﾄｳｷｮｳﾄ",
+            "ｼﾝｼﾞｭｸｸ",
+            "ｼﾝｼﾞｭｸ",
+            "東京都",
+            "新宿区",
+            "新宿",
+            "0",
+            "0",
+            "1",
+            "0",
+            "0",
+            "0",
+        ]);
+        let (code, is_multi_town) = format_csv_record_with_cache(record, &tokyo_pref_cache());
+
+        assert!(!is_multi_town);
+        assert!(code.has_chome);
+        assert!(!code.covers_multiple_towns);
+        assert_eq!(code.prefecture_id, 13);
+        assert_eq!(code.prefecture_kana, "トウキョウト");
+        assert_eq!(code.city_kana, "シンジュクク");
+        assert_eq!(code.town_kana, "シンジュク");
+    }
+
+    #[test]
+    fn format_jigyosyo_record_maps_business_fields() {
+        let record = csv_async::StringRecord::from(vec![
+            "13101",
+            "ｼｭｳｷﾞｲﾝ",
+            "衆議院",
+            "東京都",
+            "千代田区",
+            "永田町",
+            "１丁目７番１号",
+            "1008960",
+            "100  ",
+            "銀座",
+            "0",
+            "0",
+            "0",
+        ]);
+        let code = format_jigyosyo_record_with_cache(&record, &tokyo_pref_cache());
+
+        assert_eq!(code.zip_code, "1008960");
+        assert_eq!(code.prefecture_id, 13);
+        assert_eq!(code.city_id, "13101");
+        assert_eq!(code.town, "永田町");
+        assert_eq!(code.kind, PostalCodeKind::Business);
+        assert_eq!(code.business_name, "衆議院");
+        assert_eq!(code.business_name_kana, "シュウギイン");
+        assert_eq!(code.street_address, "1丁目7番1号");
+    }
+
+    #[test]
+    fn dedupe_by_primary_key_counts_conflicting_offices() {
+        let office = |name: &str, street_address: &str| PostalCode {
+            zip_code: "1008960".to_string(),
+            prefecture_id: 13,
+            city: "千代田区".to_string(),
+            town: "永田町".to_string(),
+            kind: PostalCodeKind::Business,
+            business_name: name.to_string(),
+            street_address: street_address.to_string(),
+            ..Default::default()
+        };
+        let rows = vec![
+            office("衆議院", "1丁目7番1号"),
+            office("衆議院", "1丁目7番1号"),
+            office("衆議院第一議員会館", "2丁目2番1号"),
+        ];
+        let (kept, dropped) = dedupe_by_primary_key(rows);
+
+        assert_eq!(kept, vec![office("衆議院", "1丁目7番1号")]);
+        assert_eq!(
+            dropped,
+            DroppedRows {
+                rows: 2,
+                conflicting: 1
+            }
+        );
+    }
+
+    #[test]
+    fn format_jigyosyo_record_detects_po_box() {
+        let mut fields = vec!["13101", "", "", "東京都", "千代田区", "", "", "1008799"];
+        fields.extend(["100  ", "銀座", "1", "0", "0"]);
+        let record = csv_async::StringRecord::from(fields);
+        let code = format_jigyosyo_record_with_cache(&record, &tokyo_pref_cache());
+
+        assert_eq!(code.kind, PostalCodeKind::PoBox);
+    }
+
+    #[test]
+    fn format_record_clears_kana_for_unlisted_town() {
+        let record = ken_all_record(&[
+            "13104",
+            "160  ",
+            "1600000",
+            "ﾄｳｷｮｳﾄ",
+            "ｼﾝｼﾞｭｸｸ",
+            "ｲｶﾆｹｲｻｲｶﾞﾅｲﾊﾞｱｲ",
+            "東京都",
+            "新宿区",
+            "以下に掲載がない場合",
+            "0",
+            "0",
+            "0",
+            "0",
+            "0",
+            "0",
+        ]);
+        let (mut code, _) = format_csv_record_with_cache(record, &tokyo_pref_cache());
+        split_town_detail(&mut code);
+
+        assert_eq!(code.town, "");
+        assert_eq!(code.town_kana, "");
+    }
+
+    #[test]
+    fn format_record_reads_update_flags() {
+        let record = ken_all_record(&[
+            "13104",
+            "160  ",
+            "1600023",
+            "ﾄｳｷｮｳﾄ",
+            "ｼﾝｼﾞｭｸｸ",
+            "ﾆｼｼﾝｼﾞｭｸ",
+            "東京都",
+            "新宿区",
+            "西新宿",
+            "1",
+            "1",
+            "0",
+            "1",
+            "1",
+            "3",
+        ]);
+        let (code, is_multi_town) = format_csv_record_with_cache(record, &tokyo_pref_cache());
+
+        assert!(is_multi_town);
+        assert!(code.town_has_multiple_zip_codes);
+        assert!(code.numbered_per_koaza);
+        assert!(!code.has_chome);
+        assert!(code.covers_multiple_towns);
+        assert_eq!(code.update_status, UpdateStatus::Changed);
+        assert_eq!(code.update_reason, UpdateReason::LandReadjustment);
+    }
+
+    fn town_with_detail(town: &str, town_kana: &str) -> common::models::PostalCode {
+        let record = ken_all_record(&[
+            "13104",
+            "163  ",
+            "1630000",
+            "ﾄｳｷｮｳﾄ",
+            "ｼﾝｼﾞｭｸｸ",
+            town_kana,
+            "東京都",
+            "新宿区",
+            town,
+            "0",
+            "0",
+            "0",
+            "0",
+            "0",
+            "0",
+        ]);
+        let (mut code, _) = format_csv_record_with_cache(record, &tokyo_pref_cache());
+        split_town_detail(&mut code);
+        code
+    }
+
+    #[test]
+    fn split_town_detail_keeps_chome_range() {
+        let code = town_with_detail("大通西（１～１９丁目）", "ｵｵﾄﾞｵﾘﾆｼ(1-19ﾁｮｳﾒ)");
+
+        assert_eq!(code.town, "大通西");
+        assert_eq!(code.town_kana, "オオドオリニシ");
+        assert_eq!(code.town_detail, "1～19丁目");
+        assert_eq!(code.chome_range, "1～19丁目");
+        assert_eq!(code.floor, "");
+    }
+
+    #[test]
+    fn split_town_detail_keeps_building_floor() {
+        let floor = town_with_detail("西新宿新宿住友ビル（１階）", "ﾆｼｼﾝｼﾞｭｸｼﾝｼﾞｭｸｽﾐﾄﾓﾋﾞﾙ(1ｶｲ)");
+        let unknown = town_with_detail(
+            "西新宿新宿住友ビル（地階・階層不明）",
+            "ﾆｼｼﾝｼﾞｭｸｼﾝｼﾞｭｸｽﾐﾄﾓﾋﾞﾙ(ﾁｶｲ･ｶｲｿｳﾌﾒｲ)",
+        );
+
+        assert_eq!(floor.town, "西新宿新宿住友ビル");
+        assert_eq!(floor.floor, "1階");
+        assert_eq!(floor.chome_range, "");
+        assert_eq!(unknown.town, "西新宿新宿住友ビル");
+        assert_eq!(unknown.floor, "地階・階層不明");
+    }
+
+    #[test]
+    fn split_town_detail_leaves_qualifiers_unparsed() {
+        let code = town_with_detail("西新宿（次のビルを除く）", "ﾆｼｼﾝｼﾞｭｸ(ﾂｷﾞﾉﾋﾞﾙｦﾉｿﾞｸ)");
+
+        assert_eq!(code.town, "西新宿");
+        assert_eq!(code.town_detail, "次のビルを除く");
+        assert_eq!(code.chome_range, "");
+        assert_eq!(code.floor, "");
+    }
+
+    #[test]
+    fn fill_romaji_uses_split_readings() {
+        let mut code = town_with_detail("大通西（１～１９丁目）", "オオドオリニシ(1-19チョウメ)");
+        code.prefecture = "北海道".to_string();
+        code.prefecture_kana = "ホッカイドウ".to_string();
+        code.city = "札幌市中央区".to_string();
+        code.city_kana = "サッポロシチュウオウク".to_string();
+        fill_romaji(&mut code);
+
+        assert_eq!(code.prefecture_roma, "Hokkaido");
+        assert_eq!(code.city_roma, "Sapporo-shi Chuo-ku");
+        assert_eq!(code.town_roma, "Odorinishi");
+    }
+
+    #[test]
+    fn decode_japan_post_accepts_shift_jis_and_utf8() {
+        let (shift_jis, _, _) = encoding_rs::SHIFT_JIS.encode("北海道,ﾎｯｶｲﾄﾞｳ");
+        assert_eq!(decode_japan_post(shift_jis.into_owned()), "北海道,ﾎｯｶｲﾄﾞｳ");
+        assert_eq!(
+            decode_japan_post("\u{feff}北海道".as_bytes().to_vec()),
+            "北海道"
+        );
+        assert_eq!(decode_japan_post("北海道".as_bytes().to_vec()), "北海道");
+    }
 }
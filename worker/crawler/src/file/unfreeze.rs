@@ -1,3 +1,4 @@
+use crate::tlog;
 use std::fs::File;
 use std::io::{BufWriter, Write};
 use zip::read::ZipArchive;
@@ -13,13 +14,13 @@ pub fn unzip(input_path: &str, output_path: &str) -> std::io::Result<()> {
         ));
     }
 
-    let mut zip_file = archive.by_index(0).expect("Failed to access ZIP entry");
+    let mut zip_file = archive.by_index(0)?;
     let output_file = File::create(output_path)?;
     let mut writer = BufWriter::new(output_file);
 
     // Copy the contents of the file to the output file
     std::io::copy(&mut zip_file, &mut writer)?;
     writer.flush()?;
-    println!("Unzip completed.");
+    tlog!("Unzip completed.");
     Ok(())
 }
@@ -0,0 +1,342 @@
+// Full reloads: replace every row with a complete KEN_ALL (and JIGYOSYO) feed.
+use crate::db;
+use crate::db::audit::{DataUpdateAuditRecord, LoadedDataset};
+use crate::file;
+use crate::tlog;
+use common::audit::STATUS_UNCHANGED;
+use common::models::PostalCode;
+
+pub enum FullReloadOutcome {
+    /// The feed was written, audited and snapshotted.
+    Loaded,
+    /// The feed matched the data in place; only an `unchanged` audit row was written.
+    Unchanged,
+    /// Dry run: the feed differs from the data in place and would be loaded.
+    WouldLoad,
+}
+
+pub struct FullReloadReport {
+    /// Rows were written, so cached responses are stale even when a later step failed.
+    pub data_updated: bool,
+    pub result: Result<FullReloadOutcome, String>,
+}
+
+impl FullReloadReport {
+    fn failed(message: String) -> Self {
+        FullReloadReport {
+            data_updated: false,
+            result: Err(message),
+        }
+    }
+}
+
+pub struct FullReloadRun<'a> {
+    pub data_version: &'a str,
+    pub run_started_at: chrono::DateTime<chrono::Utc>,
+    pub batch_timestamp: chrono::NaiveDateTime,
+    /// Where the feed came from: a download URL, `file://` path or `stdin`.
+    pub source_url: &'a str,
+    pub source_sha256: Option<&'a str>,
+    /// Compare the feed with the loaded data, but write nothing (no migrations or audit).
+    pub dry_run: bool,
+}
+
+impl FullReloadRun<'_> {
+    fn new_audit_record(&self, rows: &[PostalCode], fingerprint: &str) -> DataUpdateAuditRecord {
+        DataUpdateAuditRecord {
+            data_version: self.data_version.to_string(),
+            source_url: self.source_url.to_string(),
+            run_started_at: self.run_started_at,
+            run_finished_at: chrono::Utc::now(),
+            batch_timestamp: self.batch_timestamp,
+            records_in_feed: rows.len() as i64,
+            inserted_count: 0,
+            updated_count: 0,
+            deleted_count: 0,
+            total_count: 0,
+            status: "failed".to_string(),
+            error_message: None,
+            diff_month: crate::incremental::full_reload_diff_month(self.run_started_at),
+            source_sha256: self.source_sha256.map(str::to_string),
+            dataset_fingerprint: Some(fingerprint.to_string()),
+        }
+    }
+}
+
+/// Parses an extracted KEN_ALL CSV and, when given, a JIGYOSYO CSV into one feed.
+/// Business rows share the batch timestamp, so a partial feed would let
+/// delete_old_records wipe them: either file failing fails the whole feed.
+pub async fn parse_feed(
+    ken_all_csv: &str,
+    jigyosyo_csv: Option<&str>,
+) -> Result<Vec<PostalCode>, String> {
+    let mut rows = file::parse::csv::csv_stream_format(ken_all_csv, false)
+        .await
+        .map_err(|e| format!("Error reading CSV file: {e}"))?;
+    if let Some(jigyosyo_csv) = jigyosyo_csv {
+        let business = file::parse::csv::jigyosyo_stream_format(jigyosyo_csv, false)
+            .await
+            .map_err(|e| format!("Error reading JIGYOSYO CSV file: {e}"))?;
+        tlog!("Business office records: {}", business.len());
+        rows.extend(business);
+    }
+    Ok(rows)
+}
+
+/// Matches the feed against the latest dataset; a lookup error just means a full write.
+fn unchanged_dataset(
+    latest: Result<Option<LoadedDataset>, String>,
+    fingerprint: &str,
+    label: &str,
+) -> Option<LoadedDataset> {
+    match latest {
+        Ok(Some(loaded)) if loaded.fingerprint == fingerprint => Some(loaded),
+        Ok(_) => None,
+        Err(e) => {
+            eprintln!("Error reading the latest {label} dataset: {e}");
+            None
+        }
+    }
+}
+
+pub async fn run(
+    database_type: &str,
+    run: &FullReloadRun<'_>,
+    rows: &[PostalCode],
+) -> FullReloadReport {
+    match database_type {
+        "mysql" => run_mysql(run, rows).await,
+        "postgres" => run_postgres(run, rows).await,
+        other => {
+            FullReloadReport::failed(format!("Unsupported DATABASE_TYPE for crawler: {other}"))
+        }
+    }
+}
+
+pub async fn run_postgres(run: &FullReloadRun<'_>, rows: &[PostalCode]) -> FullReloadReport {
+    let pool = match db::connection::postgres_connection().await {
+        Ok(pool) => {
+            tlog!("PostgreSQL connected");
+            pool
+        }
+        Err(e) => return FullReloadReport::failed(format!("Error connecting to PostgreSQL: {e}")),
+    };
+
+    if !run.dry_run {
+        if let Err(e) = db::audit::ensure_audit_table_postgres(&pool).await {
+            eprintln!("Error preparing PostgreSQL audit table: {:?}", e);
+        }
+        if let Err(e) = db::schema::ensure_audit_columns_postgres(&pool).await {
+            eprintln!("Error migrating PostgreSQL audit columns: {:?}", e);
+        }
+        if let Err(e) = db::audit::ensure_snapshot_table_postgres(&pool).await {
+            eprintln!("Error preparing PostgreSQL snapshot table: {:?}", e);
+        }
+        if let Err(e) = db::schema::ensure_postal_code_columns_postgres(&pool).await {
+            eprintln!("Error migrating PostgreSQL postal_codes columns: {:?}", e);
+        }
+        if let Err(e) = db::schema::ensure_search_indexes_postgres(&pool).await {
+            eprintln!("Error preparing PostgreSQL search indexes: {:?}", e);
+        }
+    }
+
+    let fingerprint = db::audit::dataset_fingerprint(rows);
+    let mut audit_record = run.new_audit_record(rows, &fingerprint);
+    let latest = db::audit::latest_dataset_postgres(&pool)
+        .await
+        .map_err(|e| e.to_string());
+    if let Some(loaded) = unchanged_dataset(latest, &fingerprint, "PostgreSQL") {
+        tlog!("Dataset unchanged since the last run; skipping PostgreSQL writes.");
+        if run.dry_run {
+            return FullReloadReport {
+                data_updated: false,
+                result: Ok(FullReloadOutcome::Unchanged),
+            };
+        }
+        audit_record.total_count = loaded.total_count;
+        audit_record.status = STATUS_UNCHANGED.to_string();
+        audit_record.run_finished_at = chrono::Utc::now();
+        if let Err(e) = db::audit::insert_audit_postgres(&pool, &audit_record).await {
+            eprintln!("Error inserting PostgreSQL audit log: {:?}", e);
+        }
+        return FullReloadReport {
+            data_updated: false,
+            result: Ok(FullReloadOutcome::Unchanged),
+        };
+    }
+
+    if run.dry_run {
+        return FullReloadReport {
+            data_updated: false,
+            result: Ok(FullReloadOutcome::WouldLoad),
+        };
+    }
+
+    let mut data_updated = false;
+    let result: Result<(), String> = async {
+        db::insert_postal_code_postgres::bulk_insert_async(&pool, rows, run.batch_timestamp)
+            .await
+            .map_err(|e| format!("bulk_insert: {e}"))?;
+        tlog!("Data inserted into PostgreSQL successfully.");
+        data_updated = true;
+        audit_record.deleted_count = db::insert_postal_code_postgres::delete_old_records_postgres(
+            &pool,
+            run.batch_timestamp,
+        )
+        .await
+        .map_err(|e| format!("delete_old_records: {e}"))?
+            as i64;
+        let (inserted_count, updated_count, total_count) =
+            db::audit::compute_postgres_diff_counts(&pool, run.batch_timestamp)
+                .await
+                .map_err(|e| format!("compute_diff_counts: {e}"))?;
+        audit_record.inserted_count = inserted_count;
+        audit_record.updated_count = updated_count;
+        audit_record.total_count = total_count;
+        db::audit::create_postgres_snapshot(&pool, run.data_version)
+            .await
+            .map_err(|e| format!("create_snapshot: {e}"))?;
+        Ok(())
+    }
+    .await;
+
+    match &result {
+        Ok(()) => {
+            audit_record.status = "success".to_string();
+            if let Err(e) =
+                db::municipality::track_municipality_changes_postgres(&pool, run.data_version).await
+            {
+                eprintln!("Error tracking PostgreSQL municipality changes: {e}");
+            }
+            tlog!(
+                "PostgreSQL audit summary: inserted={}, updated={}, deleted={}, total={}",
+                audit_record.inserted_count,
+                audit_record.updated_count,
+                audit_record.deleted_count,
+                audit_record.total_count
+            );
+        }
+        Err(e) => audit_record.error_message = Some(e.clone()),
+    }
+    audit_record.run_finished_at = chrono::Utc::now();
+    if let Err(e) = db::audit::insert_audit_postgres(&pool, &audit_record).await {
+        eprintln!("Error inserting PostgreSQL audit log: {:?}", e);
+    }
+    FullReloadReport {
+        data_updated,
+        result: result
+            .map(|()| FullReloadOutcome::Loaded)
+            .map_err(|e| format!("PostgreSQL full reload failed: {e}")),
+    }
+}
+
+pub async fn run_mysql(run: &FullReloadRun<'_>, rows: &[PostalCode]) -> FullReloadReport {
+    let pool = match db::connection::mysql_connection().await {
+        Ok(pool) => {
+            tlog!("MySQL connected");
+            pool
+        }
+        Err(e) => return FullReloadReport::failed(format!("Error connecting to MySQL: {e}")),
+    };
+
+    if !run.dry_run {
+        if let Err(e) = db::audit::ensure_audit_table_mysql(&pool).await {
+            eprintln!("Error preparing MySQL audit table: {:?}", e);
+        }
+        if let Err(e) = db::schema::ensure_audit_columns_mysql(&pool).await {
+            eprintln!("Error migrating MySQL audit columns: {:?}", e);
+        }
+        if let Err(e) = db::audit::ensure_snapshot_table_mysql(&pool).await {
+            eprintln!("Error preparing MySQL snapshot table: {:?}", e);
+        }
+        if let Err(e) = db::schema::ensure_postal_code_columns_mysql(&pool).await {
+            eprintln!("Error migrating MySQL postal_codes columns: {:?}", e);
+        }
+    }
+
+    let fingerprint = db::audit::dataset_fingerprint(rows);
+    let mut audit_record = run.new_audit_record(rows, &fingerprint);
+    let latest = db::audit::latest_dataset_mysql(&pool)
+        .await
+        .map_err(|e| e.to_string());
+    if let Some(loaded) = unchanged_dataset(latest, &fingerprint, "MySQL") {
+        tlog!("Dataset unchanged since the last run; skipping MySQL writes.");
+        if run.dry_run {
+            return FullReloadReport {
+                data_updated: false,
+                result: Ok(FullReloadOutcome::Unchanged),
+            };
+        }
+        audit_record.total_count = loaded.total_count;
+        audit_record.status = STATUS_UNCHANGED.to_string();
+        audit_record.run_finished_at = chrono::Utc::now();
+        if let Err(e) = db::audit::insert_audit_mysql(&pool, &audit_record).await {
+            eprintln!("Error inserting MySQL audit log: {:?}", e);
+        }
+        return FullReloadReport {
+            data_updated: false,
+            result: Ok(FullReloadOutcome::Unchanged),
+        };
+    }
+
+    if run.dry_run {
+        return FullReloadReport {
+            data_updated: false,
+            result: Ok(FullReloadOutcome::WouldLoad),
+        };
+    }
+
+    let mut data_updated = false;
+    let result: Result<(), String> = async {
+        db::insert_postal_code_mysql::bulk_insert(&pool, rows, run.batch_timestamp)
+            .await
+            .map_err(|e| format!("bulk_insert: {e}"))?;
+        tlog!("Data inserted into MySQL successfully.");
+        data_updated = true;
+        audit_record.deleted_count =
+            db::insert_postal_code_mysql::delete_old_records_mysql(&pool, run.batch_timestamp)
+                .await
+                .map_err(|e| format!("delete_old_records: {e}"))? as i64;
+        let (inserted_count, updated_count, total_count) =
+            db::audit::compute_mysql_diff_counts(&pool, run.batch_timestamp)
+                .await
+                .map_err(|e| format!("compute_diff_counts: {e}"))?;
+        audit_record.inserted_count = inserted_count;
+        audit_record.updated_count = updated_count;
+        audit_record.total_count = total_count;
+        db::audit::create_mysql_snapshot(&pool, run.data_version)
+            .await
+            .map_err(|e| format!("create_snapshot: {e}"))?;
+        Ok(())
+    }
+    .await;
+
+    match &result {
+        Ok(()) => {
+            audit_record.status = "success".to_string();
+            if let Err(e) =
+                db::municipality::track_municipality_changes_mysql(&pool, run.data_version).await
+            {
+                eprintln!("Error tracking MySQL municipality changes: {e}");
+            }
+            tlog!(
+                "MySQL audit summary: inserted={}, updated={}, deleted={}, total={}",
+                audit_record.inserted_count,
+                audit_record.updated_count,
+                audit_record.deleted_count,
+                audit_record.total_count
+            );
+        }
+        Err(e) => audit_record.error_message = Some(e.clone()),
+    }
+    audit_record.run_finished_at = chrono::Utc::now();
+    if let Err(e) = db::audit::insert_audit_mysql(&pool, &audit_record).await {
+        eprintln!("Error inserting MySQL audit log: {:?}", e);
+    }
+    FullReloadReport {
+        data_updated,
+        result: result
+            .map(|()| FullReloadOutcome::Loaded)
+            .map_err(|e| format!("MySQL full reload failed: {e}")),
+    }
+}
@@ -0,0 +1,491 @@
+// Incremental updates from Japan Post's monthly ADD_YYMM / DEL_YYMM archives.
+use crate::db;
+use crate::db::audit::DataUpdateAuditRecord;
+use crate::file;
+use crate::tlog;
+use chrono::FixedOffset;
+use common::models::PostalCode;
+use deadpool_postgres::Pool as PgPool;
+use mysql_async::Pool as MySqlPool;
+
+pub const DEFAULT_DIFF_URL_TEMPLATE: &str =
+    "https://www.post.japanpost.jp/zipcode/dl/kogaki/zip/{kind}_{yymm}.zip";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum UpdateMode {
+    /// Download the national file and replace every row (default).
+    #[default]
+    Full,
+    /// Apply monthly add/delete diffs, falling back to a full reload when a month is missing.
+    Incremental,
+}
+
+pub fn parse_update_mode(raw: &str) -> Result<UpdateMode, String> {
+    match raw.trim().to_ascii_lowercase().as_str() {
+        "full" => Ok(UpdateMode::Full),
+        "incremental" => Ok(UpdateMode::Incremental),
+        _ => Err(format!(
+            "CRAWLER_UPDATE_MODE must be one of: full | incremental (received: {raw})"
+        )),
+    }
+}
+
+/// The monthly diffs cover KEN_ALL only, so JIGYOSYO_URL rows go stale between the
+/// full reloads that incremental mode falls back to.
+pub fn jigyosyo_warning(mode: UpdateMode, jigyosyo_url: Option<&str>) -> Option<String> {
+    (mode == UpdateMode::Incremental && jigyosyo_url.is_some()).then(|| {
+        "CRAWLER_UPDATE_MODE=incremental does not refresh JIGYOSYO_URL rows; \
+         they are only updated when a cycle falls back to a full reload"
+            .to_string()
+    })
+}
+
+/// Japan Post names diff archives after the publishing month in JST (YYMM).
+pub fn jst_month(at: chrono::DateTime<chrono::Utc>) -> String {
+    let jst = FixedOffset::east_opt(9 * 3600).expect("valid JST offset");
+    at.with_timezone(&jst).format("%y%m").to_string()
+}
+
+fn parse_month(yymm: &str) -> Option<(i32, u32)> {
+    if yymm.len() != 4 || !yymm.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let year: i32 = yymm[..2].parse().ok()?;
+    let month: u32 = yymm[2..].parse().ok()?;
+    (1..=12).contains(&month).then_some((year, month))
+}
+
+pub fn next_month(yymm: &str) -> Option<String> {
+    let (year, month) = parse_month(yymm)?;
+    let (year, month) = if month == 12 {
+        ((year + 1) % 100, 1)
+    } else {
+        (year, month + 1)
+    };
+    Some(format!("{year:02}{month:02}"))
+}
+
+pub fn previous_month(yymm: &str) -> Option<String> {
+    let (year, month) = parse_month(yymm)?;
+    let (year, month) = if month == 1 {
+        ((year + 99) % 100, 12)
+    } else {
+        (year, month - 1)
+    };
+    Some(format!("{year:02}{month:02}"))
+}
+
+/// Months after `last_applied` up to and including `current`, oldest first.
+pub fn pending_months(last_applied: &str, current: &str) -> Vec<String> {
+    let mut months = Vec::new();
+    let mut cursor = last_applied.to_string();
+    while cursor.as_str() < current {
+        let Some(next) = next_month(&cursor) else {
+            break;
+        };
+        months.push(next.clone());
+        cursor = next;
+    }
+    months
+}
+
+/// The diff month a full reload is assumed to include.
+/// Re-applying a diff is idempotent, so the previous month is the safe choice.
+pub fn full_reload_diff_month(run_started_at: chrono::DateTime<chrono::Utc>) -> Option<String> {
+    previous_month(&jst_month(run_started_at))
+}
+
+pub fn build_diff_url(template: &str, kind: &str, yymm: &str) -> String {
+    template.replace("{kind}", kind).replace("{yymm}", yymm)
+}
+
+pub struct MonthlyDiff {
+    pub month: String,
+    pub added: Vec<PostalCode>,
+    pub deleted: Vec<PostalCode>,
+}
+
+pub enum DiffPlan {
+    /// Every published diff has already been applied.
+    UpToDate,
+    Apply(Vec<MonthlyDiff>),
+    /// The diff chain cannot be continued; the reason is logged and audited.
+    FullReload(String),
+}
+
+// Returns `None` when the archive has not been published (HTTP 404).
+async fn fetch_diff_file(url: &str, file_stem: &str) -> Result<Option<Vec<PostalCode>>, String> {
+    let csv_path = match file::download_and_extract(url, file_stem).await {
+        Ok(path) => path,
+        Err(e) if e.is_not_found() => return Ok(None),
+        Err(e) => return Err(format!("Failed to fetch {url}: {e}")),
+    };
+    file::parse::csv::csv_stream_format(&csv_path, false)
+        .await
+        .map(Some)
+        .map_err(|e| format!("Failed to parse {url}: {e}"))
+}
+
+pub async fn plan_diffs(
+    last_applied: Option<&str>,
+    current_month: &str,
+    template: &str,
+) -> Result<DiffPlan, String> {
+    let Some(last_applied) = last_applied else {
+        return Ok(DiffPlan::FullReload(
+            "no diff month recorded by the last successful run".to_string(),
+        ));
+    };
+    if last_applied >= current_month {
+        return Ok(DiffPlan::UpToDate);
+    }
+
+    let months = pending_months(last_applied, current_month);
+    // Japan Post keeps roughly a year of diffs online; older gaps need a full reload anyway.
+    if months.len() > 12 {
+        return Ok(DiffPlan::FullReload(format!(
+            "last applied diff {last_applied} is more than a year old"
+        )));
+    }
+
+    let mut diffs = Vec::new();
+    for month in months {
+        let add_url = build_diff_url(template, "add", &month);
+        let del_url = build_diff_url(template, "del", &month);
+        let added = fetch_diff_file(&add_url, &format!("add_{month}")).await?;
+        let deleted = fetch_diff_file(&del_url, &format!("del_{month}")).await?;
+        match (added, deleted) {
+            (Some(added), Some(deleted)) => {
+                tlog!(
+                    "Diff {}: {} added, {} deleted",
+                    month,
+                    added.len(),
+                    deleted.len()
+                );
+                diffs.push(MonthlyDiff {
+                    month,
+                    added,
+                    deleted,
+                });
+            }
+            // The current month is only published at month-end; stop without failing.
+            _ if month == current_month => break,
+            _ => {
+                return Ok(DiffPlan::FullReload(format!(
+                    "diff archive for {month} is missing"
+                )))
+            }
+        }
+    }
+
+    if diffs.is_empty() {
+        Ok(DiffPlan::UpToDate)
+    } else {
+        Ok(DiffPlan::Apply(diffs))
+    }
+}
+
+pub enum IncrementalOutcome {
+    Applied,
+    /// Dry run: the monthly diffs that would be applied.
+    WouldApply(Vec<String>),
+    UpToDate,
+    FullReload(String),
+}
+
+pub struct IncrementalRun<'a> {
+    pub data_version: &'a str,
+    pub run_started_at: chrono::DateTime<chrono::Utc>,
+    pub batch_timestamp: chrono::NaiveDateTime,
+    pub diff_url_template: &'a str,
+    /// Plan the diffs, but write nothing (no migrations or audit).
+    pub dry_run: bool,
+}
+
+impl IncrementalRun<'_> {
+    fn new_audit_record(&self, diffs: &[MonthlyDiff]) -> DataUpdateAuditRecord {
+        let records_in_feed = diffs
+            .iter()
+            .map(|d| (d.added.len() + d.deleted.len()) as i64)
+            .sum();
+        let months: Vec<&str> = diffs.iter().map(|d| d.month.as_str()).collect();
+        DataUpdateAuditRecord {
+            data_version: self.data_version.to_string(),
+            source_url: format!("{} ({})", self.diff_url_template, months.join(",")),
+            run_started_at: self.run_started_at,
+            run_finished_at: chrono::Utc::now(),
+            batch_timestamp: self.batch_timestamp,
+            records_in_feed,
+            inserted_count: 0,
+            updated_count: 0,
+            deleted_count: 0,
+            total_count: 0,
+            status: "failed".to_string(),
+            error_message: None,
+            diff_month: diffs.last().map(|d| d.month.clone()),
+            source_sha256: None,
+            dataset_fingerprint: None,
+        }
+    }
+}
+
+// Creates or migrates the tables an incremental run writes to.
+async fn prepare_postgres(pool: &PgPool) -> Result<(), String> {
+    db::audit::ensure_audit_table_postgres(pool)
+        .await
+        .map_err(|e| format!("Error preparing PostgreSQL audit table: {e}"))?;
+    db::schema::ensure_audit_columns_postgres(pool)
+        .await
+        .map_err(|e| format!("Error migrating PostgreSQL audit columns: {e}"))?;
+    db::audit::ensure_snapshot_table_postgres(pool)
+        .await
+        .map_err(|e| format!("Error preparing PostgreSQL snapshot table: {e}"))?;
+    db::schema::ensure_postal_code_columns_postgres(pool)
+        .await
+        .map_err(|e| format!("Error migrating PostgreSQL postal_codes columns: {e}"))?;
+    db::schema::ensure_search_indexes_postgres(pool)
+        .await
+        .map_err(|e| format!("Error preparing PostgreSQL search indexes: {e}"))?;
+    Ok(())
+}
+
+pub async fn run_postgres(run: &IncrementalRun<'_>) -> Result<IncrementalOutcome, String> {
+    let pool = db::connection::postgres_connection()
+        .await
+        .map_err(|e| format!("Error connecting to PostgreSQL: {e}"))?;
+    if !run.dry_run {
+        prepare_postgres(&pool).await?;
+    }
+
+    let last_applied = db::audit::latest_diff_month_postgres(&pool)
+        .await
+        .map_err(|e| format!("Error reading PostgreSQL diff month: {e}"))?;
+    let current_month = jst_month(run.run_started_at);
+    let diffs = match plan_diffs(
+        last_applied.as_deref(),
+        &current_month,
+        run.diff_url_template,
+    )
+    .await?
+    {
+        DiffPlan::UpToDate => return Ok(IncrementalOutcome::UpToDate),
+        DiffPlan::FullReload(reason) => return Ok(IncrementalOutcome::FullReload(reason)),
+        DiffPlan::Apply(diffs) => diffs,
+    };
+    if run.dry_run {
+        let months = diffs.iter().map(|d| d.month.clone()).collect();
+        return Ok(IncrementalOutcome::WouldApply(months));
+    }
+
+    let mut audit_record = run.new_audit_record(&diffs);
+    let result: Result<(), String> = async {
+        for diff in &diffs {
+            audit_record.deleted_count +=
+                db::insert_postal_code_postgres::delete_postal_codes_postgres(&pool, &diff.deleted)
+                    .await
+                    .map_err(|e| format!("delete_diff_rows: {e}"))? as i64;
+            db::insert_postal_code_postgres::bulk_insert_async(
+                &pool,
+                &diff.added,
+                run.batch_timestamp,
+            )
+            .await
+            .map_err(|e| format!("bulk_insert: {e}"))?;
+        }
+        let (inserted_count, updated_count, total_count) =
+            db::audit::compute_postgres_diff_counts(&pool, run.batch_timestamp)
+                .await
+                .map_err(|e| format!("compute_diff_counts: {e}"))?;
+        audit_record.inserted_count = inserted_count;
+        audit_record.updated_count = updated_count;
+        audit_record.total_count = total_count;
+        db::audit::create_postgres_snapshot(&pool, run.data_version)
+            .await
+            .map_err(|e| format!("create_snapshot: {e}"))?;
+        Ok(())
+    }
+    .await;
+
+    match &result {
+        Ok(()) => {
+            audit_record.status = "success".to_string();
+            if let Err(e) =
+                db::municipality::track_municipality_changes_postgres(&pool, run.data_version).await
+            {
+                eprintln!("Error tracking PostgreSQL municipality changes: {e}");
+            }
+        }
+        Err(e) => audit_record.error_message = Some(e.clone()),
+    }
+    audit_record.run_finished_at = chrono::Utc::now();
+    if let Err(e) = db::audit::insert_audit_postgres(&pool, &audit_record).await {
+        eprintln!("Error inserting PostgreSQL audit log: {:?}", e);
+    }
+    result?;
+
+    tlog!(
+        "PostgreSQL diff summary: months={:?}, inserted={}, updated={}, deleted={}, total={}",
+        audit_record.diff_month,
+        audit_record.inserted_count,
+        audit_record.updated_count,
+        audit_record.deleted_count,
+        audit_record.total_count
+    );
+    Ok(IncrementalOutcome::Applied)
+}
+
+// Creates or migrates the tables an incremental run writes to.
+async fn prepare_mysql(pool: &MySqlPool) -> Result<(), String> {
+    db::audit::ensure_audit_table_mysql(pool)
+        .await
+        .map_err(|e| format!("Error preparing MySQL audit table: {e}"))?;
+    db::schema::ensure_audit_columns_mysql(pool)
+        .await
+        .map_err(|e| format!("Error migrating MySQL audit columns: {e}"))?;
+    db::audit::ensure_snapshot_table_mysql(pool)
+        .await
+        .map_err(|e| format!("Error preparing MySQL snapshot table: {e}"))?;
+    db::schema::ensure_postal_code_columns_mysql(pool)
+        .await
+        .map_err(|e| format!("Error migrating MySQL postal_codes columns: {e}"))?;
+    Ok(())
+}
+
+pub async fn run_mysql(run: &IncrementalRun<'_>) -> Result<IncrementalOutcome, String> {
+    let pool = db::connection::mysql_connection()
+        .await
+        .map_err(|e| format!("Error connecting to MySQL: {e}"))?;
+    if !run.dry_run {
+        prepare_mysql(&pool).await?;
+    }
+
+    let last_applied = db::audit::latest_diff_month_mysql(&pool)
+        .await
+        .map_err(|e| format!("Error reading MySQL diff month: {e}"))?;
+    let current_month = jst_month(run.run_started_at);
+    let diffs = match plan_diffs(
+        last_applied.as_deref(),
+        &current_month,
+        run.diff_url_template,
+    )
+    .await?
+    {
+        DiffPlan::UpToDate => return Ok(IncrementalOutcome::UpToDate),
+        DiffPlan::FullReload(reason) => return Ok(IncrementalOutcome::FullReload(reason)),
+        DiffPlan::Apply(diffs) => diffs,
+    };
+    if run.dry_run {
+        let months = diffs.iter().map(|d| d.month.clone()).collect();
+        return Ok(IncrementalOutcome::WouldApply(months));
+    }
+
+    let mut audit_record = run.new_audit_record(&diffs);
+    let result: Result<(), String> = async {
+        for diff in &diffs {
+            audit_record.deleted_count +=
+                db::insert_postal_code_mysql::delete_postal_codes_mysql(&pool, &diff.deleted)
+                    .await
+                    .map_err(|e| format!("delete_diff_rows: {e}"))? as i64;
+            db::insert_postal_code_mysql::bulk_insert(&pool, &diff.added, run.batch_timestamp)
+                .await
+                .map_err(|e| format!("bulk_insert: {e}"))?;
+        }
+        let (inserted_count, updated_count, total_count) =
+            db::audit::compute_mysql_diff_counts(&pool, run.batch_timestamp)
+                .await
+                .map_err(|e| format!("compute_diff_counts: {e}"))?;
+        audit_record.inserted_count = inserted_count;
+        audit_record.updated_count = updated_count;
+        audit_record.total_count = total_count;
+        db::audit::create_mysql_snapshot(&pool, run.data_version)
+            .await
+            .map_err(|e| format!("create_snapshot: {e}"))?;
+        Ok(())
+    }
+    .await;
+
+    match &result {
+        Ok(()) => {
+            audit_record.status = "success".to_string();
+            if let Err(e) =
+                db::municipality::track_municipality_changes_mysql(&pool, run.data_version).await
+            {
+                eprintln!("Error tracking MySQL municipality changes: {e}");
+            }
+        }
+        Err(e) => audit_record.error_message = Some(e.clone()),
+    }
+    audit_record.run_finished_at = chrono::Utc::now();
+    if let Err(e) = db::audit::insert_audit_mysql(&pool, &audit_record).await {
+        eprintln!("Error inserting MySQL audit log: {:?}", e);
+    }
+    result?;
+
+    tlog!(
+        "MySQL diff summary: months={:?}, inserted={}, updated={}, deleted={}, total={}",
+        audit_record.diff_month,
+        audit_record.inserted_count,
+        audit_record.updated_count,
+        audit_record.deleted_count,
+        audit_record.total_count
+    );
+    Ok(IncrementalOutcome::Applied)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        build_diff_url, jigyosyo_warning, jst_month, next_month, parse_update_mode, pending_months,
+        previous_month, UpdateMode, DEFAULT_DIFF_URL_TEMPLATE,
+    };
+
+    #[test]
+    fn parse_update_mode_accepts_supported_values() {
+        assert_eq!(parse_update_mode("full"), Ok(UpdateMode::Full));
+        assert_eq!(
+            parse_update_mode("Incremental"),
+            Ok(UpdateMode::Incremental)
+        );
+        assert!(parse_update_mode("diff").is_err());
+    }
+
+    #[test]
+    fn jigyosyo_warning_only_in_incremental_mode() {
+        let url = Some("https://example.com/jigyosyo.zip");
+        assert!(jigyosyo_warning(UpdateMode::Incremental, url).is_some());
+        assert_eq!(jigyosyo_warning(UpdateMode::Full, url), None);
+        assert_eq!(jigyosyo_warning(UpdateMode::Incremental, None), None);
+    }
+
+    #[test]
+    fn jst_month_uses_japan_time() {
+        let at = chrono::NaiveDate::from_ymd_opt(2026, 1, 31)
+            .expect("valid date")
+            .and_hms_opt(15, 30, 0)
+            .expect("valid time")
+            .and_utc();
+        assert_eq!(jst_month(at), "2602");
+    }
+
+    #[test]
+    fn month_arithmetic_wraps_years() {
+        assert_eq!(next_month("2512").as_deref(), Some("2601"));
+        assert_eq!(previous_month("2601").as_deref(), Some("2512"));
+        assert_eq!(next_month("2513"), None);
+    }
+
+    #[test]
+    fn pending_months_lists_months_after_last_applied() {
+        assert_eq!(pending_months("2511", "2602"), vec!["2512", "2601", "2602"]);
+        assert!(pending_months("2602", "2602").is_empty());
+    }
+
+    #[test]
+    fn build_diff_url_fills_template() {
+        assert_eq!(
+            build_diff_url(DEFAULT_DIFF_URL_TEMPLATE, "add", "2601"),
+            "https://www.post.japanpost.jp/zipcode/dl/kogaki/zip/add_2601.zip"
+        );
+    }
+}
@@ -1,5 +1,12 @@
+pub mod cache;
+pub mod cli;
 pub mod constants;
 pub mod db;
 pub mod file;
+pub mod full_reload;
+pub mod incremental;
+pub mod ops;
+pub mod rollback;
+pub mod schedule;
 #[macro_use]
 pub mod utils;
@@ -1,106 +1,33 @@
+mod cache;
+mod cli;
 mod constants;
 mod db;
 mod file;
+mod full_reload;
+mod incremental;
+mod ops;
+mod rollback;
+mod schedule;
 mod utils;
 use chrono::Timelike;
-use constants::temp_dir;
-use db::audit::{build_data_version, DataUpdateAuditRecord};
-use redis::AsyncCommands;
-use tokio::time::{sleep, Duration};
-
-async fn invalidate_redis_cache() {
-    let Ok(redis_url) = std::env::var("REDIS_URL") else {
-        return;
-    };
-
-    let client = match redis::Client::open(redis_url) {
-        Ok(client) => client,
-        Err(e) => {
-            eprintln!("Invalid REDIS_URL for crawler cache invalidation: {e}");
-            return;
-        }
-    };
-
-    let mut conn = match client.get_multiplexed_async_connection().await {
-        Ok(conn) => conn,
-        Err(e) => {
-            eprintln!("Failed to connect Redis for cache invalidation: {e}");
-            return;
-        }
-    };
-
-    let mut cursor: u64 = 0;
-    let mut deleted = 0usize;
-
-    loop {
-        let scan_result: redis::RedisResult<(u64, Vec<String>)> = redis::cmd("SCAN")
-            .arg(cursor)
-            .arg("MATCH")
-            .arg("postal:*")
-            .arg("COUNT")
-            .arg(500)
-            .query_async(&mut conn)
-            .await;
-
-        let (next_cursor, keys) = match scan_result {
-            Ok(v) => v,
-            Err(e) => {
-                eprintln!("Failed to scan Redis cache keys: {e}");
-                return;
-            }
-        };
-
-        if !keys.is_empty() {
-            let result: redis::RedisResult<()> = conn.del(&keys).await;
-            if let Err(e) = result {
-                eprintln!("Failed to delete Redis cache keys: {e}");
-                return;
-            }
-            deleted += keys.len();
-        }
-
-        if next_cursor == 0 {
-            break;
-        }
-        cursor = next_cursor;
-    }
-
-    tlog!(
-        "Redis cache invalidated for postal:* (deleted {} keys).",
-        deleted
-    );
+use cli::{Command, Options, OutputFormat, Parsed, Report, SnapshotCommand};
+use db::audit::build_data_version;
+use file::local::ImportSource;
+use full_reload::{FullReloadOutcome, FullReloadRun};
+use incremental::{IncrementalOutcome, IncrementalRun, UpdateMode};
+use schedule::Schedule;
+use serde::Serialize;
+use tokio::time::sleep;
+
+/// Version and timestamps shared by the rows, audit entry and snapshot of one run.
+struct RunClock {
+    data_version: String,
+    run_started_at: chrono::DateTime<chrono::Utc>,
+    batch_timestamp: chrono::NaiveDateTime,
 }
 
-#[tokio::main]
-async fn main() {
-    // Load .env file
-    if dotenv::from_filename(".env").is_err() {
-        // Try loading from crawler directory if running from workspace root
-        dotenv::from_filename("crawler/.env").ok();
-    }
-    let zip_code_url = std::env::var("ZIP_CODE_URL").expect("ZIP_CODE_URL not set");
-    // Default sleep duration: 24 hours (in seconds)
-    let sleep_seconds: u64 = std::env::var("CRAWLER_INTERVAL_SECONDS")
-        .unwrap_or_else(|_| "86400".to_string())
-        .parse()
-        .expect("CRAWLER_INTERVAL_SECONDS must be a number");
-    let run_once = std::env::var("CRAWLER_RUN_ONCE")
-        .map(|v| {
-            let value = v.to_ascii_lowercase();
-            value == "1" || value == "true" || value == "yes"
-        })
-        .unwrap_or(false);
-
-    let tmp_path_name = format!("{}/{}", temp_dir().to_str().unwrap(), "utf_ken_all.zip");
-    let in_optimize_file_path_name = format!(
-        "{}/{}",
-        temp_dir().to_str().unwrap(),
-        "utf_ken_all_optimize.zip"
-    );
-    let out_file_path = format!("{}/{}", temp_dir().to_str().unwrap(), "utf_ken_all.csv");
-
-    loop {
-        tlog!("Starting crawler cycle...");
+impl RunClock {
+    fn start() -> Self {
         let run_started_at = chrono::Utc::now();
         let batch_now = chrono::Utc::now().naive_utc();
         let batch_timestamp = batch_now
@@ -109,281 +36,448 @@ async fn main() {
         let data_version = build_data_version(batch_now);
         tlog!("Batch timestamp: {:?}", batch_timestamp);
         tlog!("Data version: {}", data_version);
+        RunClock {
+            data_version,
+            run_started_at,
+            batch_timestamp,
+        }
+    }
+}
 
-        // file download
-        tlog!("{}", &zip_code_url);
+// --database-type, then DATABASE_TYPE from environment (default: postgres)
+fn database_type(options: &Options) -> String {
+    options.database_type.clone().unwrap_or_else(|| {
+        std::env::var("DATABASE_TYPE").unwrap_or_else(|_| "postgres".to_string())
+    })
+}
 
-        if let Err(e) =
-            file::download::fetch_stream(&tmp_path_name, &in_optimize_file_path_name, &zip_code_url)
-                .await
-        {
-            eprintln!("Failed to download: {:?}", e);
-            tlog!("Retrying in {} seconds...", sleep_seconds);
-            sleep(Duration::from_secs(sleep_seconds)).await;
-            continue;
-        }
+/// Settings of `run` and `once`, read from the environment.
+#[derive(Serialize)]
+struct DaemonConfig {
+    database_type: String,
+    zip_code_url: String,
+    /// Optional business-office (JIGYOSYO) feed, loaded in the same batch as KEN_ALL.
+    jigyosyo_url: Option<String>,
+    update_mode: UpdateMode,
+    diff_url_template: String,
+    #[serde(flatten)]
+    schedule: Schedule,
+    /// Upcoming scheduled runs in JST, shown by `run --dry-run`.
+    next_runs: Vec<String>,
+    run_once: bool,
+}
 
-        // file unfreeze
-        if let Err(e) = file::unfreeze::unzip(&in_optimize_file_path_name, &out_file_path) {
-            eprintln!("Failed to unzip: {:?}", e);
-            tlog!("Retrying in {} seconds...", sleep_seconds);
-            sleep(Duration::from_secs(sleep_seconds)).await;
-            continue;
+impl DaemonConfig {
+    fn from_env(database_type: String) -> Result<Self, String> {
+        let zip_code_url =
+            std::env::var("ZIP_CODE_URL").map_err(|_| "ZIP_CODE_URL not set".to_string())?;
+        let schedule = Schedule::from_env()?;
+        let next_runs = schedule
+            .upcoming(chrono::Utc::now(), 5)
+            .into_iter()
+            .map(schedule::format_jst)
+            .collect();
+        let run_once = std::env::var("CRAWLER_RUN_ONCE")
+            .map(|v| {
+                let value = v.to_ascii_lowercase();
+                value == "1" || value == "true" || value == "yes"
+            })
+            .unwrap_or(false);
+        let update_mode = incremental::parse_update_mode(
+            &std::env::var("CRAWLER_UPDATE_MODE").unwrap_or_else(|_| "full".to_string()),
+        )?;
+        let diff_url_template = std::env::var("ZIP_CODE_DIFF_URL_TEMPLATE")
+            .unwrap_or_else(|_| incremental::DEFAULT_DIFF_URL_TEMPLATE.to_string());
+        let jigyosyo_url = std::env::var("JIGYOSYO_URL")
+            .ok()
+            .filter(|v| !v.trim().is_empty());
+        if let Some(warning) = incremental::jigyosyo_warning(update_mode, jigyosyo_url.as_deref()) {
+            tlog!("Warning: {}", warning);
         }
-        // postal code csv file format
-        let csv_map = match file::parse::csv::csv_stream_format(&out_file_path, false).await {
-            Ok(data) => data,
-            Err(e) => {
-                eprintln!("Error reading CSV file: {:?}", e);
-                tlog!("Retrying in {} seconds...", sleep_seconds);
-                sleep(Duration::from_secs(sleep_seconds)).await;
-                continue;
-            }
-        };
+        Ok(DaemonConfig {
+            database_type,
+            zip_code_url,
+            jigyosyo_url,
+            update_mode,
+            diff_url_template,
+            schedule,
+            next_runs,
+            run_once,
+        })
+    }
+}
 
-        // Read DATABASE_TYPE from environment (default: postgres)
-        let database_type =
-            std::env::var("DATABASE_TYPE").unwrap_or_else(|_| "postgres".to_string());
-        tlog!("Using database type: {}", database_type);
-        let records_in_feed = csv_map.len() as i64;
-        let mut data_updated = false;
+impl Report for DaemonConfig {
+    fn text(&self) -> String {
+        format!(
+            "database_type={}\nzip_code_url={}\njigyosyo_url={}\nupdate_mode={:?}\ndiff_url_template={}\nschedule={}\nretry_seconds={}..{}\njitter_seconds={}\nblackout={}\nrun_once={}\nnext_runs:\n  {}",
+            self.database_type,
+            self.zip_code_url,
+            self.jigyosyo_url.as_deref().unwrap_or("-"),
+            self.update_mode,
+            self.diff_url_template,
+            self.schedule.schedule,
+            self.schedule.retry_initial_seconds,
+            self.schedule.retry_max_seconds,
+            self.schedule.jitter_seconds,
+            if self.schedule.blackout.is_empty() {
+                "-".to_string()
+            } else {
+                self.schedule.blackout.join(",")
+            },
+            self.run_once,
+            self.next_runs.join("\n  ")
+        )
+    }
+}
 
-        // MySQL connection and insertion (only if DATABASE_TYPE is mysql)
-        if database_type == "mysql" {
-            let mysql_pool = match db::connection::mysql_connection().await {
-                Ok(pool) => {
-                    println!("MySQL connected");
-                    pool
-                }
-                Err(e) => {
-                    eprintln!("Error connecting to MySQL: {:?}", e);
-                    tlog!("Retrying in {} seconds...", sleep_seconds);
-                    sleep(Duration::from_secs(sleep_seconds)).await;
-                    continue;
-                }
-            };
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum CycleOutcome {
+    /// A full feed was written, audited and snapshotted.
+    Loaded,
+    /// The parsed feed matched the loaded data; only an audit row was written.
+    Unchanged,
+    /// The server reported every source file unchanged; nothing was parsed.
+    NotModified,
+    DiffsApplied,
+    /// Every published monthly diff is already applied.
+    UpToDate,
+    WouldLoad,
+    WouldApplyDiffs,
+}
 
-            if let Err(e) = db::audit::ensure_audit_table_mysql(&mysql_pool).await {
-                eprintln!("Error preparing MySQL audit table: {:?}", e);
-            }
-            if let Err(e) = db::audit::ensure_snapshot_table_mysql(&mysql_pool).await {
-                eprintln!("Error preparing MySQL snapshot table: {:?}", e);
-            }
+/// What one update cycle or import did.
+#[derive(Serialize)]
+struct CycleReport {
+    data_version: String,
+    outcome: CycleOutcome,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    records_in_feed: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    source_url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    source_sha256: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    diff_months: Vec<String>,
+    /// Why incremental mode fell back to a full reload.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    fallback_reason: Option<String>,
+}
 
-            let mut audit_record = DataUpdateAuditRecord {
-                data_version: data_version.clone(),
-                source_url: zip_code_url.clone(),
-                run_started_at,
-                run_finished_at: chrono::Utc::now(),
-                batch_timestamp,
-                records_in_feed,
-                inserted_count: 0,
-                updated_count: 0,
-                deleted_count: 0,
-                total_count: 0,
-                status: "failed".to_string(),
-                error_message: None,
-            };
+impl CycleReport {
+    fn new(data_version: &str, outcome: CycleOutcome) -> Self {
+        CycleReport {
+            data_version: data_version.to_string(),
+            outcome,
+            records_in_feed: None,
+            source_url: None,
+            source_sha256: None,
+            diff_months: Vec::new(),
+            fallback_reason: None,
+        }
+    }
+}
 
-            match db::insert_postal_code_mysql::bulk_insert(&mysql_pool, &csv_map, batch_timestamp)
-                .await
-            {
-                Err(e) => {
-                    eprintln!("Error inserting data into MySQL: {:?}", e);
-                    audit_record.error_message = Some(format!("bulk_insert: {e}"));
-                }
-                Ok(()) => {
-                    tlog!("Data inserted into MySQL successfully.");
-                    data_updated = true;
+impl Report for CycleReport {
+    fn text(&self) -> String {
+        let mut text = format!("{}: {:?}", self.data_version, self.outcome);
+        if let Some(records) = self.records_in_feed {
+            text.push_str(&format!(", {records} records in feed"));
+        }
+        if !self.diff_months.is_empty() {
+            text.push_str(&format!(", diffs {}", self.diff_months.join(",")));
+        }
+        text
+    }
+}
 
-                    let deleted_count =
-                        match db::insert_postal_code_mysql::delete_old_records_mysql(
-                            &mysql_pool,
-                            batch_timestamp,
-                        )
-                        .await
-                        {
-                            Ok(count) => count as i64,
-                            Err(e) => {
-                                eprintln!("Error deleting old records from MySQL: {:?}", e);
-                                audit_record.error_message =
-                                    Some(format!("delete_old_records: {e}"));
-                                audit_record.run_finished_at = chrono::Utc::now();
-                                if let Err(log_err) =
-                                    db::audit::insert_audit_mysql(&mysql_pool, &audit_record).await
-                                {
-                                    eprintln!("Error inserting MySQL audit log: {:?}", log_err);
-                                }
-                                continue;
-                            }
-                        };
+fn full_reload_outcome(outcome: FullReloadOutcome) -> CycleOutcome {
+    match outcome {
+        FullReloadOutcome::Loaded => CycleOutcome::Loaded,
+        FullReloadOutcome::Unchanged => CycleOutcome::Unchanged,
+        FullReloadOutcome::WouldLoad => CycleOutcome::WouldLoad,
+    }
+}
 
-                    match db::audit::compute_mysql_diff_counts(&mysql_pool, batch_timestamp).await {
-                        Ok((inserted_count, updated_count, total_count)) => {
-                            audit_record.inserted_count = inserted_count;
-                            audit_record.updated_count = updated_count;
-                            audit_record.deleted_count = deleted_count;
-                            audit_record.total_count = total_count;
-                            audit_record.status = "success".to_string();
-                            if let Err(e) =
-                                db::audit::create_mysql_snapshot(&mysql_pool, &data_version).await
-                            {
-                                eprintln!("Error creating MySQL snapshot: {:?}", e);
-                                audit_record.status = "failed".to_string();
-                                audit_record.error_message = Some(format!("create_snapshot: {e}"));
-                            }
-                            tlog!(
-                                "MySQL audit summary: inserted={}, updated={}, deleted={}, total={}",
-                                inserted_count,
-                                updated_count,
-                                deleted_count,
-                                total_count
-                            );
-                        }
-                        Err(e) => {
-                            eprintln!("Error computing MySQL audit counts: {:?}", e);
-                            audit_record.error_message = Some(format!("compute_diff_counts: {e}"));
-                        }
-                    }
-                }
+/// One update: monthly diffs in incremental mode, otherwise (or as a fallback) a full
+/// reload of the downloaded feed. A dry run downloads and compares, but writes nothing
+/// to the database, the cache or the download state.
+async fn run_cycle(config: &DaemonConfig, dry_run: bool) -> Result<CycleReport, String> {
+    let clock = RunClock::start();
+    tlog!("Using database type: {}", config.database_type);
+
+    let mut fallback_reason = None;
+    if config.update_mode == UpdateMode::Incremental {
+        let run = IncrementalRun {
+            data_version: &clock.data_version,
+            run_started_at: clock.run_started_at,
+            batch_timestamp: clock.batch_timestamp,
+            diff_url_template: &config.diff_url_template,
+            dry_run,
+        };
+        let outcome = match config.database_type.as_str() {
+            "mysql" => incremental::run_mysql(&run).await,
+            "postgres" => incremental::run_postgres(&run).await,
+            other => Err(format!("Unsupported DATABASE_TYPE for crawler: {other}")),
+        };
+        match outcome.map_err(|e| format!("Incremental update failed: {e}"))? {
+            IncrementalOutcome::Applied => {
+                cache::invalidate_after_update().await;
+                return Ok(CycleReport::new(
+                    &clock.data_version,
+                    CycleOutcome::DiffsApplied,
+                ));
+            }
+            IncrementalOutcome::WouldApply(months) => {
+                let mut report =
+                    CycleReport::new(&clock.data_version, CycleOutcome::WouldApplyDiffs);
+                report.diff_months = months;
+                return Ok(report);
+            }
+            IncrementalOutcome::UpToDate => {
+                tlog!("All published diffs are already applied.");
+                return Ok(CycleReport::new(
+                    &clock.data_version,
+                    CycleOutcome::UpToDate,
+                ));
+            }
+            IncrementalOutcome::FullReload(reason) => {
+                tlog!("Falling back to full reload: {}", reason);
+                fallback_reason = Some(reason);
             }
+        }
+    }
 
-            audit_record.run_finished_at = chrono::Utc::now();
-            if let Err(e) = db::audit::insert_audit_mysql(&mysql_pool, &audit_record).await {
-                eprintln!("Error inserting MySQL audit log: {:?}", e);
+    // file download & unfreeze
+    let mut sources = vec![(config.zip_code_url.as_str(), file::local::KEN_ALL_STEM)];
+    if let Some(jigyosyo_url) = &config.jigyosyo_url {
+        sources.push((jigyosyo_url.as_str(), file::local::JIGYOSYO_STEM));
+    }
+    let fetched = match file::fetch_sources(&sources)
+        .await
+        .map_err(|e| format!("Failed to fetch source files: {e}"))?
+    {
+        Some(fetched) => fetched,
+        None => {
+            tlog!("Source files are not modified since the last successful run.");
+            let mut report = CycleReport::new(&clock.data_version, CycleOutcome::NotModified);
+            report.fallback_reason = fallback_reason;
+            return Ok(report);
+        }
+    };
+    let jigyosyo_csv = fetched.get(1).map(|source| source.csv_path.as_str());
+    let rows = full_reload::parse_feed(&fetched[0].csv_path, jigyosyo_csv).await?;
+
+    let run = FullReloadRun {
+        data_version: &clock.data_version,
+        run_started_at: clock.run_started_at,
+        batch_timestamp: clock.batch_timestamp,
+        source_url: &config.zip_code_url,
+        source_sha256: Some(&fetched[0].state.sha256),
+        dry_run,
+    };
+    let report = full_reload::run(&config.database_type, &run, &rows).await;
+    if report.data_updated {
+        cache::invalidate_after_update().await;
+    }
+    let outcome = full_reload_outcome(report.result?);
+    if !dry_run {
+        for source in &fetched {
+            if let Err(e) = file::commit_source(source) {
+                eprintln!("Failed to record {} download state: {e}", source.file_stem);
             }
         }
+    }
 
-        // PostgreSQL connection and insertion (only if DATABASE_TYPE is postgres)
-        if database_type == "postgres" {
-            let postgres_pool = match db::connection::postgres_connection().await {
-                Ok(pool) => {
-                    tlog!("PostgreSQL connected");
-                    pool
+    let mut report = CycleReport::new(&clock.data_version, outcome);
+    report.records_in_feed = Some(rows.len());
+    report.source_url = Some(config.zip_code_url.clone());
+    report.source_sha256 = Some(fetched[0].state.sha256.clone());
+    report.fallback_reason = fallback_reason;
+    Ok(report)
+}
+
+/// Sleeps until `wake`, which is logged in JST.
+async fn sleep_until(wake: chrono::DateTime<chrono::Utc>) {
+    tlog!("Next run at {}", schedule::format_jst(wake));
+    let delay = (wake - chrono::Utc::now()).to_std().unwrap_or_default();
+    sleep(delay).await;
+}
+
+/// `run` and `once`. `run` starts a cycle right away (after any blackout window), then
+/// on the schedule; a failed cycle is retried with backoff, even with CRAWLER_RUN_ONCE,
+/// but never later than the next scheduled run. `once` exits with status 1 instead.
+async fn run_daemon(command: &Command, options: &Options, config: &DaemonConfig) -> i32 {
+    let once = *command == Command::Once;
+    let schedule = &config.schedule;
+    if !once {
+        let now = chrono::Utc::now();
+        let start = schedule.outside_blackouts(now);
+        if start > now {
+            tlog!("Inside a blackout window.");
+            sleep_until(start).await;
+        }
+    }
+    let mut failures = 0;
+    loop {
+        tlog!("Starting crawler cycle...");
+        match run_cycle(config, options.dry_run).await {
+            Ok(report) => cli::print_report(command, options, &report),
+            Err(e) => {
+                cli::print_error(command, options, &e);
+                if once {
+                    return 1;
                 }
-                Err(e) => {
-                    eprintln!("Error connecting to PostgreSQL: {:?}", e);
-                    tlog!("Retrying in {} seconds...", sleep_seconds);
-                    sleep(Duration::from_secs(sleep_seconds)).await;
-                    continue;
+                failures += 1;
+                let (wake, scheduled) = schedule.after_failure(failures, chrono::Utc::now());
+                if scheduled {
+                    failures = 0;
+                } else {
+                    tlog!("Retry {} after failure.", failures);
                 }
-            };
-
-            if let Err(e) = db::audit::ensure_audit_table_postgres(&postgres_pool).await {
-                eprintln!("Error preparing PostgreSQL audit table: {:?}", e);
-            }
-            if let Err(e) = db::audit::ensure_snapshot_table_postgres(&postgres_pool).await {
-                eprintln!("Error preparing PostgreSQL snapshot table: {:?}", e);
+                sleep_until(wake).await;
+                continue;
             }
+        }
 
-            let mut audit_record = DataUpdateAuditRecord {
-                data_version: data_version.clone(),
-                source_url: zip_code_url.clone(),
-                run_started_at,
-                run_finished_at: chrono::Utc::now(),
-                batch_timestamp,
-                records_in_feed,
-                inserted_count: 0,
-                updated_count: 0,
-                deleted_count: 0,
-                total_count: 0,
-                status: "failed".to_string(),
-                error_message: None,
-            };
+        if once {
+            return 0;
+        }
+        if config.run_once {
+            tlog!("CRAWLER_RUN_ONCE enabled. Exiting after one completed cycle.");
+            return 0;
+        }
+        failures = 0;
+        tlog!("Crawler cycle completed.");
+        sleep_until(schedule.next_run(chrono::Utc::now())).await;
+    }
+}
 
-            match db::insert_postal_code_postgres::bulk_insert_async(
-                &postgres_pool,
-                &csv_map,
-                batch_timestamp,
-            )
-            .await
-            {
-                Err(e) => {
-                    eprintln!("Error inserting data into PostgreSQL: {:?}", e);
-                    audit_record.error_message = Some(format!("bulk_insert: {e}"));
-                }
-                Ok(()) => {
-                    tlog!("Data inserted into PostgreSQL successfully.");
-                    data_updated = true;
+/// `crawler import <source>`: one full reload from local files, without network access.
+async fn import(source: &str, database_type: &str, dry_run: bool) -> Result<CycleReport, String> {
+    let feed = file::local::load(&ImportSource::parse(source))
+        .await
+        .map_err(|e| format!("Failed to read {source}: {e}"))?;
+    let jigyosyo_csv = feed.jigyosyo.as_ref().map(|file| file.csv_path.as_str());
+    let rows = full_reload::parse_feed(&feed.ken_all.csv_path, jigyosyo_csv).await?;
+    tlog!("Records in feed: {}", rows.len());
+
+    let clock = RunClock::start();
+    tlog!("Using database type: {}", database_type);
+    let run = FullReloadRun {
+        data_version: &clock.data_version,
+        run_started_at: clock.run_started_at,
+        batch_timestamp: clock.batch_timestamp,
+        source_url: &feed.ken_all.source_url,
+        source_sha256: Some(&feed.ken_all.sha256),
+        dry_run,
+    };
+    let report = full_reload::run(database_type, &run, &rows).await;
+    if report.data_updated {
+        cache::invalidate_after_update().await;
+    }
+    let mut report = CycleReport::new(&clock.data_version, full_reload_outcome(report.result?));
+    report.records_in_feed = Some(rows.len());
+    report.source_url = Some(feed.ken_all.source_url);
+    report.source_sha256 = Some(feed.ken_all.sha256);
+    Ok(report)
+}
 
-                    let deleted_count =
-                        match db::insert_postal_code_postgres::delete_old_records_postgres(
-                            &postgres_pool,
-                            batch_timestamp,
-                        )
-                        .await
-                        {
-                            Ok(count) => count as i64,
-                            Err(e) => {
-                                eprintln!("Error deleting old records from PostgreSQL: {:?}", e);
-                                audit_record.error_message =
-                                    Some(format!("delete_old_records: {e}"));
-                                audit_record.run_finished_at = chrono::Utc::now();
-                                if let Err(log_err) =
-                                    db::audit::insert_audit_postgres(&postgres_pool, &audit_record)
-                                        .await
-                                {
-                                    eprintln!(
-                                        "Error inserting PostgreSQL audit log: {:?}",
-                                        log_err
-                                    );
-                                }
-                                continue;
-                            }
-                        };
+/// Prints the outcome of a one-shot command and returns the exit status.
+fn finish<T: Report>(command: &Command, options: &Options, result: Result<T, String>) -> i32 {
+    match result {
+        Ok(report) => {
+            cli::print_report(command, options, &report);
+            0
+        }
+        Err(e) => {
+            cli::print_error(command, options, &e);
+            1
+        }
+    }
+}
 
-                    match db::audit::compute_postgres_diff_counts(&postgres_pool, batch_timestamp)
-                        .await
-                    {
-                        Ok((inserted_count, updated_count, total_count)) => {
-                            audit_record.inserted_count = inserted_count;
-                            audit_record.updated_count = updated_count;
-                            audit_record.deleted_count = deleted_count;
-                            audit_record.total_count = total_count;
-                            audit_record.status = "success".to_string();
-                            if let Err(e) =
-                                db::audit::create_postgres_snapshot(&postgres_pool, &data_version)
-                                    .await
-                            {
-                                eprintln!("Error creating PostgreSQL snapshot: {:?}", e);
-                                audit_record.status = "failed".to_string();
-                                audit_record.error_message = Some(format!("create_snapshot: {e}"));
-                            }
-                            tlog!(
-                                "PostgreSQL audit summary: inserted={}, updated={}, deleted={}, total={}",
-                                inserted_count,
-                                updated_count,
-                                deleted_count,
-                                total_count
-                            );
-                        }
-                        Err(e) => {
-                            eprintln!("Error computing PostgreSQL audit counts: {:?}", e);
-                            audit_record.error_message = Some(format!("compute_diff_counts: {e}"));
-                        }
-                    }
+#[tokio::main]
+async fn main() {
+    // Load .env file
+    if dotenv::from_filename(".env").is_err() {
+        // Try loading from crawler directory if running from workspace root
+        dotenv::from_filename("crawler/.env").ok();
+    }
+
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let (command, options) = match cli::parse(&args) {
+        Ok(Parsed::Invocation(command, options)) => (command, options),
+        Ok(Parsed::Help(usage)) => {
+            println!("{usage}");
+            return;
+        }
+        Err(e) => {
+            eprintln!("{}\n\n{}", e.message, e.usage);
+            std::process::exit(2);
+        }
+    };
+    if options.output == OutputFormat::Json {
+        utils::tlog::log_to_stderr();
+    }
+    let database_type = database_type(&options);
+    let dry_run = options.dry_run;
+
+    let status = match &command {
+        Command::Run | Command::Once => match DaemonConfig::from_env(database_type) {
+            Ok(config) if dry_run && command == Command::Run => {
+                cli::print_report(&command, &options, &config);
+                0
+            }
+            Ok(config) => run_daemon(&command, &options, &config).await,
+            Err(e) => {
+                cli::print_error(&command, &options, &e);
+                1
+            }
+        },
+        Command::Import { source } => {
+            let result = import(source, &database_type, dry_run).await;
+            finish(&command, &options, result)
+        }
+        Command::Verify => match ops::verify(&database_type).await {
+            Ok(verification) => {
+                cli::print_report(&command, &options, &verification);
+                if verification.passed {
+                    0
+                } else {
+                    1
                 }
             }
-
-            audit_record.run_finished_at = chrono::Utc::now();
-            if let Err(e) = db::audit::insert_audit_postgres(&postgres_pool, &audit_record).await {
-                eprintln!("Error inserting PostgreSQL audit log: {:?}", e);
+            Err(e) => {
+                cli::print_error(&command, &options, &e);
+                1
             }
+        },
+        Command::Snapshot(SnapshotCommand::List) => {
+            let result = ops::snapshot_list(&database_type).await;
+            finish(&command, &options, result)
         }
-
-        if data_updated {
-            invalidate_redis_cache().await;
+        Command::Snapshot(SnapshotCommand::Prune { keep }) => {
+            let result = ops::snapshot_prune(&database_type, *keep, dry_run).await;
+            finish(&command, &options, result)
         }
-
-        if run_once {
-            tlog!("CRAWLER_RUN_ONCE enabled. Exiting after one completed cycle.");
-            break;
+        Command::Snapshot(SnapshotCommand::Export { data_version, out }) => {
+            let result = ops::snapshot_export(&database_type, data_version, out, dry_run).await;
+            finish(&command, &options, result)
         }
-
-        tlog!(
-            "Crawler cycle completed. Sleeping for {} seconds...",
-            sleep_seconds
-        );
-        sleep(Duration::from_secs(sleep_seconds)).await;
-    }
+        Command::AuditList { limit } => {
+            let result = ops::audit_list(&database_type, *limit).await;
+            finish(&command, &options, result)
+        }
+        Command::CacheInvalidate => {
+            let result = ops::cache_invalidate(dry_run).await;
+            finish(&command, &options, result)
+        }
+        Command::Rollback { data_version } => {
+            let result = rollback::rollback(&database_type, data_version, dry_run).await;
+            finish(&command, &options, result)
+        }
+    };
+    std::process::exit(status);
 }
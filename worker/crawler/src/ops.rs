@@ -0,0 +1,483 @@
+// Maintenance commands: snapshot, audit, verify and cache invalidation.
+use crate::cache::CacheInvalidation;
+use crate::cli::Report;
+use crate::db;
+use crate::db::audit::AuditEntry;
+use crate::db::schema::POSTAL_CODE_COLUMNS;
+use crate::db::snapshot::SnapshotVersion;
+use crate::rollback::RollbackReport;
+use common::audit::STATUS_UNCHANGED;
+use deadpool_postgres::Pool as PgPool;
+use mysql_async::Pool as MySqlPool;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+
+/// Audits `verify` looks back through for the latest completed load.
+const VERIFY_AUDIT_WINDOW: i64 = 100;
+
+enum DbPool {
+    Postgres(PgPool),
+    MySql(MySqlPool),
+}
+
+async fn connect(database_type: &str) -> Result<DbPool, String> {
+    match database_type {
+        "postgres" => db::connection::postgres_connection()
+            .await
+            .map(DbPool::Postgres)
+            .map_err(|e| format!("Error connecting to PostgreSQL: {e}")),
+        "mysql" => db::connection::mysql_connection()
+            .await
+            .map(DbPool::MySql)
+            .map_err(|e| format!("Error connecting to MySQL: {e}")),
+        other => Err(format!("Unsupported DATABASE_TYPE for crawler: {other}")),
+    }
+}
+
+impl DbPool {
+    async fn list_snapshots(&self) -> Result<Vec<SnapshotVersion>, String> {
+        match self {
+            DbPool::Postgres(pool) => db::snapshot::list_snapshots_postgres(pool)
+                .await
+                .map_err(|e| e.to_string()),
+            DbPool::MySql(pool) => db::snapshot::list_snapshots_mysql(pool)
+                .await
+                .map_err(|e| e.to_string()),
+        }
+        .map_err(|e| format!("Error listing snapshots: {e}"))
+    }
+
+    async fn snapshot_row_count(&self, data_version: &str) -> Result<i64, String> {
+        match self {
+            DbPool::Postgres(pool) => db::snapshot::snapshot_row_count_postgres(pool, data_version)
+                .await
+                .map_err(|e| e.to_string()),
+            DbPool::MySql(pool) => db::snapshot::snapshot_row_count_mysql(pool, data_version)
+                .await
+                .map_err(|e| e.to_string()),
+        }
+        .map_err(|e| format!("Error counting snapshot {data_version}: {e}"))
+    }
+
+    async fn latest_live_snapshot(&self) -> Result<Option<String>, String> {
+        match self {
+            DbPool::Postgres(pool) => db::audit::latest_live_snapshot_postgres(pool)
+                .await
+                .map_err(|e| e.to_string()),
+            DbPool::MySql(pool) => db::audit::latest_live_snapshot_mysql(pool)
+                .await
+                .map_err(|e| e.to_string()),
+        }
+        .map_err(|e| format!("Error reading the live snapshot: {e}"))
+    }
+
+    async fn list_audits(&self, limit: i64) -> Result<Vec<AuditEntry>, String> {
+        match self {
+            DbPool::Postgres(pool) => db::audit::list_audits_postgres(pool, limit)
+                .await
+                .map_err(|e| e.to_string()),
+            DbPool::MySql(pool) => db::audit::list_audits_mysql(pool, limit)
+                .await
+                .map_err(|e| e.to_string()),
+        }
+        .map_err(|e| format!("Error listing audits: {e}"))
+    }
+}
+
+#[derive(Serialize)]
+pub struct SnapshotList {
+    pub snapshots: Vec<SnapshotVersion>,
+}
+
+impl Report for SnapshotList {
+    fn text(&self) -> String {
+        if self.snapshots.is_empty() {
+            return "No snapshots.".to_string();
+        }
+        self.snapshots
+            .iter()
+            .map(|s| format!("{}\t{} rows", s.data_version, s.row_count))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+pub async fn snapshot_list(database_type: &str) -> Result<SnapshotList, String> {
+    let pool = connect(database_type).await?;
+    Ok(SnapshotList {
+        snapshots: pool.list_snapshots().await?,
+    })
+}
+
+#[derive(Serialize)]
+pub struct SnapshotPrune {
+    /// The newest `keep` versions, plus the live one (the latest update or rollback target).
+    pub kept: Vec<String>,
+    /// Versions deleted, or the versions a dry run would delete.
+    pub pruned: Vec<SnapshotVersion>,
+    /// Snapshot rows deleted; `0` for a dry run.
+    pub deleted_rows: u64,
+}
+
+impl Report for SnapshotPrune {
+    fn text(&self) -> String {
+        let mut lines: Vec<String> = self
+            .pruned
+            .iter()
+            .map(|s| format!("pruned {}\t{} rows", s.data_version, s.row_count))
+            .collect();
+        lines.push(format!(
+            "Kept {} snapshots, pruned {} ({} rows deleted).",
+            self.kept.len(),
+            self.pruned.len(),
+            self.deleted_rows
+        ));
+        lines.join("\n")
+    }
+}
+
+pub async fn snapshot_prune(
+    database_type: &str,
+    keep: usize,
+    dry_run: bool,
+) -> Result<SnapshotPrune, String> {
+    let pool = connect(database_type).await?;
+    let snapshots = pool.list_snapshots().await?;
+    let live = pool.latest_live_snapshot().await?;
+    let pruned = db::snapshot::versions_to_prune(&snapshots, keep, live.as_deref());
+    let versions: Vec<String> = pruned.iter().map(|s| s.data_version.clone()).collect();
+    let kept = snapshots
+        .iter()
+        .map(|s| s.data_version.clone())
+        .filter(|version| !versions.contains(version))
+        .collect();
+    let deleted_rows = if dry_run || versions.is_empty() {
+        0
+    } else {
+        match &pool {
+            DbPool::Postgres(pool) => db::snapshot::delete_snapshots_postgres(pool, &versions)
+                .await
+                .map_err(|e| e.to_string()),
+            DbPool::MySql(pool) => db::snapshot::delete_snapshots_mysql(pool, &versions)
+                .await
+                .map_err(|e| e.to_string()),
+        }
+        .map_err(|e| format!("Error deleting snapshots: {e}"))?
+    };
+    Ok(SnapshotPrune {
+        kept,
+        pruned,
+        deleted_rows,
+    })
+}
+
+#[derive(Serialize)]
+pub struct SnapshotExport {
+    pub data_version: String,
+    pub path: PathBuf,
+    /// Rows written, or the rows a dry run would write.
+    pub rows: u64,
+}
+
+impl Report for SnapshotExport {
+    fn text(&self) -> String {
+        format!(
+            "Exported {} rows of {} to {}.",
+            self.rows,
+            self.data_version,
+            self.path.display()
+        )
+    }
+}
+
+fn write_csv(path: &Path, rows: &[Vec<Option<String>>]) -> Result<(), csv::Error> {
+    let mut writer = csv::Writer::from_path(path)?;
+    writer.write_record(POSTAL_CODE_COLUMNS)?;
+    for row in rows {
+        writer.write_record(row.iter().map(|value| value.as_deref().unwrap_or("")))?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+pub async fn snapshot_export(
+    database_type: &str,
+    data_version: &str,
+    out: &Path,
+    dry_run: bool,
+) -> Result<SnapshotExport, String> {
+    let pool = connect(database_type).await?;
+    let count = pool.snapshot_row_count(data_version).await?;
+    if count == 0 {
+        return Err(format!(
+            "No snapshot rows found for data_version={data_version}"
+        ));
+    }
+    let mut report = SnapshotExport {
+        data_version: data_version.to_string(),
+        path: out.to_path_buf(),
+        rows: count as u64,
+    };
+    if dry_run {
+        return Ok(report);
+    }
+
+    let rows = match &pool {
+        DbPool::Postgres(pool) => db::snapshot::snapshot_rows_postgres(pool, data_version)
+            .await
+            .map_err(|e| e.to_string()),
+        DbPool::MySql(pool) => db::snapshot::snapshot_rows_mysql(pool, data_version)
+            .await
+            .map_err(|e| e.to_string()),
+    }
+    .map_err(|e| format!("Error reading snapshot {data_version}: {e}"))?;
+    write_csv(out, &rows).map_err(|e| format!("Error writing {}: {e}", out.display()))?;
+    report.rows = rows.len() as u64;
+    Ok(report)
+}
+
+#[derive(Serialize)]
+pub struct AuditList {
+    pub audits: Vec<AuditEntry>,
+}
+
+impl Report for AuditList {
+    fn text(&self) -> String {
+        if self.audits.is_empty() {
+            return "No audits.".to_string();
+        }
+        self.audits
+            .iter()
+            .map(|a| {
+                let mut line = format!(
+                    "{}\t{}\t{}\tinserted={} updated={} deleted={} total={}\t{}",
+                    a.data_version,
+                    a.run_started_at.format("%Y-%m-%d %H:%M:%S"),
+                    a.status,
+                    a.inserted_count,
+                    a.updated_count,
+                    a.deleted_count,
+                    a.total_count,
+                    a.source_url
+                );
+                if let Some(error) = &a.error_message {
+                    line.push_str(&format!("\terror={error}"));
+                }
+                line
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+pub async fn audit_list(database_type: &str, limit: i64) -> Result<AuditList, String> {
+    let pool = connect(database_type).await?;
+    Ok(AuditList {
+        audits: pool.list_audits(limit).await?,
+    })
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize)]
+pub struct Check {
+    pub name: &'static str,
+    pub ok: bool,
+    pub detail: String,
+}
+
+#[derive(Serialize)]
+pub struct Verification {
+    /// Every check passed; `crawler verify` exits with status 1 otherwise.
+    pub passed: bool,
+    pub checks: Vec<Check>,
+}
+
+impl Report for Verification {
+    fn text(&self) -> String {
+        let mut lines: Vec<String> = self
+            .checks
+            .iter()
+            .map(|c| {
+                let mark = if c.ok { "ok  " } else { "FAIL" };
+                format!("{mark} {}: {}", c.name, c.detail)
+            })
+            .collect();
+        lines.push(
+            if self.passed {
+                "Verification passed."
+            } else {
+                "Verification failed."
+            }
+            .to_string(),
+        );
+        lines.join("\n")
+    }
+}
+
+/// The newest audit whose run left the data complete; its `total_count` is what
+/// postal_codes should hold.
+fn latest_completed(audits: &[AuditEntry]) -> Option<&AuditEntry> {
+    audits
+        .iter()
+        .find(|a| matches!(a.status.as_str(), "success" | "rollback" | STATUS_UNCHANGED))
+}
+
+/// Checks against `audits` (newest first), given the rows in postal_codes and the rows of
+/// the newest snapshot taken by a successful update.
+fn verification_checks(
+    audits: &[AuditEntry],
+    postal_code_count: i64,
+    latest_snapshot: Option<(&AuditEntry, i64)>,
+) -> Vec<Check> {
+    let mut checks = Vec::new();
+    let Some(latest) = audits.first() else {
+        checks.push(Check {
+            name: "latest_run",
+            ok: false,
+            detail: "no update has been audited".to_string(),
+        });
+        return checks;
+    };
+    checks.push(Check {
+        name: "latest_run",
+        ok: latest.status != "failed",
+        detail: match &latest.error_message {
+            Some(error) => format!("{} {}: {error}", latest.data_version, latest.status),
+            None => format!("{} {}", latest.data_version, latest.status),
+        },
+    });
+
+    checks.push(match latest_completed(audits) {
+        Some(completed) => Check {
+            name: "row_count",
+            ok: postal_code_count == completed.total_count,
+            detail: format!(
+                "postal_codes has {postal_code_count} rows, {} recorded {}",
+                completed.data_version, completed.total_count
+            ),
+        },
+        None => Check {
+            name: "row_count",
+            ok: false,
+            detail: "no completed update has been audited".to_string(),
+        },
+    });
+
+    checks.push(match latest_snapshot {
+        Some((audit, rows)) => Check {
+            name: "snapshot",
+            ok: rows == audit.total_count,
+            detail: format!(
+                "snapshot {} has {rows} rows, {} recorded",
+                audit.data_version, audit.total_count
+            ),
+        },
+        None => Check {
+            name: "snapshot",
+            ok: false,
+            detail: "no successful update to compare a snapshot with".to_string(),
+        },
+    });
+    checks
+}
+
+pub async fn verify(database_type: &str) -> Result<Verification, String> {
+    let pool = connect(database_type).await?;
+    let audits = pool.list_audits(VERIFY_AUDIT_WINDOW).await?;
+    let postal_code_count = match &pool {
+        DbPool::Postgres(pool) => db::snapshot::postal_code_count_postgres(pool)
+            .await
+            .map_err(|e| e.to_string()),
+        DbPool::MySql(pool) => db::snapshot::postal_code_count_mysql(pool)
+            .await
+            .map_err(|e| e.to_string()),
+    }
+    .map_err(|e| format!("Error counting postal_codes: {e}"))?;
+    // Unchanged runs and rollbacks take no snapshot of their own.
+    let latest_snapshot = match audits.iter().find(|a| a.status == "success") {
+        Some(audit) => Some((audit, pool.snapshot_row_count(&audit.data_version).await?)),
+        None => None,
+    };
+
+    let checks = verification_checks(&audits, postal_code_count, latest_snapshot);
+    Ok(Verification {
+        passed: checks.iter().all(|c| c.ok),
+        checks,
+    })
+}
+
+impl Report for CacheInvalidation {
+    fn text(&self) -> String {
+        format!(
+            "postal:* keys matched={}, deleted={}",
+            self.matched, self.deleted
+        )
+    }
+}
+
+pub async fn cache_invalidate(dry_run: bool) -> Result<CacheInvalidation, String> {
+    let redis_url = std::env::var("REDIS_URL").map_err(|_| "REDIS_URL is not set".to_string())?;
+    crate::cache::invalidate(&redis_url, dry_run).await
+}
+
+impl Report for RollbackReport {
+    fn text(&self) -> String {
+        match &self.rollback_data_version {
+            Some(rollback_data_version) => format!(
+                "Rollback completed. database_type={}, target_data_version={}, restored_rows={}, data_version={}",
+                self.database_type, self.target_data_version, self.restored_rows, rollback_data_version
+            ),
+            None => format!(
+                "Rollback would restore {} rows. database_type={}, target_data_version={}",
+                self.restored_rows, self.database_type, self.target_data_version
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{verification_checks, AuditEntry};
+
+    fn audit(data_version: &str, status: &str, total_count: i64) -> AuditEntry {
+        AuditEntry {
+            id: 0,
+            data_version: data_version.to_string(),
+            status: status.to_string(),
+            source_url: String::new(),
+            run_started_at: chrono::DateTime::UNIX_EPOCH,
+            run_finished_at: chrono::DateTime::UNIX_EPOCH,
+            records_in_feed: 0,
+            inserted_count: 0,
+            updated_count: 0,
+            deleted_count: 0,
+            total_count,
+            diff_month: None,
+            source_sha256: None,
+            dataset_fingerprint: None,
+            error_message: None,
+        }
+    }
+
+    #[test]
+    fn verification_compares_rows_with_the_latest_completed_run() {
+        let audits = vec![
+            audit("v3", "failed", 0),
+            audit("v2", "unchanged", 120),
+            audit("v1", "success", 120),
+        ];
+        let checks = verification_checks(&audits, 120, Some((&audits[2], 120)));
+        let failed: Vec<&str> = checks.iter().filter(|c| !c.ok).map(|c| c.name).collect();
+        assert_eq!(failed, vec!["latest_run"]);
+
+        let checks = verification_checks(&audits[1..], 119, Some((&audits[2], 100)));
+        let failed: Vec<&str> = checks.iter().filter(|c| !c.ok).map(|c| c.name).collect();
+        assert_eq!(failed, vec!["row_count", "snapshot"]);
+    }
+
+    #[test]
+    fn verification_fails_without_audits() {
+        let checks = verification_checks(&[], 0, None);
+        assert_eq!(checks.len(), 1);
+        assert!(!checks[0].ok);
+    }
+}
@@ -0,0 +1,183 @@
+// `crawler rollback`: restore postal_codes from a snapshot.
+use crate::db::audit::{
+    build_data_version, ensure_audit_table_mysql, ensure_audit_table_postgres,
+    ensure_snapshot_table_mysql, ensure_snapshot_table_postgres, insert_audit_mysql,
+    insert_audit_postgres, DataUpdateAuditRecord,
+};
+use crate::db::schema::{
+    ensure_audit_columns_mysql, ensure_audit_columns_postgres, ensure_postal_code_columns_mysql,
+    ensure_postal_code_columns_postgres, postal_code_column_list,
+};
+use chrono::Timelike;
+use common::audit::ROLLBACK_SOURCE_PREFIX;
+use mysql_async::{params, prelude::Queryable};
+use serde::Serialize;
+
+#[derive(Debug, Serialize)]
+pub struct RollbackReport {
+    pub database_type: String,
+    pub target_data_version: String,
+    /// Version of the rollback audit entry; `None` for a dry run.
+    pub rollback_data_version: Option<String>,
+    /// Rows restored, or the rows a dry run would restore.
+    pub restored_rows: u64,
+}
+
+fn make_rollback_audit_record(
+    target_data_version: &str,
+    restored_count: i64,
+) -> DataUpdateAuditRecord {
+    let now_utc = chrono::Utc::now();
+    let now_local = chrono::Local::now().naive_local();
+    let batch_timestamp = now_local
+        .with_nanosecond(0)
+        .expect("failed to normalize rollback batch timestamp");
+    let rollback_data_version = format!("r{}", build_data_version(now_local));
+
+    DataUpdateAuditRecord {
+        data_version: rollback_data_version,
+        source_url: format!("{ROLLBACK_SOURCE_PREFIX}{target_data_version}"),
+        run_started_at: now_utc,
+        run_finished_at: now_utc,
+        batch_timestamp,
+        records_in_feed: restored_count,
+        inserted_count: restored_count,
+        updated_count: 0,
+        deleted_count: 0,
+        total_count: restored_count,
+        status: "rollback".to_string(),
+        error_message: None,
+        diff_month: None,
+        source_sha256: None,
+        dataset_fingerprint: None,
+    }
+}
+
+async fn rollback_postgres(
+    target_data_version: &str,
+    dry_run: bool,
+) -> Result<(u64, Option<String>), Box<dyn std::error::Error>> {
+    let pool = common::db::postgres_connection().await?;
+    if !dry_run {
+        ensure_audit_table_postgres(&pool).await?;
+        ensure_audit_columns_postgres(&pool).await?;
+        ensure_snapshot_table_postgres(&pool).await?;
+        ensure_postal_code_columns_postgres(&pool).await?;
+    }
+
+    let mut client = pool.get().await?;
+    let snapshot_count: i64 = client
+        .query_one(
+            "SELECT COUNT(*)::BIGINT FROM postal_codes_snapshots WHERE data_version = $1",
+            &[&target_data_version],
+        )
+        .await?
+        .get(0);
+
+    if snapshot_count == 0 {
+        return Err(
+            format!("No snapshot rows found for data_version={target_data_version}").into(),
+        );
+    }
+    if dry_run {
+        return Ok((snapshot_count as u64, None));
+    }
+
+    let tx = client.transaction().await?;
+    tx.execute("DELETE FROM postal_codes", &[]).await?;
+    let columns = postal_code_column_list();
+    let restored = tx
+        .execute(
+            &format!(
+                "INSERT INTO postal_codes ({columns}, created_at, updated_at)
+                SELECT {columns}, created_at, updated_at
+                FROM postal_codes_snapshots
+                WHERE data_version = $1"
+            ),
+            &[&target_data_version],
+        )
+        .await?;
+    tx.commit().await?;
+
+    let audit_record = make_rollback_audit_record(target_data_version, restored as i64);
+    insert_audit_postgres(&pool, &audit_record).await?;
+
+    Ok((restored, Some(audit_record.data_version)))
+}
+
+async fn rollback_mysql(
+    target_data_version: &str,
+    dry_run: bool,
+) -> Result<(u64, Option<String>), Box<dyn std::error::Error>> {
+    let pool = common::db::mysql_connection().await?;
+    if !dry_run {
+        ensure_audit_table_mysql(&pool).await?;
+        ensure_audit_columns_mysql(&pool).await?;
+        ensure_snapshot_table_mysql(&pool).await?;
+        ensure_postal_code_columns_mysql(&pool).await?;
+    }
+
+    let mut conn = pool.get_conn().await?;
+    let snapshot_count = conn
+        .exec_first::<i64, _, _>(
+            "SELECT COUNT(*) FROM postal_codes_snapshots WHERE data_version = :data_version",
+            params! { "data_version" => target_data_version },
+        )
+        .await?
+        .unwrap_or(0);
+
+    if snapshot_count == 0 {
+        return Err(
+            format!("No snapshot rows found for data_version={target_data_version}").into(),
+        );
+    }
+    if dry_run {
+        return Ok((snapshot_count as u64, None));
+    }
+
+    let mut tx = conn.start_transaction(Default::default()).await?;
+    tx.query_drop("DELETE FROM postal_codes").await?;
+    let columns = postal_code_column_list();
+    tx.exec_drop(
+        format!(
+            "INSERT INTO postal_codes ({columns}, created_at, updated_at)
+            SELECT {columns}, created_at, updated_at
+            FROM postal_codes_snapshots
+            WHERE data_version = :data_version"
+        ),
+        params! { "data_version" => target_data_version },
+    )
+    .await?;
+    let restored = tx.affected_rows();
+    tx.commit().await?;
+
+    let audit_record = make_rollback_audit_record(target_data_version, restored as i64);
+    insert_audit_mysql(&pool, &audit_record).await?;
+
+    Ok((restored, Some(audit_record.data_version)))
+}
+
+/// Replaces postal_codes with the snapshot of `target_data_version`. A dry run only counts
+/// the snapshot rows and skips the schema migrations.
+pub async fn rollback(
+    database_type: &str,
+    target_data_version: &str,
+    dry_run: bool,
+) -> Result<RollbackReport, String> {
+    let result = match database_type {
+        "postgres" => rollback_postgres(target_data_version, dry_run).await,
+        "mysql" => rollback_mysql(target_data_version, dry_run).await,
+        other => Err(format!("Unsupported database_type: {other}").into()),
+    };
+    let (restored_rows, rollback_data_version) = result.map_err(|e| {
+        format!(
+            "Rollback failed. database_type={database_type}, target_data_version={target_data_version}, error={e}"
+        )
+    })?;
+    Ok(RollbackReport {
+        database_type: database_type.to_string(),
+        target_data_version: target_data_version.to_string(),
+        rollback_data_version,
+        restored_rows,
+    })
+}
@@ -0,0 +1,581 @@
+// When the daemon runs: a fixed interval or a cron schedule in JST, exponential backoff
+// after failures, random jitter and maintenance blackout windows.
+use chrono::{DateTime, Datelike, Duration, FixedOffset, NaiveDate, NaiveDateTime, Timelike, Utc};
+use serde::Serialize;
+use std::hash::{BuildHasher, Hasher};
+
+/// Japan Post publishes in JST; schedules and blackout windows are read in JST as well.
+fn jst() -> FixedOffset {
+    FixedOffset::east_opt(9 * 3600).expect("valid JST offset")
+}
+
+/// How far ahead a cron expression is searched; `0 0 29 2 *` needs up to 8 years.
+const CRON_SEARCH_DAYS: i64 = 8 * 366;
+
+/// A 5-field cron expression (`minute hour day-of-month month day-of-week`) in JST.
+/// Fields accept `*`, `N`, `A-B`, `*/S`, `A-B/S` and lists; day-of-month also accepts `L`
+/// for the last day of the month, and day-of-week 0 or 7 for Sunday. As in Vixie cron,
+/// a day matches either day field when both are restricted, and a field starting with
+/// `*` (such as `*/2`) counts as unrestricted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CronExpr {
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    last_day: bool,
+    months: u64,
+    weekdays: u64,
+    days_restricted: bool,
+    weekdays_restricted: bool,
+}
+
+fn parse_cron_field(field: &str, min: u32, max: u32) -> Result<u64, String> {
+    let mut bits = 0u64;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => {
+                let step: u32 = step
+                    .parse()
+                    .ok()
+                    .filter(|step| *step > 0)
+                    .ok_or_else(|| format!("invalid step in {part:?}"))?;
+                (range, step)
+            }
+            None => (part, 1),
+        };
+        let (start, end) = if range == "*" {
+            (min, max)
+        } else if let Some((start, end)) = range.split_once('-') {
+            let start = start
+                .parse()
+                .map_err(|_| format!("invalid range {part:?}"))?;
+            let end = end.parse().map_err(|_| format!("invalid range {part:?}"))?;
+            (start, end)
+        } else {
+            let value: u32 = range
+                .parse()
+                .map_err(|_| format!("invalid value {part:?}"))?;
+            // `N/S` means from N to the end of the field.
+            (value, if part.contains('/') { max } else { value })
+        };
+        if start < min || end > max || start > end {
+            return Err(format!("{part:?} is outside {min}-{max}"));
+        }
+        for value in (start..=end).step_by(step as usize) {
+            bits |= 1 << value;
+        }
+    }
+    Ok(bits)
+}
+
+impl CronExpr {
+    pub fn parse(raw: &str) -> Result<Self, String> {
+        let fields: Vec<&str> = raw.split_whitespace().collect();
+        let [minute, hour, day, month, weekday] = fields[..] else {
+            return Err(format!("cron expression needs 5 fields: {raw:?}"));
+        };
+        let invalid = |e: String| format!("invalid cron expression {raw:?}: {e}");
+
+        let day_parts: Vec<&str> = day.split(',').collect();
+        let last_day = day_parts.contains(&"L");
+        let other_days: Vec<&str> = day_parts.into_iter().filter(|p| *p != "L").collect();
+        let days = if other_days.is_empty() {
+            0
+        } else {
+            parse_cron_field(&other_days.join(","), 1, 31).map_err(invalid)?
+        };
+        let mut weekdays = parse_cron_field(weekday, 0, 7).map_err(invalid)?;
+        if weekdays & (1 << 7) != 0 {
+            weekdays |= 1;
+        }
+        Ok(CronExpr {
+            minutes: parse_cron_field(minute, 0, 59).map_err(invalid)?,
+            hours: parse_cron_field(hour, 0, 23).map_err(invalid)?,
+            days,
+            last_day,
+            months: parse_cron_field(month, 1, 12).map_err(invalid)?,
+            weekdays,
+            days_restricted: !day.starts_with('*'),
+            weekdays_restricted: !weekday.starts_with('*'),
+        })
+    }
+
+    fn matches_date(&self, date: NaiveDate) -> bool {
+        if self.months & (1 << date.month()) == 0 {
+            return false;
+        }
+        let is_last_day = date
+            .succ_opt()
+            .is_none_or(|next| next.month() != date.month());
+        let day = self.days & (1 << date.day()) != 0 || (self.last_day && is_last_day);
+        let weekday = self.weekdays & (1 << date.weekday().num_days_from_sunday()) != 0;
+        // An unrestricted field still carries its mask (all values for `*`, every other
+        // value for `*/2`), so ANDing the two covers every case but the restricted pair.
+        if self.days_restricted && self.weekdays_restricted {
+            day || weekday
+        } else {
+            day && weekday
+        }
+    }
+
+    /// The first matching minute strictly after `after`.
+    pub fn next_after(&self, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let local = after.with_timezone(&jst()).naive_local();
+        let start = local.with_second(0)?.with_nanosecond(0)? + Duration::minutes(1);
+        for day in 0..CRON_SEARCH_DAYS {
+            let date = start.date() + Duration::days(day);
+            if !self.matches_date(date) {
+                continue;
+            }
+            let first_minute = if day == 0 {
+                start.hour() * 60 + start.minute()
+            } else {
+                0
+            };
+            for minute_of_day in first_minute..24 * 60 {
+                let (hour, minute) = (minute_of_day / 60, minute_of_day % 60);
+                if self.hours & (1 << hour) != 0 && self.minutes & (1 << minute) != 0 {
+                    let at = date.and_hms_opt(hour, minute, 0)?;
+                    return Some(jst_to_utc(at));
+                }
+            }
+        }
+        None
+    }
+}
+
+fn jst_to_utc(at: NaiveDateTime) -> DateTime<Utc> {
+    (at - Duration::hours(9)).and_utc()
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Trigger {
+    /// Run again this many seconds after a cycle completes (`CRAWLER_INTERVAL_SECONDS`).
+    Interval(u64),
+    Cron(CronExpr),
+}
+
+impl Trigger {
+    /// `CRAWLER_SCHEDULE`: a daily `HH:MM` in JST or a cron expression.
+    pub fn parse(raw: &str) -> Result<Self, String> {
+        let raw = raw.trim();
+        if let Some((hour, minute)) = raw.split_once(':') {
+            let valid = |value: &str, max: u32| value.parse::<u32>().ok().filter(|v| *v <= max);
+            return match (valid(hour, 23), valid(minute, 59)) {
+                (Some(hour), Some(minute)) => {
+                    CronExpr::parse(&format!("{minute} {hour} * * *")).map(Trigger::Cron)
+                }
+                _ => Err(format!("CRAWLER_SCHEDULE time must be HH:MM: {raw:?}")),
+            };
+        }
+        CronExpr::parse(raw).map(Trigger::Cron)
+    }
+}
+
+/// A window in JST during which the crawler must not start a cycle.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Blackout {
+    /// `HH:MM-HH:MM` every day; wraps past midnight when the end is not after the start.
+    Daily { start: u32, end: u32 },
+    /// `Sun HH:MM-HH:MM`: the same, only when the window starts on that weekday.
+    Weekly {
+        weekday: chrono::Weekday,
+        start: u32,
+        end: u32,
+    },
+    /// `YYYY-MM-DDTHH:MM/YYYY-MM-DDTHH:MM`: a one-off window.
+    Once {
+        start: NaiveDateTime,
+        end: NaiveDateTime,
+    },
+}
+
+fn parse_minute_of_day(raw: &str) -> Option<u32> {
+    let (hour, minute) = raw.split_once(':')?;
+    let hour: u32 = hour.parse().ok().filter(|h| *h <= 24)?;
+    let minute: u32 = minute.parse().ok().filter(|m| *m <= 59)?;
+    Some(hour * 60 + minute).filter(|m| *m <= 24 * 60)
+}
+
+impl Blackout {
+    pub fn parse(raw: &str) -> Result<Self, String> {
+        let raw = raw.trim();
+        let invalid = || {
+            format!(
+                "invalid blackout window {raw:?}: expected HH:MM-HH:MM, Sun HH:MM-HH:MM \
+                 or YYYY-MM-DDTHH:MM/YYYY-MM-DDTHH:MM"
+            )
+        };
+        if let Some((start, end)) = raw.split_once('/') {
+            let parse = |value: &str| NaiveDateTime::parse_from_str(value.trim(), "%Y-%m-%dT%H:%M");
+            let (start, end) = (
+                parse(start).map_err(|_| invalid())?,
+                parse(end).map_err(|_| invalid())?,
+            );
+            if end <= start {
+                return Err(invalid());
+            }
+            return Ok(Blackout::Once { start, end });
+        }
+
+        let (weekday, window) = match raw.split_once(' ') {
+            Some((weekday, window)) => (
+                Some(weekday.parse::<chrono::Weekday>().map_err(|_| invalid())?),
+                window.trim(),
+            ),
+            None => (None, raw),
+        };
+        let (start, end) = window.split_once('-').ok_or_else(invalid)?;
+        let start = parse_minute_of_day(start).ok_or_else(invalid)?;
+        let end = parse_minute_of_day(end).ok_or_else(invalid)?;
+        if start == end {
+            return Err(invalid());
+        }
+        Ok(match weekday {
+            Some(weekday) => Blackout::Weekly {
+                weekday,
+                start,
+                end,
+            },
+            None => Blackout::Daily { start, end },
+        })
+    }
+
+    /// When the window containing `at` ends, or `None` when `at` is outside it.
+    fn end_of_window_containing(&self, at: NaiveDateTime) -> Option<NaiveDateTime> {
+        let (start, end, weekday) = match self {
+            Blackout::Once { start, end } => {
+                return (*start <= at && at < *end).then_some(*end);
+            }
+            Blackout::Daily { start, end } => (*start, *end, None),
+            Blackout::Weekly {
+                weekday,
+                start,
+                end,
+            } => (*start, *end, Some(*weekday)),
+        };
+        let date = at.date();
+        let minute = at.hour() * 60 + at.minute();
+        let starts_on = |date: NaiveDate| weekday.is_none_or(|weekday| date.weekday() == weekday);
+        let at_minute = |date: NaiveDate, minute_of_day: u32| {
+            date.and_hms_opt(0, 0, 0)
+                .map(|midnight| midnight + Duration::minutes(minute_of_day.into()))
+        };
+        if start < end {
+            (starts_on(date) && start <= minute && minute < end)
+                .then(|| at_minute(date, end))
+                .flatten()
+        } else if minute >= start && starts_on(date) {
+            at_minute(date.succ_opt()?, end)
+        } else if minute < end && starts_on(date.pred_opt()?) {
+            at_minute(date, end)
+        } else {
+            None
+        }
+    }
+}
+
+pub fn parse_blackouts(raw: &str) -> Result<Vec<Blackout>, String> {
+    raw.split(',')
+        .filter(|entry| !entry.trim().is_empty())
+        .map(Blackout::parse)
+        .collect()
+}
+
+/// Delay after the `attempt`-th consecutive failure: `initial * 2^(attempt - 1)`, capped.
+pub fn backoff_seconds(attempt: u32, initial: u64, max: u64) -> u64 {
+    let factor = 1u64
+        .checked_shl(attempt.saturating_sub(1))
+        .unwrap_or(u64::MAX);
+    initial.saturating_mul(factor).min(max)
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Schedule {
+    /// `CRAWLER_SCHEDULE`, or `every Ns` for the interval fallback.
+    pub schedule: String,
+    #[serde(skip)]
+    pub trigger: Trigger,
+    pub retry_initial_seconds: u64,
+    pub retry_max_seconds: u64,
+    pub jitter_seconds: u64,
+    /// `CRAWLER_BLACKOUT` entries.
+    pub blackout: Vec<String>,
+    #[serde(skip)]
+    pub blackouts: Vec<Blackout>,
+}
+
+fn env_seconds(name: &str, default: u64) -> Result<u64, String> {
+    match std::env::var(name) {
+        Ok(value) if !value.trim().is_empty() => value
+            .trim()
+            .parse()
+            .map_err(|_| format!("{name} must be a number")),
+        _ => Ok(default),
+    }
+}
+
+impl Schedule {
+    pub fn from_env() -> Result<Self, String> {
+        let (schedule, trigger) = match std::env::var("CRAWLER_SCHEDULE") {
+            Ok(raw) if !raw.trim().is_empty() => (raw.trim().to_string(), Trigger::parse(&raw)?),
+            _ => {
+                // Default sleep duration: 24 hours (in seconds)
+                let seconds = env_seconds("CRAWLER_INTERVAL_SECONDS", 86400)?;
+                (format!("every {seconds}s"), Trigger::Interval(seconds))
+            }
+        };
+        let blackout_raw = std::env::var("CRAWLER_BLACKOUT").unwrap_or_default();
+        let blackouts = parse_blackouts(&blackout_raw)?;
+        let retry_initial_seconds = env_seconds("CRAWLER_RETRY_INITIAL_SECONDS", 300)?;
+        let retry_max_seconds = env_seconds("CRAWLER_RETRY_MAX_SECONDS", 21600)?;
+        if retry_initial_seconds == 0 || retry_max_seconds < retry_initial_seconds {
+            return Err(
+                "CRAWLER_RETRY_INITIAL_SECONDS must be positive and at most CRAWLER_RETRY_MAX_SECONDS"
+                    .to_string(),
+            );
+        }
+        Ok(Schedule {
+            schedule,
+            trigger,
+            retry_initial_seconds,
+            retry_max_seconds,
+            jitter_seconds: env_seconds("CRAWLER_JITTER_SECONDS", 0)?,
+            blackout: blackout_raw
+                .split(',')
+                .map(str::trim)
+                .filter(|entry| !entry.is_empty())
+                .map(str::to_string)
+                .collect(),
+            blackouts,
+        })
+    }
+
+    /// Moves `at` past every blackout window it falls into.
+    pub fn outside_blackouts(&self, at: DateTime<Utc>) -> DateTime<Utc> {
+        let mut local = at.with_timezone(&jst()).naive_local();
+        // Adjacent windows push `at` along; the bound only guards against a bad config.
+        for _ in 0..64 {
+            let end = self
+                .blackouts
+                .iter()
+                .filter_map(|blackout| blackout.end_of_window_containing(local))
+                .max();
+            match end {
+                Some(end) => local = end,
+                None => break,
+            }
+        }
+        if local == at.with_timezone(&jst()).naive_local() {
+            at
+        } else {
+            jst_to_utc(local)
+        }
+    }
+
+    /// Up to `jitter_seconds`, so several crawlers do not hit Japan Post at the same second.
+    fn jitter(&self) -> Duration {
+        if self.jitter_seconds == 0 {
+            return Duration::zero();
+        }
+        // RandomState is seeded per process and per call; good enough for spreading load.
+        let random = std::collections::hash_map::RandomState::new()
+            .build_hasher()
+            .finish();
+        Duration::seconds((random % (self.jitter_seconds + 1)) as i64)
+    }
+
+    fn next_run_with(&self, completed_at: DateTime<Utc>, jitter: Duration) -> DateTime<Utc> {
+        let due = match &self.trigger {
+            Trigger::Interval(seconds) => completed_at + Duration::seconds(*seconds as i64),
+            // A cron expression always matches within the search horizon once parsed,
+            // except for impossible dates such as `0 0 31 2 *`.
+            Trigger::Cron(cron) => cron
+                .next_after(completed_at)
+                .unwrap_or(completed_at + Duration::days(1)),
+        };
+        self.outside_blackouts(due + jitter)
+    }
+
+    /// When the next scheduled cycle starts after one completed at `completed_at`.
+    pub fn next_run(&self, completed_at: DateTime<Utc>) -> DateTime<Utc> {
+        self.next_run_with(completed_at, self.jitter())
+    }
+
+    fn retry_at_with(
+        &self,
+        attempt: u32,
+        failed_at: DateTime<Utc>,
+        jitter: Duration,
+    ) -> DateTime<Utc> {
+        let delay = backoff_seconds(attempt, self.retry_initial_seconds, self.retry_max_seconds);
+        self.outside_blackouts(failed_at + Duration::seconds(delay as i64) + jitter)
+    }
+
+    /// The next `count` scheduled runs after `from`, without jitter, for `run --dry-run`.
+    pub fn upcoming(&self, from: DateTime<Utc>, count: usize) -> Vec<DateTime<Utc>> {
+        let mut runs = Vec::with_capacity(count);
+        let mut at = from;
+        for _ in 0..count {
+            at = self.next_run_with(at, Duration::zero());
+            runs.push(at);
+        }
+        runs
+    }
+
+    /// When to try again after the `attempt`-th consecutive failure. Retries never go past
+    /// the next scheduled run; `true` means the wake-up is that scheduled run.
+    pub fn after_failure(&self, attempt: u32, failed_at: DateTime<Utc>) -> (DateTime<Utc>, bool) {
+        let jitter = self.jitter();
+        let retry = self.retry_at_with(attempt, failed_at, jitter);
+        let scheduled = self.next_run_with(failed_at, jitter);
+        if scheduled <= retry {
+            (scheduled, true)
+        } else {
+            (retry, false)
+        }
+    }
+}
+
+/// `at` as JST, for log lines and reports.
+pub fn format_jst(at: DateTime<Utc>) -> String {
+    at.with_timezone(&jst())
+        .format("%Y-%m-%d %H:%M:%S JST")
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{backoff_seconds, parse_blackouts, Blackout, CronExpr, Schedule, Trigger};
+    use chrono::{DateTime, Duration, NaiveDate, Utc};
+
+    /// A JST wall-clock time as UTC.
+    fn jst(y: i32, m: u32, d: u32, h: u32, min: u32) -> DateTime<Utc> {
+        let local = NaiveDate::from_ymd_opt(y, m, d)
+            .unwrap()
+            .and_hms_opt(h, min, 0)
+            .unwrap();
+        (local - Duration::hours(9)).and_utc()
+    }
+
+    fn schedule(trigger: &str, blackout: &str) -> Schedule {
+        Schedule {
+            schedule: trigger.to_string(),
+            trigger: Trigger::parse(trigger).unwrap(),
+            retry_initial_seconds: 300,
+            retry_max_seconds: 3600,
+            jitter_seconds: 0,
+            blackout: Vec::new(),
+            blackouts: parse_blackouts(blackout).unwrap(),
+        }
+    }
+
+    #[test]
+    fn cron_finds_the_next_matching_minute_in_jst() {
+        let cron = CronExpr::parse("30 6 * * *").unwrap();
+        assert_eq!(
+            cron.next_after(jst(2026, 3, 1, 5, 0)),
+            Some(jst(2026, 3, 1, 6, 30))
+        );
+        assert_eq!(
+            cron.next_after(jst(2026, 3, 1, 6, 30)),
+            Some(jst(2026, 3, 2, 6, 30))
+        );
+
+        let every_15 = CronExpr::parse("*/15 9-17 * * 1-5").unwrap();
+        // 2026-03-07 is a Saturday.
+        assert_eq!(
+            every_15.next_after(jst(2026, 3, 6, 17, 50)),
+            Some(jst(2026, 3, 9, 9, 0))
+        );
+    }
+
+    #[test]
+    fn cron_last_day_of_month_and_either_day_field() {
+        let month_end = CronExpr::parse("0 7 L * *").unwrap();
+        assert_eq!(
+            month_end.next_after(jst(2026, 2, 10, 0, 0)),
+            Some(jst(2026, 2, 28, 7, 0))
+        );
+        assert_eq!(
+            month_end.next_after(jst(2028, 2, 28, 8, 0)),
+            Some(jst(2028, 2, 29, 7, 0))
+        );
+
+        // The 1st of the month or any Sunday (7 is Sunday too).
+        let either = CronExpr::parse("0 0 1 * 7").unwrap();
+        assert_eq!(
+            either.next_after(jst(2026, 3, 2, 0, 0)),
+            Some(jst(2026, 3, 8, 0, 0))
+        );
+
+        // `*/2` is unrestricted, so both fields must match: an odd day that is a Monday.
+        // 2026-03-02 is an even Monday; 2026-03-09 is the next odd one.
+        let both = CronExpr::parse("0 6 */2 * 1").unwrap();
+        assert_eq!(
+            both.next_after(jst(2026, 3, 1, 7, 0)),
+            Some(jst(2026, 3, 9, 6, 0))
+        );
+    }
+
+    #[test]
+    fn invalid_schedules_are_rejected() {
+        assert!(Trigger::parse("25:00").is_err());
+        assert!(Trigger::parse("0 6 * *").is_err());
+        assert!(CronExpr::parse("60 * * * *").is_err());
+        assert!(CronExpr::parse("*/0 * * * *").is_err());
+        assert_eq!(
+            Trigger::parse("06:30"),
+            CronExpr::parse("30 6 * * *").map(Trigger::Cron)
+        );
+    }
+
+    #[test]
+    fn blackouts_push_runs_to_the_end_of_the_window() {
+        let nightly = schedule("02:00", "01:00-04:00");
+        assert_eq!(
+            nightly.next_run(jst(2026, 3, 1, 12, 0)),
+            jst(2026, 3, 2, 4, 0)
+        );
+
+        // Wraps midnight, only on Sundays (2026-03-01 is a Sunday).
+        let sunday = parse_blackouts("Sun 23:00-02:00").unwrap();
+        let s = Schedule {
+            blackouts: sunday,
+            ..schedule("03:00", "")
+        };
+        assert_eq!(
+            s.outside_blackouts(jst(2026, 3, 2, 1, 0)),
+            jst(2026, 3, 2, 2, 0)
+        );
+        assert_eq!(
+            s.outside_blackouts(jst(2026, 3, 3, 1, 0)),
+            jst(2026, 3, 3, 1, 0)
+        );
+
+        let once = schedule("02:00", "2026-12-31T20:00/2027-01-01T09:00");
+        assert_eq!(
+            once.next_run(jst(2026, 12, 31, 12, 0)),
+            jst(2027, 1, 1, 9, 0)
+        );
+        assert!(Blackout::parse("03:00-03:00").is_err());
+        assert!(Blackout::parse("2026-01-02T00:00/2026-01-01T00:00").is_err());
+    }
+
+    #[test]
+    fn failures_back_off_until_the_next_scheduled_run() {
+        assert_eq!(backoff_seconds(1, 300, 3600), 300);
+        assert_eq!(backoff_seconds(3, 300, 3600), 1200);
+        assert_eq!(backoff_seconds(10, 300, 3600), 3600);
+        assert_eq!(backoff_seconds(200, 300, 3600), 3600);
+
+        let daily = schedule("06:00", "");
+        let failed_at = jst(2026, 3, 1, 6, 0);
+        assert_eq!(
+            daily.after_failure(2, failed_at),
+            (failed_at + Duration::seconds(600), false)
+        );
+        let late = jst(2026, 3, 2, 5, 50);
+        assert_eq!(daily.after_failure(5, late), (jst(2026, 3, 2, 6, 0), true));
+    }
+}
@@ -1,17 +1,28 @@
 use chrono::Local;
-use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 
 static QUERY_COUNTER: AtomicUsize = AtomicUsize::new(1);
+static TO_STDERR: AtomicBool = AtomicBool::new(false);
+
+/// Sends log lines to stderr, keeping stdout for `--output json` reports.
+pub fn log_to_stderr() {
+    TO_STDERR.store(true, Ordering::SeqCst);
+}
 
 pub fn log_with_timestamp(args: std::fmt::Arguments) {
     let current_time = Local::now();
     let query_number = QUERY_COUNTER.fetch_add(1, Ordering::SeqCst);
-    println!(
+    let line = format!(
         "[{}] [Number #{}] {}",
         current_time.format("%Y-%m-%d %H:%M:%S"),
         query_number,
         args
     );
+    if TO_STDERR.load(Ordering::SeqCst) {
+        eprintln!("{line}");
+    } else {
+        println!("{line}");
+    }
 }
 
 #[macro_export]